@@ -0,0 +1,933 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Access log module which records every request once its response is completed.
+//!
+
+use chrono::{DateTime, Local, SecondsFormat};
+use clap::ValueEnum;
+use flate2::{write::GzEncoder, Compression};
+use hyper::{header, Body, Method, Request, Response, StatusCode, Uri, Version};
+use lazy_static::lazy_static;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::{
+    handler::RequestHandlerOpts, request_id::RequestId, response, Context as _, Error, Result,
+};
+
+/// Timestamp format of the Common and Combined Log Formats.
+const CLF_TIME_FORMAT: &str = "%d/%b/%Y:%H:%M:%S %z";
+
+/// Access log entries format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// Common Log Format.
+    Common,
+    /// Combined Log Format.
+    Combined,
+    /// One JSON object per line.
+    Json,
+    /// Custom template with `{field}` placeholders.
+    Template(Vec<Segment>),
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "common" => Ok(Self::Common),
+            "combined" => Ok(Self::Combined),
+            "json" => Ok(Self::Json),
+            template => parse_template(template).map(Self::Template),
+        }
+    }
+}
+
+/// A piece of a custom access log template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Text written as is.
+    Text(String),
+    /// A placeholder replaced by a field value.
+    Field(Field),
+}
+
+/// Fields available to custom access log templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Request time in RFC 3339 format.
+    Time,
+    /// Request time in the Common Log Format.
+    TimeLocal,
+    /// Client IP address.
+    RemoteAddr,
+    /// Request method.
+    Method,
+    /// Request URI.
+    Uri,
+    /// Request HTTP protocol version.
+    Protocol,
+    /// Request line like `GET /index.html HTTP/1.1`.
+    Request,
+    /// Response status code.
+    Status,
+    /// Response body bytes sent.
+    Bytes,
+    /// Time in milliseconds elapsed until the response was completed.
+    DurationMs,
+    /// Request `Referer` header.
+    Referer,
+    /// Request `User-Agent` header.
+    UserAgent,
+    /// Request `Host` header (virtual host).
+    Host,
+    /// Path of the served file.
+    File,
+    /// Response `Content-Encoding` header.
+    ContentEncoding,
+    /// Request ID.
+    RequestId,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name {
+            "time" => Self::Time,
+            "time_local" => Self::TimeLocal,
+            "remote_addr" => Self::RemoteAddr,
+            "method" => Self::Method,
+            "uri" => Self::Uri,
+            "protocol" => Self::Protocol,
+            "request" => Self::Request,
+            "status" => Self::Status,
+            "bytes" => Self::Bytes,
+            "duration_ms" => Self::DurationMs,
+            "referer" => Self::Referer,
+            "user_agent" => Self::UserAgent,
+            "host" => Self::Host,
+            "file" => Self::File,
+            "content_encoding" => Self::ContentEncoding,
+            "request_id" => Self::RequestId,
+            _ => return None,
+        };
+        Some(field)
+    }
+}
+
+/// Parses a custom template like `{remote_addr} "{request}" {status}`.
+fn parse_template(template: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            segments.push(Segment::Text(rest[..start].to_owned()));
+        }
+        let Some(end) = rest[start..].find('}') else {
+            bail!("access log template placeholder is not closed: \"{template}\"");
+        };
+        let name = &rest[start + 1..start + end];
+        match Field::from_name(name) {
+            Some(field) => segments.push(Segment::Field(field)),
+            None => bail!("unknown access log template placeholder \"{{{name}}}\""),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_owned()));
+    }
+    if !segments.iter().any(|s| matches!(s, Segment::Field(_))) {
+        bail!("access log format \"{template}\" is neither a known format nor a template with placeholders");
+    }
+    Ok(segments)
+}
+
+/// Interval of the time-based access log file rotation.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RotateInterval {
+    /// No time-based rotation.
+    Never,
+    /// Rotate once a new hour starts.
+    Hourly,
+    /// Rotate once a new day starts.
+    Daily,
+}
+
+impl RotateInterval {
+    /// Returns a key of the period containing the given time.
+    fn period(self, time: DateTime<Local>) -> Option<String> {
+        match self {
+            Self::Never => None,
+            Self::Hourly => Some(time.format("%Y%m%d%H").to_string()),
+            Self::Daily => Some(time.format("%Y%m%d").to_string()),
+        }
+    }
+}
+
+/// Access log file rotation options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// Maximum file size in bytes, zero disables the size-based rotation.
+    pub size: u64,
+    /// Time-based rotation interval.
+    pub interval: RotateInterval,
+    /// Number of rotated files to keep.
+    pub keep: usize,
+    /// Compress the rotated files using gzip.
+    pub compress: bool,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            interval: RotateInterval::Never,
+            keep: 7,
+            compress: false,
+        }
+    }
+}
+
+/// Messages handled by the access log writer thread.
+enum Message {
+    /// A formatted entry.
+    Entry(String),
+    /// Reopen the access log file.
+    Reopen,
+}
+
+/// Maximum number of entries waiting to be written, the newer entries are dropped beyond it.
+const QUEUE_CAPACITY: usize = 8192;
+
+lazy_static! {
+    /// Writers of the access log files, shared across config reloads and reopened on demand.
+    static ref FILE_WRITERS: Mutex<Vec<Weak<Writer>>> = Mutex::default();
+}
+
+/// Reopens every access log file, e.g. once an external tool like `logrotate` moved them.
+pub fn reopen() {
+    let mut writers = FILE_WRITERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    writers.retain(|writer| match writer.upgrade() {
+        Some(writer) => writer.sender.send(Message::Reopen).is_ok(),
+        None => false,
+    });
+}
+
+/// The access log configured for a request handler.
+pub struct AccessLog {
+    format: AccessLogFormat,
+    writer: Arc<Writer>,
+}
+
+impl AccessLog {
+    /// Creates an access log which appends its entries to a file or to the standard error output.
+    /// The writer of a file is shared with the other access logs of the same file and rotation options.
+    pub fn new(format: AccessLogFormat, file: Option<&Path>, rotation: Rotation) -> Result<Self> {
+        let writer = match file {
+            Some(path) => file_writer(path, rotation)?,
+            None => Arc::new(Writer::spawn(
+                Output::Stderr(BufWriter::new(io::stderr())),
+                None,
+            )?),
+        };
+        Ok(Self { format, writer })
+    }
+}
+
+/// Returns the writer of an access log file, spawning it unless one with the same rotation options exists.
+fn file_writer(path: &Path, rotation: Rotation) -> Result<Arc<Writer>> {
+    let mut writers = FILE_WRITERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    writers.retain(|writer| writer.strong_count() > 0);
+    let file = (path.to_owned(), rotation);
+    if let Some(writer) = writers
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|writer| writer.file.as_ref() == Some(&file))
+    {
+        return Ok(writer);
+    }
+
+    let out = LogFile::open(path.to_owned(), rotation)
+        .with_context(|| format!("unable to open the access log file {}", path.display()))?;
+    let writer = Arc::new(Writer::spawn(Output::File(out), Some(file))?);
+    writers.push(Arc::downgrade(&writer));
+    Ok(writer)
+}
+
+/// The thread writing the access log entries so requests never wait on the disk.
+struct Writer {
+    /// The file path and rotation options, `None` for the standard error output.
+    file: Option<(PathBuf, Rotation)>,
+    sender: mpsc::SyncSender<Message>,
+    /// Number of entries dropped since the queue was full.
+    dropped: Arc<AtomicU64>,
+}
+
+impl Writer {
+    /// Spawns the writer thread, which stops once the writer is dropped.
+    fn spawn(mut out: Output, file: Option<(PathBuf, Rotation)>) -> Result<Self> {
+        let (sender, rx) = mpsc::sync_channel::<Message>(QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_entries = dropped.clone();
+        std::thread::Builder::new()
+            .name("sws-access-log".into())
+            .spawn(move || {
+                while let Ok(msg) = rx.recv() {
+                    let mut result = out.handle(msg);
+                    // Batch the entries already queued before flushing
+                    while let (Ok(()), Ok(msg)) = (&result, rx.try_recv()) {
+                        result = out.handle(msg);
+                    }
+                    if let Err(err) = result.and_then(|_| out.flush()) {
+                        tracing::error!("unable to write the access log: {err}");
+                    }
+                    let dropped = dropped_entries.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        tracing::warn!(
+                            "access log: {dropped} entries dropped since the writer can't keep up"
+                        );
+                    }
+                }
+            })
+            .with_context(|| "unable to spawn the access log writer thread")?;
+        Ok(Self {
+            file,
+            sender,
+            dropped,
+        })
+    }
+
+    /// Queues an entry, which is dropped if the queue is full.
+    fn write(&self, line: String) {
+        // The thread only goes away along with the writer
+        if let Err(mpsc::TrySendError::Full(_)) = self.sender.try_send(Message::Entry(line)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Destination of the access log entries.
+enum Output {
+    Stderr(BufWriter<io::Stderr>),
+    File(LogFile),
+}
+
+impl Output {
+    fn handle(&mut self, msg: Message) -> io::Result<()> {
+        match (self, msg) {
+            (Self::Stderr(out), Message::Entry(line)) => out.write_all(line.as_bytes()),
+            (Self::File(file), Message::Entry(line)) => file.write(&line),
+            (Self::Stderr(_), Message::Reopen) => Ok(()),
+            (Self::File(file), Message::Reopen) => file.reopen(),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stderr(out) => out.flush(),
+            Self::File(file) => file.file.flush(),
+        }
+    }
+}
+
+/// An access log file rotated by size and time.
+struct LogFile {
+    path: PathBuf,
+    rotation: Rotation,
+    file: BufWriter<File>,
+    size: u64,
+    period: Option<String>,
+    /// The thread compressing the last rotated file if any.
+    compression: Option<JoinHandle<()>>,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, rotation: Rotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let meta = file.metadata()?;
+        // An existing file belongs to the period it was last written in
+        let modified = meta
+            .modified()
+            .map(DateTime::<Local>::from)
+            .unwrap_or_else(|_| Local::now());
+        Ok(Self {
+            period: rotation.interval.period(modified),
+            size: meta.len(),
+            file: BufWriter::new(file),
+            path,
+            rotation,
+            compression: None,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64;
+        let period = self.rotation.interval.period(Local::now());
+        let oversized = self.rotation.size > 0 && self.size + len > self.rotation.size;
+        if self.size > 0 && (oversized || period != self.period) {
+            self.rotate()?;
+        }
+        self.period = period;
+        self.file.write_all(line.as_bytes())?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Moves the current file to `<path>.1` shifting the older ones and opens a new file.
+    /// The moved file is compressed on its own thread so the entries keep being written meanwhile.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        // The rotated files can't be shifted while one of them is being compressed
+        self.wait_for_compression();
+        let rotated = rotate_files(&self.path, self.rotation.keep)?;
+        *self = Self::open(self.path.clone(), self.rotation)?;
+        if let Some(rotated) = rotated.filter(|_| self.rotation.compress) {
+            let compression = std::thread::Builder::new()
+                .name("sws-access-log-gzip".into())
+                .spawn(move || {
+                    if let Err(err) = compress_file(&rotated) {
+                        tracing::error!(
+                            "unable to compress the access log file {}: {err}",
+                            rotated.display()
+                        );
+                    }
+                });
+            match compression {
+                Ok(compression) => self.compression = Some(compression),
+                Err(err) => tracing::error!("unable to compress the access log file: {err}"),
+            }
+        }
+        tracing::debug!("access log file {} rotated", self.path.display());
+        Ok(())
+    }
+
+    /// Waits until the last rotated file is compressed.
+    fn wait_for_compression(&mut self) {
+        if let Some(compression) = self.compression.take() {
+            let _ = compression.join();
+        }
+    }
+
+    /// Opens the file path again so a file moved away is no longer held.
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let compression = self.compression.take();
+        *self = Self::open(self.path.clone(), self.rotation)?;
+        self.compression = compression;
+        tracing::info!("access log file {} reopened", self.path.display());
+        Ok(())
+    }
+}
+
+/// Returns the path of the rotated file number `n` like `access.log.1` or `access.log.1.gz`.
+fn rotated_path(path: &Path, n: usize, gz: bool) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    if gz {
+        name.push(".gz");
+    }
+    PathBuf::from(name)
+}
+
+/// Shifts the rotated files keeping `keep` of them at most and moves the current file in.
+/// It returns the path the current file was moved to, if kept.
+fn rotate_files(path: &Path, keep: usize) -> io::Result<Option<PathBuf>> {
+    if keep == 0 {
+        return remove_file(path).map(|_| None);
+    }
+    for gz in [false, true] {
+        remove_file(&rotated_path(path, keep, gz))?;
+        for n in (1..keep).rev() {
+            let from = rotated_path(path, n, gz);
+            if from.exists() {
+                fs::rename(&from, rotated_path(path, n + 1, gz))?;
+            }
+        }
+    }
+
+    let rotated = rotated_path(path, 1, false);
+    fs::rename(path, &rotated)?;
+    Ok(Some(rotated))
+}
+
+/// Compresses a file into `<path>.gz` and removes it.
+fn compress_file(path: &Path) -> io::Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut input = File::open(path)?;
+    let mut output = GzEncoder::new(File::create(gz_path)?, Compression::default());
+    io::copy(&mut input, &mut output)?;
+    output.finish()?;
+    fs::remove_file(path)
+}
+
+/// Removes a file which may not exist.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Initializes the access log.
+pub(crate) fn init(
+    enabled: bool,
+    format: &str,
+    file: Option<&Path>,
+    rotation: Rotation,
+    handler_opts: &mut RequestHandlerOpts,
+) -> Result {
+    server_info!("access log: enabled={enabled}");
+    if !enabled {
+        return Ok(());
+    }
+
+    let log = AccessLog::new(format.parse()?, file, rotation)?;
+    server_info!(
+        "access log: format={format}, output={}",
+        file.map_or_else(|| "stderr".to_owned(), |f| f.display().to_string())
+    );
+    if file.is_some() {
+        server_info!(
+            "access log rotation: size={}, interval={:?}, keep={}, compress={}",
+            rotation.size,
+            rotation.interval,
+            rotation.keep,
+            rotation.compress
+        );
+    }
+    handler_opts.access_log = Some(Arc::new(log));
+    Ok(())
+}
+
+/// The path of the file served by a response, kept in the response extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct ServedFile(pub(crate) PathBuf);
+
+/// Keeps the served file path in the response when the access log is enabled.
+pub(crate) fn set_served_file(
+    opts: &RequestHandlerOpts,
+    resp: &mut Response<Body>,
+    file_path: Option<PathBuf>,
+) {
+    if let (Some(_), Some(path)) = (&opts.access_log, file_path) {
+        resp.extensions_mut().insert(ServedFile(path));
+    }
+}
+
+/// An access log entry which is written once its response is completed.
+pub(crate) struct Entry {
+    log: Arc<AccessLog>,
+    time: DateTime<Local>,
+    started: Instant,
+    remote_addr: Option<SocketAddr>,
+    method: Method,
+    uri: Uri,
+    version: Version,
+    host: Option<String>,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    status: StatusCode,
+    file: Option<PathBuf>,
+    content_encoding: Option<String>,
+    request_id: Option<RequestId>,
+}
+
+/// Captures the incoming request information if the access log is enabled.
+pub(crate) fn pre_process<T>(
+    opts: &RequestHandlerOpts,
+    req: &Request<T>,
+    remote_addr: Option<SocketAddr>,
+) -> Option<Entry> {
+    let log = opts.access_log.as_ref()?;
+    let header = |name: header::HeaderName| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned())
+    };
+    Some(Entry {
+        log: log.clone(),
+        time: Local::now(),
+        started: Instant::now(),
+        remote_addr,
+        method: req.method().clone(),
+        uri: req.uri().clone(),
+        version: req.version(),
+        host: header(header::HOST).or_else(|| req.uri().authority().map(|a| a.to_string())),
+        referer: header(header::REFERER),
+        user_agent: header(header::USER_AGENT),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+        file: None,
+        content_encoding: None,
+        request_id: req.extensions().get::<RequestId>().cloned(),
+    })
+}
+
+/// Completes the access log entry with the response information.
+/// The entry is written once the response body was sent or dropped.
+pub(crate) fn post_process(
+    entry: Option<Entry>,
+    result: Result<Response<Body>>,
+) -> Result<Response<Body>> {
+    let Some(mut entry) = entry else {
+        return result;
+    };
+    let mut resp = match result {
+        Ok(resp) => resp,
+        Err(err) => {
+            entry.write(0);
+            return Err(err);
+        }
+    };
+
+    entry.status = resp.status();
+    entry.file = resp.extensions_mut().remove::<ServedFile>().map(|f| f.0);
+    entry.content_encoding = resp
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+
+    Ok(response::on_body_complete(resp, move |bytes| {
+        entry.write(bytes)
+    }))
+}
+
+impl Entry {
+    /// Queues the entry to the access log writer.
+    fn write(self, bytes: u64) {
+        let line = self.format(bytes, self.started.elapsed());
+        self.log.writer.write(line);
+    }
+
+    /// Formats the entry as a single line.
+    fn format(&self, bytes: u64, duration: Duration) -> String {
+        let mut line = String::new();
+        match &self.log.format {
+            AccessLogFormat::Common | AccessLogFormat::Combined => {
+                let _ = write!(
+                    line,
+                    "{} - - [{}] \"{}\" {} {}",
+                    self.value(Field::RemoteAddr, bytes, duration),
+                    self.value(Field::TimeLocal, bytes, duration),
+                    self.value(Field::Request, bytes, duration),
+                    self.status.as_u16(),
+                    self.value(Field::Bytes, bytes, duration),
+                );
+                if self.log.format == AccessLogFormat::Combined {
+                    let _ = write!(
+                        line,
+                        " \"{}\" \"{}\"",
+                        self.value(Field::Referer, bytes, duration),
+                        self.value(Field::UserAgent, bytes, duration),
+                    );
+                }
+            }
+            AccessLogFormat::Json => {
+                let json = serde_json::json!({
+                    "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, false),
+                    "remote_addr": self.remote_addr.map(|a| a.ip().to_canonical().to_string()),
+                    "method": self.method.as_str(),
+                    "uri": self.uri.to_string(),
+                    "protocol": format!("{:?}", self.version),
+                    "status": self.status.as_u16(),
+                    "bytes": bytes,
+                    "duration_ms": duration_ms(duration),
+                    "referer": self.referer,
+                    "user_agent": self.user_agent,
+                    "host": self.host,
+                    "file": self.file.as_ref().map(|f| f.display().to_string()),
+                    "content_encoding": self.content_encoding,
+                    "request_id": self.request_id.as_ref().map(|id| id.as_str()),
+                });
+                line.push_str(&json.to_string());
+            }
+            AccessLogFormat::Template(segments) => {
+                for segment in segments {
+                    match segment {
+                        Segment::Text(text) => line.push_str(text),
+                        Segment::Field(field) => {
+                            line.push_str(&self.value(*field, bytes, duration))
+                        }
+                    }
+                }
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Returns the escaped value of a field or `-` if not available.
+    fn value(&self, field: Field, bytes: u64, duration: Duration) -> String {
+        let text = |value: Option<&str>| value.map_or_else(|| "-".to_owned(), escape);
+        match field {
+            Field::Time => self.time.to_rfc3339_opts(SecondsFormat::Millis, false),
+            Field::TimeLocal => self.time.format(CLF_TIME_FORMAT).to_string(),
+            Field::RemoteAddr => self
+                .remote_addr
+                .map_or_else(|| "-".to_owned(), |a| a.ip().to_canonical().to_string()),
+            Field::Method => self.method.to_string(),
+            Field::Uri => escape(&self.uri.to_string()),
+            Field::Protocol => format!("{:?}", self.version),
+            Field::Request => escape(&format!("{} {} {:?}", self.method, self.uri, self.version)),
+            Field::Status => self.status.as_u16().to_string(),
+            Field::Bytes if bytes == 0 => "-".to_owned(),
+            Field::Bytes => bytes.to_string(),
+            Field::DurationMs => format!("{:.3}", duration_ms(duration)),
+            Field::Referer => text(self.referer.as_deref()),
+            Field::UserAgent => text(self.user_agent.as_deref()),
+            Field::Host => text(self.host.as_deref()),
+            Field::File => text(
+                self.file
+                    .as_ref()
+                    .map(|f| f.display().to_string())
+                    .as_deref(),
+            ),
+            Field::ContentEncoding => text(self.content_encoding.as_deref()),
+            Field::RequestId => text(self.request_id.as_ref().map(|id| id.as_str())),
+        }
+    }
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Escapes quotes, backslashes and control characters so every entry stays on a single line.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\x{:02x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn writer(capacity: usize) -> (Arc<Writer>, mpsc::Receiver<Message>) {
+        let (sender, rx) = mpsc::sync_channel(capacity);
+        let writer = Writer {
+            file: None,
+            sender,
+            dropped: Arc::default(),
+        };
+        (Arc::new(writer), rx)
+    }
+
+    fn access_log(format: &str) -> (AccessLog, mpsc::Receiver<Message>) {
+        let (writer, rx) = writer(QUEUE_CAPACITY);
+        let log = AccessLog {
+            format: format.parse().unwrap(),
+            writer,
+        };
+        (log, rx)
+    }
+
+    fn next_entry(rx: &mpsc::Receiver<Message>) -> Option<String> {
+        match rx.try_recv() {
+            Ok(Message::Entry(line)) => Some(line),
+            _ => None,
+        }
+    }
+
+    fn request() -> Request<Body> {
+        Request::get("/assets/app.js?v=1")
+            .header(header::HOST, "localhost:8787")
+            .header(header::REFERER, "http://localhost:8787/")
+            .header(header::USER_AGENT, "curl/8.0 \"test\"")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn handler_opts(log: AccessLog) -> RequestHandlerOpts {
+        RequestHandlerOpts {
+            access_log: Some(Arc::new(log)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(
+            "common".parse::<AccessLogFormat>().unwrap(),
+            AccessLogFormat::Common
+        );
+        assert_eq!(
+            "{status} {bytes}b".parse::<AccessLogFormat>().unwrap(),
+            AccessLogFormat::Template(vec![
+                Segment::Field(Field::Status),
+                Segment::Text(" ".into()),
+                Segment::Field(Field::Bytes),
+                Segment::Text("b".into()),
+            ])
+        );
+        assert!("{status".parse::<AccessLogFormat>().is_err());
+        assert!("{unknown}".parse::<AccessLogFormat>().is_err());
+        assert!("apache".parse::<AccessLogFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn logs_after_the_body_is_sent() {
+        let (log, rx) = access_log("combined");
+        let opts = handler_opts(log);
+        let addr = "192.168.1.10:51000".parse().ok();
+        let entry = pre_process(&opts, &request(), addr);
+
+        let mut resp = Response::new(Body::from("console.log(1);"));
+        set_served_file(&opts, &mut resp, Some(PathBuf::from("/srv/assets/app.js")));
+        let resp = post_process(entry, Ok(resp)).unwrap();
+        assert_eq!(resp.headers()[header::CONTENT_LENGTH], "15");
+        assert!(next_entry(&rx).is_none());
+
+        hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let line = next_entry(&rx).unwrap();
+        assert!(line.starts_with("192.168.1.10 - - ["), "{line}");
+        assert!(line.ends_with(
+            "] \"GET /assets/app.js?v=1 HTTP/1.1\" 200 15 \"http://localhost:8787/\" \"curl/8.0 \\\"test\\\"\"\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn logs_json_and_template_entries() {
+        let (log, rx) = access_log("json");
+        let opts = handler_opts(log);
+        let entry = pre_process(&opts, &request(), None);
+        let mut resp = Response::new(Body::empty());
+        *resp.status_mut() = StatusCode::NOT_MODIFIED;
+        post_process(entry, Ok(resp)).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&next_entry(&rx).unwrap()).unwrap();
+        assert_eq!(json["status"], 304);
+        assert_eq!(json["bytes"], 0);
+        assert_eq!(json["host"], "localhost:8787");
+        assert_eq!(json["remote_addr"], serde_json::Value::Null);
+
+        let (log, rx) = access_log("{host} {request} {status} {bytes} {file} {content_encoding}");
+        let opts = handler_opts(log);
+        let entry = pre_process(&opts, &request(), None);
+        let mut resp = Response::new(Body::from("x".repeat(10)));
+        resp.headers_mut()
+            .insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        let resp = post_process(entry, Ok(resp)).unwrap();
+        // An interrupted response is logged with the bytes sent so far
+        drop(resp);
+        assert_eq!(
+            next_entry(&rx).unwrap(),
+            "localhost:8787 GET /assets/app.js?v=1 HTTP/1.1 200 - - gzip\n"
+        );
+    }
+
+    #[test]
+    fn drops_entries_once_the_queue_is_full() {
+        let (writer, rx) = writer(2);
+        for line in ["one\n", "two\n", "three\n"] {
+            writer.write(line.to_owned());
+        }
+        assert_eq!(writer.dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(next_entry(&rx).unwrap(), "one\n");
+        assert_eq!(next_entry(&rx).unwrap(), "two\n");
+        assert_eq!(next_entry(&rx), None);
+    }
+
+    #[test]
+    fn shares_file_writers() {
+        let path = std::env::temp_dir().join(format!("sws-access-log-{}.log", std::process::id()));
+        let rotation = Rotation::default();
+        let new =
+            |rotation| AccessLog::new(AccessLogFormat::Common, Some(&path), rotation).unwrap();
+
+        let log = new(rotation);
+        assert!(Arc::ptr_eq(&log.writer, &new(rotation).writer));
+        let other = new(Rotation {
+            size: 1024,
+            ..rotation
+        });
+        assert!(!Arc::ptr_eq(&log.writer, &other.writer));
+
+        drop((log, other));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rotates_files_by_size() {
+        let dir = std::env::temp_dir().join(format!("sws-access-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("access.log");
+        let rotation = Rotation {
+            size: 10,
+            keep: 2,
+            compress: true,
+            ..Default::default()
+        };
+
+        let mut file = LogFile::open(path.clone(), rotation).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            file.write(line).unwrap();
+        }
+        file.file.flush().unwrap();
+        file.wait_for_compression();
+
+        // Entries are never split across files
+        assert_eq!(fs::read_to_string(&path).unwrap(), "four\nfive\n");
+        let mut rotated = String::new();
+        let gz = File::open(rotated_path(&path, 1, true)).unwrap();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(gz), &mut rotated).unwrap();
+        assert_eq!(rotated, "three\n");
+        assert!(rotated_path(&path, 2, true).is_file());
+        assert!(!rotated_path(&path, 3, true).exists());
+        assert!(!rotated_path(&path, 1, false).exists());
+
+        // A moved file is released once reopened
+        fs::rename(&path, dir.join("moved.log")).unwrap();
+        file.reopen().unwrap();
+        file.write("six\n").unwrap();
+        file.file.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "six\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("moved.log")).unwrap(),
+            "four\nfive\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotation_periods() {
+        let time = Local::now();
+        assert_eq!(RotateInterval::Never.period(time), None);
+        assert_ne!(
+            RotateInterval::Hourly.period(time),
+            RotateInterval::Hourly.period(time + chrono::Duration::hours(1))
+        );
+        assert_eq!(
+            RotateInterval::Daily.period(time),
+            Some(time.format("%Y%m%d").to_string())
+        );
+    }
+}
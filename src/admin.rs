@@ -0,0 +1,322 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module providing a separate admin listener serving the health, readiness and metrics endpoints
+//! apart from the public listeners, as well as an endpoint to toggle the maintenance mode.
+//!
+
+use headers::{ContentType, HeaderMapExt};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server as HyperServer, StatusCode};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::sync::Arc;
+
+use crate::{
+    handler::RequestHandlerOpts, health, maintenance_mode, service::SharedRequestHandler,
+    settings::cli::General, Context as _, Result,
+};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+
+/// The admin listener options.
+pub(crate) struct AdminOpts {
+    /// Health endpoint path.
+    health_path: String,
+    /// Readiness endpoint path.
+    ready_path: String,
+    /// Maintenance mode endpoint path.
+    maintenance_path: String,
+    /// Metrics endpoint path.
+    #[cfg(feature = "metrics")]
+    metrics_path: String,
+    /// SHA-256 digest of the token the clients must send.
+    auth_token: Option<[u8; 32]>,
+}
+
+/// Moves the health, readiness and metrics endpoints out of the public listeners if the admin listener is enabled.
+pub(crate) fn init(enabled: bool, handler_opts: &mut RequestHandlerOpts) {
+    handler_opts.admin_listener = enabled;
+}
+
+/// Binds the admin listener if enabled.
+pub(crate) fn bind(general: &General) -> Result<Option<(TcpListener, AdminOpts)>> {
+    let Some(port) = general.admin_port else {
+        server_info!("admin listener: enabled=false");
+        return Ok(None);
+    };
+
+    let opts = AdminOpts {
+        health_path: endpoint_path(&general.admin_health_path)?,
+        ready_path: endpoint_path(&general.admin_ready_path)?,
+        maintenance_path: endpoint_path(&general.admin_maintenance_path)?,
+        #[cfg(feature = "metrics")]
+        metrics_path: endpoint_path(&general.admin_metrics_path)?,
+        auth_token: match general.admin_auth_token.as_deref() {
+            Some("") => bail!("admin auth token can not be empty"),
+            Some(token) => Some(Sha256::digest(token).into()),
+            None => None,
+        },
+    };
+    let paths = [&opts.health_path, &opts.ready_path, &opts.maintenance_path].into_iter();
+    #[cfg(feature = "metrics")]
+    let paths = paths.chain([&opts.metrics_path]);
+    let mut paths = paths.collect::<Vec<_>>();
+    paths.sort();
+    if paths.windows(2).any(|pair| pair[0] == pair[1]) {
+        bail!("admin endpoints can not share the same path")
+    }
+
+    let ip = general
+        .admin_host
+        .parse::<IpAddr>()
+        .with_context(|| format!("failed to parse {} admin address", general.admin_host))?;
+    let addr = SocketAddr::from((ip, port));
+    let tcp_listener =
+        TcpListener::bind(addr).with_context(|| format!("failed to bind to {addr} address"))?;
+    tcp_listener
+        .set_nonblocking(true)
+        .with_context(|| "failed to set TCP non-blocking mode")?;
+
+    server_info!(
+        "admin listener: enabled=true, address={addr}, auth={}",
+        opts.auth_token.is_some()
+    );
+    Ok(Some((tcp_listener, opts)))
+}
+
+/// Checks that an endpoint path is absolute.
+fn endpoint_path(path: &str) -> Result<String> {
+    if !path.starts_with('/') {
+        bail!("admin endpoint path \"{path}\" must start with a slash")
+    }
+    Ok(path.to_owned())
+}
+
+/// Creates the admin server which runs until the given `shutdown` future resolves.
+pub(crate) fn serve<F>(
+    tcp_listener: TcpListener,
+    opts: AdminOpts,
+    handler: SharedRequestHandler,
+    shutdown: F,
+) -> Result<impl Future<Output = hyper::Result<()>>>
+where
+    F: Future<Output = ()>,
+{
+    let opts = Arc::new(opts);
+    let server = HyperServer::from_tcp(tcp_listener)?
+        .tcp_nodelay(true)
+        .http1_only(true)
+        .serve(make_service_fn(move |_: &AddrStream| {
+            let opts = opts.clone();
+            let handler = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let resp = handle(&opts, &handler, &req);
+                    async move { Ok::<_, Infallible>(resp) }
+                }))
+            }
+        }))
+        .with_graceful_shutdown(shutdown);
+    Ok(server)
+}
+
+/// Handles a request to the admin listener.
+fn handle<T>(opts: &AdminOpts, handler: &SharedRequestHandler, req: &Request<T>) -> Response<Body> {
+    tracing::debug!(
+        "incoming admin request: method={} uri={}",
+        req.method(),
+        req.uri()
+    );
+
+    if !is_authorized(opts, req) {
+        let mut resp = text_response(StatusCode::UNAUTHORIZED);
+        resp.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer realm=\"admin\""),
+        );
+        return resp;
+    }
+
+    let resp = match req.uri().path() {
+        path if path == opts.health_path => health::response(req.method()),
+        path if path == opts.ready_path => {
+            health::readiness_response(&handler.get().opts, req.method())
+        }
+        path if path == opts.maintenance_path => {
+            return maintenance_response(&handler.get().opts, req.method())
+        }
+        #[cfg(feature = "metrics")]
+        path if path == opts.metrics_path && handler.get().opts.metrics => {
+            metrics::response(req.method())
+        }
+        _ => return text_response(StatusCode::NOT_FOUND),
+    };
+
+    resp.unwrap_or_else(|| {
+        let mut resp = text_response(StatusCode::METHOD_NOT_ALLOWED);
+        resp.headers_mut()
+            .typed_insert(headers::Allow::from_iter([Method::GET, Method::HEAD]));
+        resp
+    })
+}
+
+/// Gets, enables or disables the maintenance mode at runtime.
+fn maintenance_response(opts: &RequestHandlerOpts, method: &Method) -> Response<Body> {
+    match *method {
+        Method::POST => maintenance_mode::set_runtime_state(true),
+        Method::DELETE => maintenance_mode::set_runtime_state(false),
+        Method::GET | Method::HEAD => {}
+        _ => {
+            let mut resp = text_response(StatusCode::METHOD_NOT_ALLOWED);
+            resp.headers_mut().typed_insert(headers::Allow::from_iter([
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::DELETE,
+            ]));
+            return resp;
+        }
+    }
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        let enabled = maintenance_mode::is_switched_on(opts);
+        Body::from(json!({ "maintenance_mode": enabled }).to_string())
+    };
+    let mut resp = Response::new(body);
+    resp.headers_mut().typed_insert(ContentType::json());
+    resp
+}
+
+/// Checks the `Authorization: Bearer <token>` header if a token is required.
+fn is_authorized<T>(opts: &AdminOpts, req: &Request<T>) -> bool {
+    let Some(expected) = &opts.auth_token else {
+        return true;
+    };
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        // Comparing the digests doesn't leak the token via timing
+        .is_some_and(|token| Sha256::digest(token.trim()).as_slice() == expected)
+}
+
+/// Creates a plain text response with the reason phrase of the status.
+fn text_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::from(status.canonical_reason().unwrap_or_default()));
+    *resp.status_mut() = status;
+    resp.headers_mut().typed_insert(ContentType::text_utf8());
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::RequestHandler;
+
+    fn admin_opts(auth_token: Option<&str>) -> AdminOpts {
+        AdminOpts {
+            health_path: "/-/health".to_owned(),
+            ready_path: "/-/ready".to_owned(),
+            maintenance_path: "/-/maintenance".to_owned(),
+            #[cfg(feature = "metrics")]
+            metrics_path: "/-/metrics".to_owned(),
+            auth_token: auth_token.map(|token| Sha256::digest(token).into()),
+        }
+    }
+
+    fn shared_handler(metrics: bool) -> SharedRequestHandler {
+        #[cfg(not(feature = "metrics"))]
+        let _ = metrics;
+        SharedRequestHandler::new(RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                #[cfg(feature = "metrics")]
+                metrics,
+                admin_listener: true,
+                ..Default::default()
+            }),
+        })
+    }
+
+    fn request(method: Method, uri: &str, token: Option<&str>) -> Request<Body> {
+        let mut req = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            req = req.header("authorization", format!("Bearer {token}"));
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn serves_the_endpoints_at_their_paths() {
+        let opts = admin_opts(None);
+        let handler = shared_handler(true);
+
+        let resp = handle(&opts, &handler, &request(Method::GET, "/-/health", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = handle(&opts, &handler, &request(Method::GET, "/health", None));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = handle(&opts, &handler, &request(Method::GET, "/-/ready", None));
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let resp = handle(&opts, &handler, &request(Method::POST, "/-/health", None));
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()["allow"], "GET, HEAD");
+        let resp = handle(
+            &opts,
+            &handler,
+            &request(Method::GET, "/-/maintenance", None),
+        );
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = handle(
+            &opts,
+            &handler,
+            &request(Method::PUT, "/-/maintenance", None),
+        );
+        assert_eq!(resp.headers()["allow"], "GET, HEAD, POST, DELETE");
+
+        #[cfg(feature = "metrics")]
+        {
+            let resp = handle(&opts, &handler, &request(Method::GET, "/-/metrics", None));
+            assert_eq!(resp.status(), StatusCode::OK);
+            let handler = shared_handler(false);
+            let resp = handle(&opts, &handler, &request(Method::GET, "/-/metrics", None));
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[test]
+    fn requires_the_auth_token() {
+        let opts = admin_opts(Some("s3cr3t"));
+        let handler = shared_handler(true);
+
+        let resp = handle(&opts, &handler, &request(Method::GET, "/-/health", None));
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers()["www-authenticate"], "Bearer realm=\"admin\"");
+        let resp = handle(
+            &opts,
+            &handler,
+            &request(Method::GET, "/-/health", Some("nope")),
+        );
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = handle(
+            &opts,
+            &handler,
+            &request(Method::GET, "/-/health", Some("s3cr3t")),
+        );
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn rejects_relative_paths() {
+        assert!(endpoint_path("/-/health").is_ok());
+        assert!(endpoint_path("health").is_err());
+    }
+}
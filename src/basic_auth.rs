@@ -6,69 +6,543 @@
 //! Basic HTTP Authorization Schema module.
 //!
 
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use bcrypt::verify as bcrypt_verify;
 use headers::{authorization::Basic, Authorization, HeaderMap, HeaderMapExt};
-use hyper::{header::WWW_AUTHENTICATE, Body, Request, Response, StatusCode};
+use hyper::{
+    header::{HOST, WWW_AUTHENTICATE},
+    Body, Request, Response, StatusCode,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::{error_page, handler::RequestHandlerOpts, http_ext::MethodExt, Error};
+use crate::{
+    error_page, handler::RequestHandlerOpts, helpers, http_ext::MethodExt, settings::cli::General,
+    settings::Auth, virtual_hosts, Context as _, Error, Result,
+};
+
+#[cfg(feature = "jwt-auth")]
+use crate::jwt_auth::{self, Claims};
+
+/// The default realm of the `WWW-Authenticate` header.
+pub const DEFAULT_REALM: &str = "Static Web Server";
+
+/// Minimum time between two checks for changes of the users and groups files.
+const FILES_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Users and groups loaded from an htpasswd file and an optional groups file,
+/// which are loaded again once they change.
+pub struct HtpasswdFile {
+    users_path: PathBuf,
+    groups_path: Option<PathBuf>,
+    state: RwLock<HtpasswdState>,
+}
+
+/// The loaded users and groups.
+struct HtpasswdState {
+    /// Password hashes by user.
+    users: HashMap<String, String>,
+    /// Groups by user.
+    groups: HashMap<String, Vec<String>>,
+    /// Modification times of the users and groups files once loaded.
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    /// Last time the files were checked for changes.
+    checked_at: Instant,
+}
+
+impl HtpasswdFile {
+    /// Loads the users from an htpasswd file and their groups from an optional groups file.
+    pub fn load(users_path: &Path, groups_path: Option<&Path>) -> Result<Self> {
+        let file = Self {
+            users_path: users_path.to_owned(),
+            groups_path: groups_path.map(Path::to_owned),
+            state: RwLock::new(HtpasswdState {
+                users: HashMap::new(),
+                groups: HashMap::new(),
+                modified: (None, None),
+                checked_at: Instant::now(),
+            }),
+        };
+        let state = file.read_files()?;
+        *file.state.write().unwrap() = state;
+        Ok(file)
+    }
+
+    /// Reads and parses the users and groups files.
+    fn read_files(&self) -> Result<HtpasswdState> {
+        let (users_modified, users) = read_file(&self.users_path)?;
+        let users = parse_htpasswd(&users, &self.users_path);
+        let (groups_modified, groups) = match &self.groups_path {
+            Some(path) => {
+                let (modified, groups) = read_file(path)?;
+                (modified, parse_groups(&groups))
+            }
+            None => (None, HashMap::new()),
+        };
+        Ok(HtpasswdState {
+            users,
+            groups,
+            modified: (users_modified, groups_modified),
+            checked_at: Instant::now(),
+        })
+    }
+
+    /// Loads the files again if they were modified since the last check, keeping the
+    /// current users and groups if they can't be loaded.
+    fn reload_if_modified(&self) {
+        {
+            let state = self.state.read().unwrap();
+            if state.checked_at.elapsed() < FILES_CHECK_INTERVAL {
+                return;
+            }
+        }
+        let mut state = self.state.write().unwrap();
+        if state.checked_at.elapsed() < FILES_CHECK_INTERVAL {
+            return;
+        }
+        state.checked_at = Instant::now();
+
+        let modified = (
+            modified_time(&self.users_path),
+            self.groups_path.as_deref().and_then(modified_time),
+        );
+        if modified == state.modified {
+            return;
+        }
+        match self.read_files() {
+            Ok(new_state) => {
+                tracing::info!(
+                    "basic authentication: reloaded {} users from \"{}\"",
+                    new_state.users.len(),
+                    self.users_path.display()
+                );
+                *state = new_state;
+            }
+            Err(err) => {
+                tracing::error!(
+                    "basic authentication: failed to reload the users, keeping the current ones: {err:#}"
+                );
+                state.modified = modified;
+            }
+        }
+    }
+
+    /// Returns the password hash of a user.
+    fn password_hash(&self, user: &str) -> Option<String> {
+        self.reload_if_modified();
+        self.state.read().unwrap().users.get(user).cloned()
+    }
+
+    /// Checks whether a user belongs to any of the given groups.
+    pub fn user_in_groups(&self, user: &str, groups: &[String]) -> bool {
+        self.reload_if_modified();
+        self.state
+            .read()
+            .unwrap()
+            .groups
+            .get(user)
+            .is_some_and(|user_groups| user_groups.iter().any(|group| groups.contains(group)))
+    }
+
+    /// Returns the number of loaded users.
+    fn len(&self) -> usize {
+        self.state.read().unwrap().users.len()
+    }
+}
+
+fn read_file(path: &Path) -> Result<(Option<SystemTime>, String)> {
+    let modified = modified_time(path);
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read the file \"{}\"", path.display()))?;
+    Ok((modified, content))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Parses the `user:hash` lines of an htpasswd file, skipping the unsupported hashes.
+fn parse_htpasswd(content: &str, path: &Path) -> HashMap<String, String> {
+    let mut users = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((user, hash)) if !user.is_empty() && is_supported_hash(hash) => {
+                users.insert(user.to_owned(), hash.to_owned());
+            }
+            _ => server_warn!(
+                "basic authentication: skipping line {} of \"{}\", the user is empty or the password hash is not supported",
+                i + 1,
+                path.display()
+            ),
+        }
+    }
+    users
+}
+
+/// Parses the `group: user1 user2` lines of an Apache groups file.
+fn parse_groups(content: &str) -> HashMap<String, Vec<String>> {
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((group, users)) = line.split_once(':') {
+            for user in users.split_whitespace() {
+                groups
+                    .entry(user.to_owned())
+                    .or_default()
+                    .push(group.trim().to_owned());
+            }
+        }
+    }
+    groups
+}
+
+/// Checks whether a password hash uses one of the supported algorithms:
+/// `bcrypt`, `SHA-256-crypt`, `SHA-512-crypt` or `argon2`.
+fn is_supported_hash(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2y$", "$5$", "$6$", "$argon2"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
+/// Verifies a password against a hash using the algorithm defined by the hash prefix.
+fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$5$") {
+        pwhash::sha256_crypt::verify(password, hash)
+    } else if hash.starts_with("$6$") {
+        pwhash::sha512_crypt::verify(password, hash)
+    } else if hash.starts_with("$argon2") {
+        match PasswordHash::new(hash) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(err) => {
+                tracing::error!("argon2 password hash parsing error: {:?}", err);
+                false
+            }
+        }
+    } else {
+        match bcrypt_verify(password, hash) {
+            Ok(valid) => valid,
+            Err(err) => {
+                tracing::error!("bcrypt password verification error: {:?}", err);
+                false
+            }
+        }
+    }
+}
 
 /// Initializes `Basic` HTTP Authorization handling
-pub(crate) fn init(credentials: &str, handler_opts: &mut RequestHandlerOpts) {
-    credentials.trim().clone_into(&mut handler_opts.basic_auth);
+pub(crate) fn init(general: &General, handler_opts: &mut RequestHandlerOpts) -> Result {
+    general
+        .basic_auth
+        .trim()
+        .clone_into(&mut handler_opts.basic_auth);
+    if let Some(path) = &general.basic_auth_file {
+        let file = HtpasswdFile::load(path, general.basic_auth_groups_file.as_deref())
+            .with_context(|| "failed to load the basic authentication users")?;
+        server_info!(
+            "basic authentication: loaded {} users from \"{}\"",
+            file.len(),
+            path.display()
+        );
+        handler_opts.basic_auth_file = Some(file.into());
+    } else if general.basic_auth_groups_file.is_some() {
+        bail!("basic authentication groups file requires a users file (basic-auth-file)")
+    }
+    if general
+        .basic_auth_realm
+        .contains(|c: char| c == '"' || c == '\\' || c.is_control())
+    {
+        bail!(
+            "basic authentication realm can not contain quotes, backslashes or control characters"
+        )
+    }
+    general
+        .basic_auth_realm
+        .clone_into(&mut handler_opts.basic_auth_realm);
+    handler_opts.basic_auth_groups = general.basic_auth_groups.clone();
+    if !handler_opts.basic_auth_groups.is_empty() && general.basic_auth_groups_file.is_none() {
+        bail!("basic authentication groups require a groups file (basic-auth-groups-file)")
+    }
+
+    let rules = auth_rules(handler_opts);
+    for rule in rules.iter().filter(|rule| !rule.public) {
+        if !is_enabled(handler_opts) {
+            bail!(
+                "auth rule {} requires basic authentication credentials (basic-auth or basic-auth-file) or a jwt authentication key",
+                rule.source.glob()
+            )
+        }
+        #[cfg(feature = "jwt-auth")]
+        if !rule.claims.is_empty() && handler_opts.jwt_auth.is_none() {
+            bail!(
+                "auth rule {} claims require a jwt authentication key",
+                rule.source.glob()
+            )
+        }
+        if !rule.groups.is_empty() && general.basic_auth_groups_file.is_none() {
+            bail!(
+                "auth rule {} groups require a groups file (basic-auth-groups-file)",
+                rule.source.glob()
+            )
+        }
+    }
+    if !rules.is_empty() {
+        server_info!("basic authentication: {} auth rules", rules.len());
+    }
+
     server_info!(
-        "basic authentication: enabled={}",
-        !handler_opts.basic_auth.is_empty()
+        "basic authentication: enabled={}, realm=\"{}\", groups={:?}",
+        !handler_opts.basic_auth.is_empty() || handler_opts.basic_auth_file.is_some(),
+        handler_opts.basic_auth_realm,
+        handler_opts.basic_auth_groups
     );
+    Ok(())
+}
+
+/// Returns the authentication rules.
+fn auth_rules(opts: &RequestHandlerOpts) -> &[Auth] {
+    opts.advanced_opts
+        .as_ref()
+        .and_then(|advanced| advanced.auth.as_deref())
+        .unwrap_or_default()
+}
+
+/// Returns the first authentication rule matching the normalized request path and host if any.
+fn find_rule<'a, T>(
+    opts: &'a RequestHandlerOpts,
+    req: &Request<T>,
+    path: &str,
+) -> Option<&'a Auth> {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(virtual_hosts::strip_port);
+    auth_rules(opts).iter().find(|rule| {
+        rule.source.is_match(path)
+            && rule.host.as_deref().map_or(true, |pattern| {
+                host.is_some_and(|host| virtual_hosts::host_matches(pattern, host))
+            })
+    })
 }
 
 /// Handles `Basic` HTTP Authorization Schema
+///
+/// The authenticated client is kept in the request extensions, so that the rules can be
+/// evaluated again for a rewritten request without authenticating the client twice.
 pub(crate) fn pre_process<T>(
     opts: &RequestHandlerOpts,
-    req: &Request<T>,
+    req: &mut Request<T>,
 ) -> Option<Result<Response<Body>, Error>> {
-    if opts.basic_auth.is_empty() {
-        return None;
+    let identity = req.extensions_mut().remove::<Identity>();
+    let uri = req.uri();
+    let method = req.method();
+    let path = match helpers::normalize_request_path(uri.path()) {
+        Ok(path) => path,
+        Err(_) if !is_enabled(opts) && auth_rules(opts).is_empty() => return None,
+        Err(status) => {
+            return Some(error_page::error_response(
+                uri,
+                method,
+                &status,
+                &opts.page404,
+                &opts.page50x,
+            ))
+        }
+    };
+
+    let rule = find_rule(opts, req, &path);
+    match rule {
+        Some(rule) if rule.public => return None,
+        Some(_) => {}
+        None if !is_enabled(opts) => return None,
+        None => {}
     }
 
-    let method = req.method();
     if method.is_options() {
         return None;
     }
 
-    let uri = req.uri();
-    if let Some((user_id, password)) = opts.basic_auth.split_once(':') {
-        let err = check_request(req.headers(), user_id, password).err()?;
-        tracing::warn!("basic authentication failed {:?}", err);
-        let mut result = error_page::error_response(
-            uri,
-            method,
-            &StatusCode::UNAUTHORIZED,
-            &opts.page404,
-            &opts.page50x,
-        );
-        if let Ok(ref mut resp) = result {
-            resp.headers_mut().insert(
-                WWW_AUTHENTICATE,
-                "Basic realm=\"Static Web Server\", charset=\"UTF-8\""
-                    .parse()
-                    .unwrap(),
+    let identity = match identity {
+        Some(identity) => Ok(identity),
+        None => authenticate_request(opts, req.headers()),
+    };
+    let status = match identity {
+        Ok(identity) if is_allowed(opts, &identity, rule) => {
+            req.extensions_mut().insert(identity);
+            return None;
+        }
+        Ok(identity) => {
+            tracing::warn!(
+                "authentication: user \"{}\" not allowed to access {uri}",
+                identity.user
             );
+            StatusCode::FORBIDDEN
         }
-        Some(result)
+        Err(status) => status,
+    };
+
+    let mut result = error_page::error_response(uri, method, &status, &opts.page404, &opts.page50x);
+    if status == StatusCode::UNAUTHORIZED {
+        tracing::warn!("authentication failed {:?}", status);
+        if let Ok(ref mut resp) = result {
+            let mut challenges = Vec::new();
+            if has_basic_credentials(opts) {
+                challenges.push(format!(
+                    "Basic realm=\"{}\", charset=\"UTF-8\"",
+                    opts.basic_auth_realm
+                ));
+            }
+            #[cfg(feature = "jwt-auth")]
+            if opts.jwt_auth.is_some() {
+                challenges.push(format!("Bearer realm=\"{}\"", opts.basic_auth_realm));
+            }
+            for challenge in challenges {
+                if let Ok(value) = challenge.parse() {
+                    resp.headers_mut().append(WWW_AUTHENTICATE, value);
+                }
+            }
+        }
+    }
+    Some(result)
+}
+
+/// An authenticated client.
+struct Identity {
+    /// The user name, or the `sub` claim of a token.
+    user: String,
+    /// The claims of a token.
+    #[cfg(feature = "jwt-auth")]
+    claims: Option<Claims>,
+}
+
+/// Checks whether the basic authentication credentials are defined.
+fn has_basic_credentials(opts: &RequestHandlerOpts) -> bool {
+    !opts.basic_auth.is_empty() || opts.basic_auth_file.is_some()
+}
+
+/// Checks whether any authentication method is enabled for the whole server.
+fn is_enabled(opts: &RequestHandlerOpts) -> bool {
+    #[cfg(feature = "jwt-auth")]
+    if opts.jwt_auth.is_some() {
+        return true;
+    }
+    has_basic_credentials(opts)
+}
+
+/// Authenticates the request via a token if sent and the JWT authentication is enabled,
+/// or via the basic authentication credentials otherwise.
+fn authenticate_request(
+    opts: &RequestHandlerOpts,
+    headers: &HeaderMap,
+) -> Result<Identity, StatusCode> {
+    #[cfg(feature = "jwt-auth")]
+    if let Some(jwt_auth) = &opts.jwt_auth {
+        if let Some(token) = jwt_auth.token(headers) {
+            let claims = jwt_auth.validate(&token)?;
+            let user = claims
+                .get("sub")
+                .and_then(|sub| sub.as_str())
+                .unwrap_or_default()
+                .to_owned();
+            return Ok(Identity {
+                user,
+                claims: Some(claims),
+            });
+        }
+        if !has_basic_credentials(opts) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    authenticate(opts, headers).map(|user| Identity {
+        user,
+        #[cfg(feature = "jwt-auth")]
+        claims: None,
+    })
+}
+
+/// Authenticates the request against the configured credentials and users file,
+/// returning the user if successful.
+pub(crate) fn authenticate(
+    opts: &RequestHandlerOpts,
+    headers: &HeaderMap,
+) -> Result<String, StatusCode> {
+    if !opts.basic_auth.is_empty() {
+        let Some((user_id, password)) = opts.basic_auth.split_once(':') else {
+            tracing::error!("invalid basic authentication `user_id:password` pairs");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+        match check_request(headers, user_id, password) {
+            Ok(()) => return Ok(user_id.to_owned()),
+            Err(err) if opts.basic_auth_file.is_none() => return Err(err),
+            Err(_) => {}
+        }
+    }
+
+    let file = opts
+        .basic_auth_file
+        .as_ref()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let credentials = headers
+        .typed_get::<Authorization<Basic>>()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let user = credentials.0.username();
+    let hash = file.password_hash(user).ok_or(StatusCode::UNAUTHORIZED)?;
+    if verify_password(credentials.0.password(), &hash) {
+        Ok(user.to_owned())
     } else {
-        tracing::error!("invalid basic authentication `user_id:password` pairs");
-        Some(error_page::error_response(
-            uri,
-            method,
-            &StatusCode::INTERNAL_SERVER_ERROR,
-            &opts.page404,
-            &opts.page50x,
-        ))
+        Err(StatusCode::UNAUTHORIZED)
     }
 }
 
+/// Checks whether an authenticated client is allowed by the matching rule, or by the server-wide
+/// groups if no rule matches. The client must be one of the allowed users, belong to the allowed
+/// groups or have a token matching the allowed claims. Any authenticated client is allowed if
+/// there are neither allowed users, groups nor claims.
+fn is_allowed(opts: &RequestHandlerOpts, identity: &Identity, rule: Option<&Auth>) -> bool {
+    let (users, groups) = match rule {
+        Some(rule) => (rule.users.as_slice(), rule.groups.as_slice()),
+        None => (&[][..], opts.basic_auth_groups.as_slice()),
+    };
+    #[cfg(feature = "jwt-auth")]
+    let claims = rule.map_or(&[][..], |rule| rule.claims.as_slice());
+    #[cfg(not(feature = "jwt-auth"))]
+    let claims: &[()] = &[];
+    if users.is_empty() && groups.is_empty() && claims.is_empty() {
+        return true;
+    }
+
+    let user = identity.user.as_str();
+    #[cfg(feature = "jwt-auth")]
+    if !claims.is_empty()
+        && identity
+            .claims
+            .as_ref()
+            .is_some_and(|token_claims| jwt_auth::claims_match(token_claims, claims))
+    {
+        return true;
+    }
+    users.iter().any(|u| u == user)
+        || (!groups.is_empty()
+            && opts
+                .basic_auth_file
+                .as_ref()
+                .is_some_and(|file| file.user_in_groups(user, groups)))
+}
+
 /// Check for a `Basic` HTTP Authorization Schema of an incoming request
-/// and uses `bcrypt` for password hashing verification.
+/// and uses `bcrypt`, `SHA-256-crypt`, `SHA-512-crypt` or `argon2` for password hashing verification.
 pub fn check_request(headers: &HeaderMap, userid: &str, password: &str) -> Result<(), StatusCode> {
     let credentials = headers
         .typed_get::<Authorization<Basic>>()
@@ -78,22 +552,26 @@ pub fn check_request(headers: &HeaderMap, userid: &str, password: &str) -> Resul
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    match bcrypt_verify(credentials.0.password(), password) {
-        Ok(valid) if valid => Ok(()),
-        Ok(_) => Err(StatusCode::UNAUTHORIZED),
-        Err(err) => {
-            tracing::error!("bcrypt password verification error: {:?}", err);
-            Err(StatusCode::UNAUTHORIZED)
-        }
+    if verify_password(credentials.0.password(), password) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{check_request, pre_process};
-    use crate::{handler::RequestHandlerOpts, Error};
-    use headers::HeaderMap;
+    use super::{check_request, pre_process, verify_password, HtpasswdFile};
+    use crate::{
+        handler::RequestHandlerOpts,
+        settings::{Advanced, Auth},
+        Error,
+    };
+    use globset::Glob;
+    use headers::{Authorization, HeaderMap, HeaderMapExt};
     use hyper::{header::WWW_AUTHENTICATE, Body, Request, Response, StatusCode};
+    use std::path::Path;
+    use std::sync::Arc;
 
     fn make_request(method: &str, auth_header: &str) -> Request<Body> {
         let mut builder = Request::builder();
@@ -127,7 +605,7 @@ mod tests {
                 basic_auth: "".into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )
         .is_none());
     }
@@ -139,7 +617,7 @@ mod tests {
                 basic_auth: "xyz".into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )));
     }
 
@@ -151,7 +629,7 @@ mod tests {
                     .into(),
                 ..Default::default()
             },
-            &make_request("OPTIONS", "")
+            &mut make_request("OPTIONS", "")
         )
         .is_none());
     }
@@ -173,7 +651,7 @@ mod tests {
                     .into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )
         .is_none());
     }
@@ -188,7 +666,7 @@ mod tests {
                 basic_auth: "jq:".into(),
                 ..Default::default()
             },
-            &make_request("GET", "")
+            &mut make_request("GET", "")
         )));
     }
 
@@ -203,7 +681,7 @@ mod tests {
                 basic_auth: "xyz:".into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )));
     }
 
@@ -227,28 +705,28 @@ mod tests {
                     .into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )));
         assert!(is_401(pre_process(
             &RequestHandlerOpts {
                 basic_auth: "jq:password".into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )));
         assert!(is_401(pre_process(
             &RequestHandlerOpts {
                 basic_auth: ":password".into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )));
         assert!(is_401(pre_process(
             &RequestHandlerOpts {
                 basic_auth: "jq:".into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic anE6anE=")
+            &mut make_request("GET", "Basic anE6anE=")
         )));
     }
 
@@ -269,7 +747,7 @@ mod tests {
                     .into(),
                 ..Default::default()
             },
-            &make_request("GET", "Basic xyz")
+            &mut make_request("GET", "Basic xyz")
         )));
     }
 
@@ -290,7 +768,184 @@ mod tests {
                     .into(),
                 ..Default::default()
             },
-            &make_request("GET", "abcd")
+            &mut make_request("GET", "abcd")
         )));
     }
+
+    fn users_file() -> Arc<HtpasswdFile> {
+        Arc::new(
+            HtpasswdFile::load(
+                Path::new("tests/fixtures/basic_auth/htpasswd"),
+                Some(Path::new("tests/fixtures/basic_auth/htgroup")),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn make_user_request(user: &str, password: &str) -> Request<Body> {
+        let mut req = make_request("GET", "");
+        req.headers_mut()
+            .typed_insert(Authorization::basic(user, password));
+        req
+    }
+
+    fn get_status(result: Option<Result<Response<Body>, Error>>) -> Option<StatusCode> {
+        result.map(|result| result.unwrap().status())
+    }
+
+    #[test]
+    fn test_password_hashes() {
+        let file = users_file();
+        assert_eq!(file.len(), 4, "the apr1 hash is skipped");
+        for user in ["alice", "bob", "carol", "dave"] {
+            let hash = file.password_hash(user).unwrap();
+            assert!(verify_password("jq", &hash), "{user}");
+            assert!(!verify_password("qj", &hash), "{user}");
+        }
+        assert!(file.password_hash("erin").is_none());
+    }
+
+    #[test]
+    fn test_users_file() {
+        let opts = RequestHandlerOpts {
+            basic_auth_file: Some(users_file()),
+            basic_auth_realm: "Staff only".to_owned(),
+            ..Default::default()
+        };
+        assert!(pre_process(&opts, &mut make_user_request("dave", "jq")).is_none());
+        let resp = pre_process(&opts, &mut make_user_request("dave", "qj"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resp.headers()[WWW_AUTHENTICATE],
+            "Basic realm=\"Staff only\", charset=\"UTF-8\""
+        );
+        assert_eq!(
+            get_status(pre_process(&opts, &mut make_user_request("erin", "jq"))),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+
+        // The single credentials and the users file can be combined
+        let opts = RequestHandlerOpts {
+            basic_auth: "jq:$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q".into(),
+            basic_auth_file: Some(users_file()),
+            ..Default::default()
+        };
+        assert!(pre_process(&opts, &mut make_user_request("jq", "jq")).is_none());
+        assert!(pre_process(&opts, &mut make_user_request("bob", "jq")).is_none());
+    }
+
+    #[test]
+    fn test_users_groups() {
+        let opts = RequestHandlerOpts {
+            basic_auth_file: Some(users_file()),
+            basic_auth_groups: vec!["staff".to_owned(), "admins".to_owned()],
+            ..Default::default()
+        };
+        for user in ["alice", "bob", "carol"] {
+            assert!(pre_process(&opts, &mut make_user_request(user, "jq")).is_none());
+        }
+        assert_eq!(
+            get_status(pre_process(&opts, &mut make_user_request("dave", "jq"))),
+            Some(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn test_users_file_reload() {
+        let path = std::env::temp_dir().join(format!("sws-htpasswd-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "bob:$5$saltsalt$t2ZGPri3qXXXdtNtgb7MTmU9QbG0A5gcreblbyATPR.\n",
+        )
+        .unwrap();
+        let file = HtpasswdFile::load(&path, None).unwrap();
+        assert!(file.password_hash("bob").is_some());
+
+        std::thread::sleep(super::FILES_CHECK_INTERVAL);
+        std::fs::write(&path, "carol:$6$saltsalt$vq/hfF09E0u0d1UM.uXbNjUAnBbgfv6k.gZDSmd5.WG8Ovt4fLzHkk4qe04FHAKlqOAVB0YHGo3fOAx1/kfPb/\n").unwrap();
+        assert!(file.password_hash("carol").is_some());
+        assert!(file.password_hash("bob").is_none());
+
+        // The current users are kept if the file can't be loaded
+        std::thread::sleep(super::FILES_CHECK_INTERVAL);
+        std::fs::remove_file(&path).unwrap();
+        assert!(file.password_hash("carol").is_some());
+    }
+
+    #[test]
+    fn test_auth_rules() {
+        let rule = |source: &str, public: bool| Auth {
+            source: Glob::new(source).unwrap().compile_matcher(),
+            host: None,
+            users: vec!["bob".to_owned()],
+            groups: Vec::new(),
+            public,
+            #[cfg(feature = "jwt-auth")]
+            claims: Vec::new(),
+        };
+        let opts = RequestHandlerOpts {
+            basic_auth: "jq:$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q".into(),
+            advanced_opts: Some(Advanced {
+                auth: Some(vec![rule("/public/**", true), rule("/bob/**", false)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let request = |uri: &str| {
+            let mut req = make_user_request("jq", "jq");
+            *req.uri_mut() = uri.parse().unwrap();
+            req
+        };
+
+        let mut req = request("/public/index.html");
+        req.headers_mut().remove("authorization");
+        assert!(pre_process(&opts, &mut req).is_none());
+        assert!(pre_process(&opts, &mut request("/index.html")).is_none());
+        assert_eq!(
+            get_status(pre_process(&opts, &mut request("/bob/index.html"))),
+            Some(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn test_auth_rules_path_bypass() {
+        let opts = RequestHandlerOpts {
+            advanced_opts: Some(Advanced {
+                auth: Some(vec![Auth {
+                    source: Glob::new("/private/**").unwrap().compile_matcher(),
+                    host: None,
+                    users: vec!["bob".to_owned()],
+                    groups: Vec::new(),
+                    public: false,
+                    #[cfg(feature = "jwt-auth")]
+                    claims: Vec::new(),
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let status = |uri: &str| {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            get_status(pre_process(&opts, &mut req))
+        };
+
+        assert_eq!(status("/public/index.html"), None);
+        let expected = status("/private/secret.txt");
+        assert!(expected.is_some());
+        for uri in [
+            "/%70rivate/secret.txt",
+            "/private%2Fsecret.txt",
+            "//private/secret.txt",
+            "/./private/secret.txt",
+            "/private/./secret.txt",
+        ] {
+            assert_eq!(status(uri), expected, "{uri}");
+        }
+        assert_eq!(
+            status("/public/../private/secret.txt"),
+            Some(StatusCode::BAD_REQUEST)
+        );
+    }
 }
@@ -14,7 +14,7 @@ static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 use static_web_server::{
     settings::{cli::General, Commands},
-    Result, Settings,
+    signed_urls, Result, Settings,
 };
 
 fn main() -> Result {
@@ -55,6 +55,23 @@ fn main() -> Result {
                 }
                 return Ok(());
             }
+            Commands::SignUrl {
+                secret,
+                expires_in,
+                ip,
+                prefix,
+                base_url,
+                path,
+            } => {
+                let secret = secret
+                    .or(opts.general.signed_urls_secret)
+                    .unwrap_or_default();
+                let expires = signed_urls::unix_time() + expires_in;
+                let url = signed_urls::sign_url(&secret, &path, expires, ip, prefix.as_deref())?;
+                let base_url = base_url.unwrap_or_default();
+                println!("{}{url}", base_url.trim_end_matches('/'));
+                return Ok(());
+            }
         }
     }
 
@@ -33,7 +33,7 @@ use std::task::{Context, Poll};
 use tokio_util::io::{ReaderStream, StreamReader};
 
 use crate::{
-    error_page,
+    error_page, etag,
     handler::RequestHandlerOpts,
     headers_ext::{AcceptEncoding, ContentCoding},
     http_ext::MethodExt,
@@ -41,6 +41,9 @@ use crate::{
     Error, Result,
 };
 
+#[cfg(feature = "metrics")]
+use crate::metrics;
+
 lazy_static! {
     /// Contains a fixed list of common text-based MIME types that aren't recognizable in a generic way.
     static ref TEXT_MIME_TYPES: HashSet<&'static str> = [
@@ -110,7 +113,9 @@ pub(crate) fn post_process<T>(
     );
 
     // Auto compression based on the `Accept-Encoding` header
-    match auto(req.method(), req.headers(), opts.compression_level, resp) {
+    let compressed = tracing::debug_span!("compression")
+        .in_scope(|| auto(req.method(), req.headers(), opts.compression_level, resp));
+    match compressed {
         Ok(resp) => Ok(resp),
         Err(err) => {
             tracing::error!("error during body compression: {:?}", err);
@@ -156,26 +161,26 @@ pub fn auto(
 
         #[cfg(any(feature = "compression", feature = "compression-gzip"))]
         if encoding == ContentCoding::GZIP {
-            let (head, body) = resp.into_parts();
-            return Ok(gzip(head, body.into(), level));
+            return Ok(encode(encoding, resp, |head, body| gzip(head, body, level)));
         }
 
         #[cfg(any(feature = "compression", feature = "compression-deflate"))]
         if encoding == ContentCoding::DEFLATE {
-            let (head, body) = resp.into_parts();
-            return Ok(deflate(head, body.into(), level));
+            return Ok(encode(encoding, resp, |head, body| {
+                deflate(head, body, level)
+            }));
         }
 
         #[cfg(any(feature = "compression", feature = "compression-brotli"))]
         if encoding == ContentCoding::BROTLI {
-            let (head, body) = resp.into_parts();
-            return Ok(brotli(head, body.into(), level));
+            return Ok(encode(encoding, resp, |head, body| {
+                brotli(head, body, level)
+            }));
         }
 
         #[cfg(any(feature = "compression", feature = "compression-zstd"))]
         if encoding == ContentCoding::ZSTD {
-            let (head, body) = resp.into_parts();
-            return Ok(zstd(head, body.into(), level));
+            return Ok(encode(encoding, resp, |head, body| zstd(head, body, level)));
         }
 
         tracing::trace!("no compression feature matched the preferred encoding, probably not enabled or unsupported");
@@ -184,6 +189,25 @@ pub fn auto(
     Ok(resp)
 }
 
+/// Compresses the response body with the given encoder, counting its bytes for the metrics.
+fn encode(
+    encoding: ContentCoding,
+    resp: Response<Body>,
+    encoder: impl FnOnce(http::response::Parts, CompressableBody<Body, hyper::Error>) -> Response<Body>,
+) -> Response<Body> {
+    let encoder = |resp: Response<Body>| {
+        let (head, body) = resp.into_parts();
+        encoder(head, body.into())
+    };
+    #[cfg(feature = "metrics")]
+    return metrics::compression(encoding.as_str(), resp, encoder);
+    #[cfg(not(feature = "metrics"))]
+    {
+        let _ = encoding;
+        encoder(resp)
+    }
+}
+
 /// Checks whether the MIME type corresponds to any of the known text types.
 fn is_text(mime: Mime) -> bool {
     mime.type_() == mime::TEXT
@@ -217,6 +241,7 @@ pub fn gzip(
     let header = create_encoding_header(head.headers.remove(CONTENT_ENCODING), ContentCoding::GZIP);
     head.headers.remove(CONTENT_LENGTH);
     head.headers.insert(CONTENT_ENCODING, header);
+    etag::weaken(&mut head.headers);
     Response::from_parts(head, body)
 }
 
@@ -247,6 +272,7 @@ pub fn deflate(
     );
     head.headers.remove(CONTENT_LENGTH);
     head.headers.insert(CONTENT_ENCODING, header);
+    etag::weaken(&mut head.headers);
     Response::from_parts(head, body)
 }
 
@@ -275,6 +301,7 @@ pub fn brotli(
         create_encoding_header(head.headers.remove(CONTENT_ENCODING), ContentCoding::BROTLI);
     head.headers.remove(CONTENT_LENGTH);
     head.headers.insert(CONTENT_ENCODING, header);
+    etag::weaken(&mut head.headers);
     Response::from_parts(head, body)
 }
 
@@ -302,6 +329,7 @@ pub fn zstd(
     let header = create_encoding_header(head.headers.remove(CONTENT_ENCODING), ContentCoding::ZSTD);
     head.headers.remove(CONTENT_LENGTH);
     head.headers.insert(CONTENT_ENCODING, header);
+    etag::weaken(&mut head.headers);
     Response::from_parts(head, body)
 }
 
@@ -7,13 +7,15 @@
 //!
 
 use headers::{
-    HeaderMap, HeaderMapExt, HeaderValue, IfModifiedSince, IfRange, IfUnmodifiedSince,
-    LastModified, Range,
+    ETag, HeaderMap, HeaderMapExt, HeaderValue, IfMatch, IfModifiedSince, IfNoneMatch, IfRange,
+    IfUnmodifiedSince, LastModified, Range,
 };
 use hyper::{Body, Response, StatusCode};
 
 #[derive(Debug)]
 pub(crate) struct ConditionalHeaders {
+    pub(crate) if_match: Option<IfMatch>,
+    pub(crate) if_none_match: Option<IfNoneMatch>,
     pub(crate) if_modified_since: Option<IfModifiedSince>,
     pub(crate) if_unmodified_since: Option<IfUnmodifiedSince>,
     pub(crate) if_range: Option<IfRange>,
@@ -22,12 +24,16 @@ pub(crate) struct ConditionalHeaders {
 
 impl ConditionalHeaders {
     pub(crate) fn new(headers: &HeaderMap<HeaderValue>) -> Self {
+        let if_match = headers.typed_get::<IfMatch>();
+        let if_none_match = headers.typed_get::<IfNoneMatch>();
         let if_modified_since = headers.typed_get::<IfModifiedSince>();
         let if_unmodified_since = headers.typed_get::<IfUnmodifiedSince>();
         let if_range = headers.typed_get::<IfRange>();
         let range = headers.typed_get::<Range>();
 
         Self {
+            if_match,
+            if_none_match,
             if_modified_since,
             if_unmodified_since,
             if_range,
@@ -37,8 +43,27 @@ impl ConditionalHeaders {
 }
 
 impl ConditionalHeaders {
-    pub(crate) fn check(self, last_modified: Option<LastModified>) -> ConditionalBody {
-        if let Some(since) = self.if_unmodified_since {
+    /// Evaluates the request preconditions against the current representation
+    /// following the order defined by RFC 9110, section 13.2.2.
+    pub(crate) fn check(
+        self,
+        last_modified: Option<LastModified>,
+        etag: Option<&ETag>,
+    ) -> ConditionalBody {
+        // 1. `If-Match` takes precedence over `If-Unmodified-Since`
+        if let Some(if_match) = self.if_match {
+            let precondition = match etag {
+                Some(etag) => if_match.precondition_passes(etag),
+                // The representation exists, so only `*` can match
+                None => if_match.is_any(),
+            };
+
+            tracing::trace!("if-match? {:?} vs {:?} = {}", if_match, etag, precondition);
+            if !precondition {
+                return ConditionalBody::NoBody(empty_response(StatusCode::PRECONDITION_FAILED));
+            }
+        } else if let Some(since) = self.if_unmodified_since {
+            // 2. `If-Unmodified-Since`
             let precondition = last_modified
                 .map(|time| since.precondition_passes(time.into()))
                 .unwrap_or(false);
@@ -50,13 +75,30 @@ impl ConditionalHeaders {
                 precondition
             );
             if !precondition {
-                let mut res = Response::new(Body::empty());
-                *res.status_mut() = StatusCode::PRECONDITION_FAILED;
-                return ConditionalBody::NoBody(res);
+                return ConditionalBody::NoBody(empty_response(StatusCode::PRECONDITION_FAILED));
             }
         }
 
-        if let Some(since) = self.if_modified_since {
+        // 3. `If-None-Match` takes precedence over `If-Modified-Since`
+        if let Some(if_none_match) = self.if_none_match {
+            let precondition = match etag {
+                Some(etag) => if_none_match.precondition_passes(etag),
+                // The representation exists, so only `*` can match
+                None => if_none_match != IfNoneMatch::any(),
+            };
+
+            tracing::trace!(
+                "if-none-match? {:?} vs {:?} = {}",
+                if_none_match,
+                etag,
+                precondition
+            );
+            // NOTE: only safe methods (`GET` and `HEAD`) reach this point
+            if !precondition {
+                return ConditionalBody::NoBody(not_modified(last_modified, etag));
+            }
+        } else if let Some(since) = self.if_modified_since {
+            // 4. `If-Modified-Since`
             tracing::trace!(
                 "if-modified-since? header = {:?}, file = {:?}",
                 since,
@@ -67,16 +109,20 @@ impl ConditionalHeaders {
                 // no last_modified means its always modified
                 .unwrap_or(false);
             if unmodified {
-                let mut res = Response::new(Body::empty());
-                *res.status_mut() = StatusCode::NOT_MODIFIED;
-                return ConditionalBody::NoBody(res);
+                return ConditionalBody::NoBody(not_modified(last_modified, etag));
             }
         }
 
+        // 5. `If-Range`
         if let Some(if_range) = self.if_range {
-            tracing::trace!("if-range? {:?} vs {:?}", if_range, last_modified);
+            tracing::trace!(
+                "if-range? {:?} vs {:?} / {:?}",
+                if_range,
+                last_modified,
+                etag
+            );
 
-            let can_range = !if_range.is_modified(None, last_modified.as_ref());
+            let can_range = !if_range.is_modified(etag, last_modified.as_ref());
             if !can_range {
                 return ConditionalBody::WithBody(None);
             }
@@ -86,6 +132,24 @@ impl ConditionalHeaders {
     }
 }
 
+fn empty_response(status: StatusCode) -> Response<Body> {
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = status;
+    res
+}
+
+/// A `304 Not Modified` response carrying the validators of the representation.
+fn not_modified(last_modified: Option<LastModified>, etag: Option<&ETag>) -> Response<Body> {
+    let mut res = empty_response(StatusCode::NOT_MODIFIED);
+    if let Some(etag) = etag {
+        res.headers_mut().typed_insert(etag.clone());
+    }
+    if let Some(last_modified) = last_modified {
+        res.headers_mut().typed_insert(last_modified);
+    }
+    res
+}
+
 pub(crate) enum ConditionalBody {
     NoBody(Response<Body>),
     WithBody(Option<Range>),
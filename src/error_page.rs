@@ -11,7 +11,10 @@ use hyper::{Body, Method, Response, StatusCode, Uri};
 use mime_guess::mime;
 use std::path::Path;
 
-use crate::{helpers, http_ext::MethodExt, Result};
+use crate::{helpers, http_ext::MethodExt, request_id, Result};
+
+/// Placeholder of custom error pages replaced by the request ID.
+const REQUEST_ID_PLACEHOLDER: &str = "{{request_id}}";
 
 /// It returns a HTTP error response which also handles available `404` or `50x` HTML content.
 pub fn error_response(
@@ -90,6 +93,7 @@ pub fn error_response(
         _ => status_code,
     };
 
+    let request_id = request_id::current();
     if page_content.is_empty() {
         page_content = [
             "<html><head><title>",
@@ -100,9 +104,16 @@ pub fn error_response(
             status_code.as_str(),
             " ",
             status_code.canonical_reason().unwrap_or_default(),
-            "</h1></center></body></html>",
+            "</h1>",
         ]
         .concat();
+        if let Some(id) = &request_id {
+            page_content.push_str(&format!("<p>Request ID: {}</p>", id.as_str()));
+        }
+        page_content.push_str("</center></body></html>");
+    } else if page_content.contains(REQUEST_ID_PLACEHOLDER) {
+        let id = request_id.as_ref().map_or("", |id| id.as_str());
+        page_content = page_content.replace(REQUEST_ID_PLACEHOLDER, id);
     }
 
     let mut body = Body::empty();
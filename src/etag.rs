@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module that provides `ETag` (entity tag) generation for file responses.
+//!
+
+use clap::ValueEnum;
+use headers::ETag;
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::handler::RequestHandlerOpts;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Defines how the `ETag` header of a file response is generated.
+pub enum ETagMode {
+    /// Weak entity tag based on the file modification time and size (default).
+    Weak,
+    /// Strong entity tag based on a SHA-256 hash of the file content.
+    Strong,
+    /// Do not generate entity tags.
+    Off,
+}
+
+/// Maximum number of files whose strong `ETag` is kept in the cache.
+const STRONG_CACHE_CAPACITY: usize = 10_000;
+
+lazy_static! {
+    /// Strong `ETag`s of the files along with the modification time and size they were computed for.
+    static ref STRONG_CACHE: Mutex<HashMap<PathBuf, (SystemTime, u64, ETag)>> = Mutex::default();
+}
+
+/// Initializes the `ETag` generation.
+pub fn init(mode: ETagMode, handler_opts: &mut RequestHandlerOpts) {
+    handler_opts.etag = mode;
+    server_info!("etag: mode={mode:?}");
+}
+
+/// Builds a weak `ETag` from the file modification time and size.
+/// E.g. `W/"65a7c6f1.1b3c9a80-2af"`.
+pub(crate) fn weak(meta: &Metadata) -> Option<ETag> {
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    format!(
+        "W/\"{:x}.{:x}-{:x}\"",
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        meta.len()
+    )
+    .parse()
+    .ok()
+}
+
+/// Builds a strong `ETag` from a SHA-256 hash of the whole reader content.
+pub(crate) fn strong_from_reader<R: Read>(mut reader: R) -> io::Result<Option<ETag>> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(from_digest(hasher.finalize().as_slice()))
+}
+
+/// Builds a strong `ETag` of an opened file, hashing its content on a blocking thread
+/// only if it isn't cached yet for the current modification time and size of the file.
+/// The `path` and `meta` params must belong to the opened file (e.g. a precompressed variant)
+/// and the file is rewound after being hashed.
+pub(crate) async fn strong(path: &Path, meta: &Metadata, file: &File) -> io::Result<Option<ETag>> {
+    let version = meta.modified().ok().map(|modified| (modified, meta.len()));
+    if let Some((modified, len)) = version {
+        let cache = STRONG_CACHE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((_, _, etag)) = cache
+            .get(path)
+            .filter(|(m, l, _)| *m == modified && *l == len)
+        {
+            return Ok(Some(etag.clone()));
+        }
+    }
+
+    let mut reader = file.try_clone()?;
+    let etag = tokio::task::spawn_blocking(move || {
+        let etag = strong_from_reader(BufReader::new(&mut reader))?;
+        reader.rewind()?;
+        Ok::<_, io::Error>(etag)
+    })
+    .await
+    .map_err(io::Error::other)??;
+
+    if let (Some((modified, len)), Some(etag)) = (version, &etag) {
+        let mut cache = STRONG_CACHE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if cache.len() >= STRONG_CACHE_CAPACITY && !cache.contains_key(path) {
+            // Make room by evicting an arbitrary entry
+            if let Some(key) = cache.keys().next().cloned() {
+                cache.remove(&key);
+            }
+        }
+        cache.insert(path.to_owned(), (modified, len, etag.clone()));
+    }
+    Ok(etag)
+}
+
+/// Turns a strong `ETag` header into a weak one.
+/// It's used when a response body is transformed (e.g. compressed on the fly)
+/// so it's no longer byte-for-byte identical to the tagged representation.
+#[cfg(any(
+    feature = "compression",
+    feature = "compression-deflate",
+    feature = "compression-gzip",
+    feature = "compression-brotli",
+    feature = "compression-zstd",
+))]
+pub(crate) fn weaken(headers: &mut headers::HeaderMap) {
+    use hyper::header::ETAG;
+
+    if let Some(value) = headers.get(ETAG) {
+        if value.as_bytes().starts_with(b"\"") {
+            let mut weak = b"W/".to_vec();
+            weak.extend_from_slice(value.as_bytes());
+            if let Ok(weak) = weak.try_into() {
+                headers.insert(ETAG, weak);
+            }
+        }
+    }
+}
+
+fn from_digest(digest: &[u8]) -> Option<ETag> {
+    // 128 bits of the digest are more than enough to identify a representation
+    let hex = digest[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    format!("\"{hex}\"").parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strong, strong_from_reader, weak};
+    use headers::{HeaderMap, HeaderMapExt};
+    use std::fs::File;
+    use std::io::{Seek, Write};
+    use std::time::{Duration, SystemTime};
+
+    fn etag_str(etag: headers::ETag) -> String {
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(etag);
+        headers["etag"].to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn weak_etag_from_metadata() {
+        let meta = std::fs::metadata("docker/public/index.html").unwrap();
+        let etag = etag_str(weak(&meta).unwrap());
+        assert!(etag.starts_with("W/\""));
+        assert!(etag.ends_with(&format!("-{:x}\"", meta.len())));
+    }
+
+    #[test]
+    fn strong_etag_from_content() {
+        let a = strong_from_reader(&b"hello"[..]).unwrap().unwrap();
+        let b = strong_from_reader(&b"hello"[..]).unwrap().unwrap();
+        let c = strong_from_reader(&b"hello!"[..]).unwrap().unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(etag_str(a), "\"2cf24dba5fb0a30e26e83b2ac5b9e29e\"");
+    }
+
+    async fn strong_etag(path: &std::path::Path) -> headers::ETag {
+        let mut file = File::open(path).unwrap();
+        let meta = file.metadata().unwrap();
+        let etag = strong(path, &meta, &file).await.unwrap().unwrap();
+        assert_eq!(file.stream_position().unwrap(), 0);
+        etag
+    }
+
+    #[tokio::test]
+    async fn strong_etag_cached_per_file_version() {
+        let path = std::env::temp_dir().join(format!("sws-etag-{}.txt", std::process::id()));
+
+        std::fs::write(&path, "hello").unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let hello = strong_etag(&path).await;
+        assert_eq!(
+            etag_str(hello.clone()),
+            "\"2cf24dba5fb0a30e26e83b2ac5b9e29e\""
+        );
+
+        // Same modification time and size, the cached value is used
+        std::fs::write(&path, "howdy").unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified).unwrap();
+        assert_eq!(strong_etag(&path).await, hello);
+
+        // The file changed, so it's hashed again
+        let mut file = File::options().append(true).open(&path).unwrap();
+        file.write_all(b"!").unwrap();
+        assert_ne!(strong_etag(&path).await, hello);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(
+        feature = "compression",
+        feature = "compression-deflate",
+        feature = "compression-gzip",
+        feature = "compression-brotli",
+        feature = "compression-zstd",
+    ))]
+    fn weaken_strong_etag() {
+        use super::weaken;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("etag", "\"abc\"".parse().unwrap());
+        weaken(&mut headers);
+        assert_eq!(headers["etag"], "W/\"abc\"");
+
+        weaken(&mut headers);
+        assert_eq!(headers["etag"], "W/\"abc\"");
+    }
+}
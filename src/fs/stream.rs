@@ -8,8 +8,9 @@
 
 use bytes::{Bytes, BytesMut};
 use futures_util::Stream;
+use std::collections::VecDeque;
 use std::fs::Metadata;
-use std::io::Read;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
@@ -46,6 +47,79 @@ impl<T: Read + Unpin> Stream for FileStream<T> {
     }
 }
 
+/// A single part of a `multipart/byteranges` body.
+#[derive(Debug)]
+pub(crate) struct MultipartPart {
+    /// The boundary delimiter and the part headers preceding the data.
+    pub(crate) header: Bytes,
+    /// The first byte position of the part (inclusive).
+    pub(crate) start: u64,
+    /// The last byte position of the part (exclusive).
+    pub(crate) end: u64,
+}
+
+/// A stream that yields a `multipart/byteranges` body by seeking the reader
+/// to every part in turn, so the parts are never buffered as a whole.
+#[derive(Debug)]
+pub(crate) struct MultipartStream<T> {
+    reader: T,
+    buf_size: usize,
+    parts: VecDeque<MultipartPart>,
+    remaining: u64,
+    closing: Option<Bytes>,
+}
+
+impl<T> MultipartStream<T> {
+    pub(crate) fn new(
+        reader: T,
+        buf_size: usize,
+        parts: VecDeque<MultipartPart>,
+        closing: Bytes,
+    ) -> Self {
+        Self {
+            reader,
+            buf_size,
+            parts,
+            remaining: 0,
+            closing: Some(closing),
+        }
+    }
+}
+
+impl<T: Read + Seek + Unpin> Stream for MultipartStream<T> {
+    type Item = Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pinned = Pin::into_inner(self);
+
+        if pinned.remaining > 0 {
+            let size = std::cmp::min(pinned.buf_size as u64, pinned.remaining) as usize;
+            let mut buf = BytesMut::zeroed(size);
+            return match pinned.reader.read(&mut buf[..]) {
+                Ok(0) => Poll::Ready(Some(Err(anyhow::Error::from(io::Error::from(
+                    io::ErrorKind::UnexpectedEof,
+                ))))),
+                Ok(n) => {
+                    buf.truncate(n);
+                    pinned.remaining -= n as u64;
+                    Poll::Ready(Some(Ok(buf.freeze())))
+                }
+                Err(err) => Poll::Ready(Some(Err(anyhow::Error::from(err)))),
+            };
+        }
+
+        if let Some(part) = pinned.parts.pop_front() {
+            if let Err(err) = pinned.reader.seek(SeekFrom::Start(part.start)) {
+                return Poll::Ready(Some(Err(anyhow::Error::from(err))));
+            }
+            pinned.remaining = part.end - part.start;
+            return Poll::Ready(Some(Ok(part.header)));
+        }
+
+        Poll::Ready(pinned.closing.take().map(Ok))
+    }
+}
+
 pub(crate) fn optimal_buf_size(metadata: &Metadata) -> usize {
     let block_size = get_block_size(metadata);
     // If file length is smaller than block size,
@@ -6,13 +6,16 @@
 //! Request handler module intended to manage incoming HTTP requests.
 //!
 
-use hyper::{Body, Request, Response, StatusCode};
+use globset::GlobSet;
+use hyper::{header::HeaderName, Body, Request, Response, StatusCode};
+use ipnet::IpNet;
 use std::{
     future::Future,
     net::{IpAddr, SocketAddr},
     path::PathBuf,
     sync::Arc,
 };
+use tracing::{field::Empty, Instrument, Span};
 
 #[cfg(any(
     feature = "compression",
@@ -24,22 +27,39 @@ use std::{
 use crate::{compression, compression_static};
 
 #[cfg(feature = "basic-auth")]
-use crate::basic_auth;
+use crate::basic_auth::{self, HtpasswdFile};
 
 #[cfg(feature = "fallback-page")]
 use crate::fallback_page;
 
-#[cfg(all(unix, feature = "experimental"))]
+#[cfg(feature = "jwt-auth")]
+use crate::jwt_auth::JwtAuth;
+
+#[cfg(feature = "http2")]
+use crate::mtls;
+
+#[cfg(feature = "otlp")]
+use crate::otlp;
+
+#[cfg(feature = "metrics")]
 use crate::metrics;
 
 #[cfg(feature = "experimental")]
 use crate::mem_cache::cache::MemCacheOpts;
 
 use crate::{
-    control_headers, cors, custom_headers, error_page, health,
+    access_log::{self, AccessLog},
+    control_headers, cors, custom_headers, error_page,
+    etag::ETagMode,
+    health,
     http_ext::MethodExt,
-    log_addr, maintenance_mode, redirects, rewrites, security_headers,
+    ip_access,
+    log_addr::{self, ForwardedHeader},
+    maintenance_mode, redirects,
+    request_id::{self, RequestId},
+    rewrites, security_headers,
     settings::Advanced,
+    signed_urls,
     static_files::{self, HandleOpts},
     virtual_hosts, Error, Result,
 };
@@ -98,6 +118,22 @@ pub struct RequestHandlerOpts {
     #[cfg(feature = "basic-auth")]
     #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
     pub basic_auth: String,
+    /// Basic auth users and groups files.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_file: Option<Arc<HtpasswdFile>>,
+    /// Basic auth groups allowed to access, all users if empty.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_groups: Vec<String>,
+    /// Basic auth realm of the `WWW-Authenticate` header.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_realm: String,
+    /// JWT auth feature.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth: Option<Arc<JwtAuth>>,
     /// Index files feature.
     pub index_files: Vec<String>,
     /// Log remote address feature.
@@ -106,23 +142,53 @@ pub struct RequestHandlerOpts {
     pub log_forwarded_for: bool,
     /// Trusted IPs for remote addresses.
     pub trusted_proxies: Vec<IpAddr>,
+    /// Header the client IP is taken from behind a trusted proxy.
+    pub trusted_proxies_header: ForwardedHeader,
+    /// Access log feature.
+    pub access_log: Option<Arc<AccessLog>>,
+    /// Request ID response header, the feature is disabled if not set.
+    pub request_id_header: Option<HeaderName>,
+    /// OpenTelemetry traces export feature.
+    #[cfg(feature = "otlp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "otlp")))]
+    pub otlp: bool,
     /// Redirect trailing slash feature.
     pub redirect_trailing_slash: bool,
     /// Ignore hidden files feature.
     pub ignore_hidden_files: bool,
     /// Prevent following symlinks for files and directories.
     pub disable_symlinks: bool,
+    /// The `ETag` generation mode.
+    pub etag: ETagMode,
     /// Health endpoint feature.
     pub health: bool,
-    /// Metrics endpoint feature (experimental).
-    #[cfg(all(unix, feature = "experimental"))]
-    pub experimental_metrics: bool,
+    /// Whether the health, readiness and metrics endpoints are served by the admin listener instead.
+    pub admin_listener: bool,
+    /// Metrics endpoint feature.
+    #[cfg(feature = "metrics")]
+    pub metrics: bool,
     /// Maintenance mode feature.
     pub maintenance_mode: bool,
     /// Custom HTTP status for when entering into maintenance mode.
     pub maintenance_mode_status: StatusCode,
     /// Custom maintenance mode HTML file.
     pub maintenance_mode_file: PathBuf,
+    /// File enabling the maintenance mode while it exists, relative to the root directory if not absolute.
+    pub maintenance_mode_trigger_file: Option<PathBuf>,
+    /// Hosts the maintenance mode is scoped to, all if empty.
+    pub maintenance_mode_hosts: Vec<String>,
+    /// Path glob patterns the maintenance mode is scoped to, all if not set.
+    pub maintenance_mode_paths: Option<GlobSet>,
+    /// Client IPs or CIDRs allowed to bypass the maintenance mode.
+    pub maintenance_mode_allow_ips: Vec<IpNet>,
+    /// Shared secret verifying the signed URLs, which are required if defined.
+    pub signed_urls_secret: Option<String>,
+    /// Path glob patterns requiring a signed URL, all if not set.
+    pub signed_urls_paths: Option<GlobSet>,
+    /// Client IPs or CIDRs allowed to access, all if empty.
+    pub allow_ips: Vec<IpNet>,
+    /// Client IPs or CIDRs denied access.
+    pub deny_ips: Vec<IpNet>,
 
     /// Advanced options from the config file.
     pub advanced_opts: Option<Advanced>,
@@ -159,19 +225,42 @@ impl Default for RequestHandlerOpts {
             page_fallback: Vec::new(),
             #[cfg(feature = "basic-auth")]
             basic_auth: String::new(),
+            #[cfg(feature = "basic-auth")]
+            basic_auth_file: None,
+            #[cfg(feature = "basic-auth")]
+            basic_auth_groups: Vec::new(),
+            #[cfg(feature = "basic-auth")]
+            basic_auth_realm: basic_auth::DEFAULT_REALM.to_owned(),
+            #[cfg(feature = "jwt-auth")]
+            jwt_auth: None,
             index_files: vec!["index.html".into()],
             log_remote_address: false,
             log_forwarded_for: false,
             trusted_proxies: Vec::new(),
+            trusted_proxies_header: ForwardedHeader::XForwardedFor,
+            access_log: None,
+            request_id_header: None,
+            #[cfg(feature = "otlp")]
+            otlp: false,
             redirect_trailing_slash: true,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             health: false,
-            #[cfg(all(unix, feature = "experimental"))]
-            experimental_metrics: false,
+            admin_listener: false,
+            #[cfg(feature = "metrics")]
+            metrics: false,
             maintenance_mode: false,
             maintenance_mode_status: StatusCode::SERVICE_UNAVAILABLE,
             maintenance_mode_file: PathBuf::new(),
+            maintenance_mode_trigger_file: None,
+            maintenance_mode_hosts: Vec::new(),
+            maintenance_mode_paths: None,
+            maintenance_mode_allow_ips: Vec::new(),
+            signed_urls_secret: None,
+            signed_urls_paths: None,
+            allow_ips: Vec::new(),
+            deny_ips: Vec::new(),
             advanced_opts: None,
         }
     }
@@ -201,13 +290,28 @@ impl RequestHandler {
         let compression_static = self.opts.compression_static;
         let ignore_hidden_files = self.opts.ignore_hidden_files;
         let disable_symlinks = self.opts.disable_symlinks;
+        let etag = self.opts.etag;
         let index_files: Vec<&str> = self.opts.index_files.iter().map(|s| s.as_str()).collect();
         #[cfg(feature = "experimental")]
         let memory_cache = self.opts.memory_cache.as_ref();
 
+        // Request ID to correlate the log entries of the request
+        let request_id = request_id::pre_process(&self.opts, req, remote_addr);
+        let span = self.request_span(request_id.as_ref());
+        #[cfg(feature = "otlp")]
+        otlp::pre_process(&self.opts, req, remote_addr, &span);
+        let entered = span.enter();
+
         log_addr::pre_process(&self.opts, req, remote_addr);
 
-        async move {
+        // Access log entry completed once the response is sent
+        let access_log = access_log::pre_process(&self.opts, req, remote_addr);
+
+        // Request metrics recorded once the response is sent
+        #[cfg(feature = "metrics")]
+        let request_metrics = metrics::start(&self.opts, req);
+
+        let response = async move {
             // Reject if the HTTP request method is not allowed
             if !req.method().is_allowed() {
                 return error_page::error_response(
@@ -219,13 +323,18 @@ impl RequestHandler {
                 );
             }
 
+            // IP access control
+            if let Some(response) = ip_access::pre_process(&self.opts, req, remote_addr) {
+                return response;
+            }
+
             // Health endpoint check
             if let Some(result) = health::pre_process(&self.opts, req) {
                 return result;
             }
 
             // Metrics endpoint check
-            #[cfg(all(unix, feature = "experimental"))]
+            #[cfg(feature = "metrics")]
             if let Some(result) = metrics::pre_process(&self.opts, req) {
                 return result;
             }
@@ -235,6 +344,12 @@ impl RequestHandler {
                 return result;
             }
 
+            // TLS client certificate authorization
+            #[cfg(feature = "http2")]
+            if let Some(response) = mtls::pre_process(&self.opts, req) {
+                return response;
+            }
+
             // `Basic` HTTP Authorization Schema
             #[cfg(feature = "basic-auth")]
             if let Some(response) = basic_auth::pre_process(&self.opts, req) {
@@ -242,7 +357,12 @@ impl RequestHandler {
             }
 
             // Maintenance Mode
-            if let Some(response) = maintenance_mode::pre_process(&self.opts, req) {
+            if let Some(response) = maintenance_mode::pre_process(&self.opts, req, remote_addr) {
+                return response;
+            }
+
+            // Signed URLs
+            if let Some(response) = signed_urls::pre_process(&self.opts, req, remote_addr) {
                 return response;
             }
 
@@ -252,10 +372,32 @@ impl RequestHandler {
             }
 
             // Rewrites
+            let uri = req.uri().clone();
             if let Some(result) = rewrites::pre_process(&self.opts, req) {
                 return result;
             }
 
+            // Evaluate the path and host scoped access rules again for a rewritten request
+            if *req.uri() != uri {
+                if let Some(response) = ip_access::pre_process(&self.opts, req, remote_addr) {
+                    return response;
+                }
+
+                #[cfg(feature = "http2")]
+                if let Some(response) = mtls::pre_process(&self.opts, req) {
+                    return response;
+                }
+
+                #[cfg(feature = "basic-auth")]
+                if let Some(response) = basic_auth::pre_process(&self.opts, req) {
+                    return response;
+                }
+
+                if let Some(response) = signed_urls::pre_process(&self.opts, req, remote_addr) {
+                    return response;
+                }
+            }
+
             // Advanced options
             if let Some(advanced) = &self.opts.advanced_opts {
                 // If the "Host" header matches any virtual_host, change the root directory
@@ -288,7 +430,9 @@ impl RequestHandler {
                 ignore_hidden_files,
                 index_files,
                 disable_symlinks,
+                etag,
             })
+            .instrument(tracing::debug_span!("static_files"))
             .await
             {
                 Ok(result) => (result.resp, Some(result.file_path)),
@@ -338,9 +482,49 @@ impl RequestHandler {
             let resp = security_headers::post_process(&self.opts, req, resp)?;
 
             // Add/update custom headers
-            let resp = custom_headers::post_process(&self.opts, req, resp, file_path.as_ref())?;
+            let mut resp = custom_headers::post_process(&self.opts, req, resp, file_path.as_ref())?;
+
+            // Keep the served file path for the access log
+            access_log::set_served_file(&self.opts, &mut resp, file_path);
 
             Ok(resp)
+        };
+
+        drop(entered);
+
+        let scoped_id = request_id.clone();
+        #[cfg(feature = "otlp")]
+        let request_span = span.clone();
+        let response = async move {
+            let result = response.await.map(|resp| {
+                #[cfg(feature = "otlp")]
+                otlp::post_process(&self.opts, &request_span, &resp);
+                request_id::post_process(&self.opts, request_id.as_ref(), resp)
+            });
+            #[cfg(feature = "metrics")]
+            let result = metrics::post_process(request_metrics, result);
+            access_log::post_process(access_log, result)
+        };
+
+        request_id::scope(scoped_id, response.instrument(span))
+    }
+
+    /// Creates the span wrapping the whole request pipeline if request IDs or traces are enabled.
+    fn request_span(&self, id: Option<&RequestId>) -> Span {
+        #[cfg(feature = "otlp")]
+        let traced = self.opts.otlp;
+        #[cfg(not(feature = "otlp"))]
+        let traced = false;
+
+        if id.is_none() && !traced {
+            return Span::none();
         }
+        // The span name and kind are only recorded when the traces are exported
+        tracing::info_span!(
+            "request",
+            id = id.map(RequestId::as_str),
+            otel.name = Empty,
+            otel.kind = Empty,
+        )
     }
 }
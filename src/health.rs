@@ -3,13 +3,53 @@
 // See https://static-web-server.net/ for more information
 // Copyright (C) 2019-present Jose Quintana <joseluisq.net>
 
-//! Module providing the health endpoint.
+//! Module providing the health (liveness) and readiness endpoints.
 //!
 
 use headers::{ContentType, HeaderMapExt};
-use hyper::{Body, Method, Request, Response};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
 
-use crate::{handler::RequestHandlerOpts, Error};
+use crate::{handler::RequestHandlerOpts, maintenance_mode, Error};
+
+/// Path of the health endpoint on the public listeners.
+const HEALTH_PATH: &str = "/health";
+
+/// Path of the readiness endpoint on the public listeners.
+const READY_PATH: &str = "/ready";
+
+/// The current server state reported by the readiness endpoint.
+static STATE: AtomicU8 = AtomicU8::new(ServerState::Starting as u8);
+
+/// The lifecycle state of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum ServerState {
+    /// The listeners are being set up.
+    Starting,
+    /// All the listeners are running.
+    Serving,
+    /// The graceful shutdown was requested.
+    ShuttingDown,
+}
+
+impl ServerState {
+    /// Returns the current server state.
+    pub(crate) fn get() -> Self {
+        match STATE.load(Ordering::Relaxed) {
+            0 => Self::Starting,
+            1 => Self::Serving,
+            _ => Self::ShuttingDown,
+        }
+    }
+
+    /// Sets the current server state.
+    pub(crate) fn set(self) {
+        STATE.store(self as u8, Ordering::Relaxed);
+    }
+}
 
 /// Initializes the health endpoint.
 pub fn init(enabled: bool, handler_opts: &mut RequestHandlerOpts) {
@@ -22,15 +62,18 @@ pub fn pre_process<T>(
     opts: &RequestHandlerOpts,
     req: &Request<T>,
 ) -> Option<Result<Response<Body>, Error>> {
-    if !opts.health {
+    if !is_health_endpoint(opts, req) {
         return None;
     }
-
-    if !is_health_endpoint(req) {
-        return None;
+    match req.uri().path() {
+        READY_PATH => readiness_response(opts, req.method()).map(Ok),
+        _ => response(req.method()).map(Ok),
     }
+}
 
-    let body = match *req.method() {
+/// Returns the health endpoint response if the method is supported.
+pub(crate) fn response(method: &Method) -> Option<Response<Body>> {
+    let body = match *method {
         Method::HEAD => Body::empty(),
         Method::GET => Body::from("OK"),
         _ => return None,
@@ -38,11 +81,89 @@ pub fn pre_process<T>(
 
     let mut resp = Response::new(body);
     resp.headers_mut().typed_insert(ContentType::html());
-    Some(Ok(resp))
+    Some(resp)
 }
 
-pub(crate) fn is_health_endpoint<T>(req: &Request<T>) -> bool {
-    req.uri().path() == "/health"
+/// Returns the readiness endpoint response describing every check if the method is supported.
+pub(crate) fn readiness_response(
+    opts: &RequestHandlerOpts,
+    method: &Method,
+) -> Option<Response<Body>> {
+    if method != Method::GET && method != Method::HEAD {
+        return None;
+    }
+
+    let (ready, checks) = readiness_checks(opts);
+    let body = if method == Method::GET {
+        let status = if ready { "ready" } else { "not_ready" };
+        Body::from(json!({ "status": status, "checks": checks }).to_string())
+    } else {
+        Body::empty()
+    };
+
+    let mut resp = Response::new(body);
+    if !ready {
+        *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    resp.headers_mut().typed_insert(ContentType::json());
+    Some(resp)
+}
+
+/// Runs the readiness checks, returning whether all of them passed and their description.
+fn readiness_checks(opts: &RequestHandlerOpts) -> (bool, Vec<Value>) {
+    let mut ready = true;
+    let mut checks = Vec::new();
+    let mut check = |name: String, error: Option<String>| {
+        let mut check = Map::new();
+        check.insert("name".to_owned(), name.into());
+        check.insert(
+            "status".to_owned(),
+            if error.is_some() { "fail" } else { "pass" }.into(),
+        );
+        if let Some(error) = error {
+            ready = false;
+            check.insert("error".to_owned(), error.into());
+        }
+        checks.push(Value::Object(check));
+    };
+
+    let state = ServerState::get();
+    check(
+        "startup".to_owned(),
+        (state == ServerState::Starting).then(|| "the server is starting up".to_owned()),
+    );
+    check(
+        "shutdown".to_owned(),
+        (state == ServerState::ShuttingDown).then(|| "the server is shutting down".to_owned()),
+    );
+    check(
+        "maintenance_mode".to_owned(),
+        maintenance_mode::is_enabled(opts).then(|| "the maintenance mode is enabled".to_owned()),
+    );
+    check("root".to_owned(), readable_dir(&opts.root_dir));
+    if let Some(vhosts) = opts
+        .advanced_opts
+        .as_ref()
+        .and_then(|advanced| advanced.virtual_hosts.as_ref())
+    {
+        for vhost in vhosts {
+            check(format!("vhost:{}", vhost.host), readable_dir(&vhost.root));
+        }
+    }
+
+    (ready, checks)
+}
+
+/// Checks that a root directory can be read, returning the error otherwise.
+fn readable_dir(path: &Path) -> Option<String> {
+    std::fs::read_dir(path)
+        .err()
+        .map(|err| format!("unable to read the directory {}: {err}", path.display()))
+}
+
+/// Checks whether the request targets the health or readiness endpoints of the public listeners.
+pub(crate) fn is_health_endpoint<T>(opts: &RequestHandlerOpts, req: &Request<T>) -> bool {
+    opts.health && !opts.admin_listener && matches!(req.uri().path(), HEALTH_PATH | READY_PATH)
 }
 
 #[cfg(test)]
@@ -106,4 +227,74 @@ mod tests {
         )
         .is_some());
     }
+
+    #[tokio::test]
+    async fn test_readiness_checks() {
+        use super::ServerState;
+        use crate::settings::{Advanced, VirtualHosts};
+
+        let opts = RequestHandlerOpts {
+            health: true,
+            root_dir: "docker/public".into(),
+            advanced_opts: Some(Advanced {
+                virtual_hosts: Some(vec![VirtualHosts {
+                    host: "example.com".to_owned(),
+                    root: "docker/missing".into(),
+                    #[cfg(feature = "http2")]
+                    tls_cert_key: None,
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let readiness = |opts: &RequestHandlerOpts| {
+            let resp = pre_process(opts, &make_request("GET", "/ready"))
+                .unwrap()
+                .unwrap();
+            let status = resp.status().as_u16();
+            async move {
+                let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                (status, body)
+            }
+        };
+        let failed = |body: &serde_json::Value| {
+            body["checks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|check| check["status"] == "fail")
+                .map(|check| check["name"].as_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        ServerState::Starting.set();
+        let (status, body) = readiness(&opts).await;
+        assert_eq!(status, 503);
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(failed(&body), ["startup", "vhost:example.com"]);
+
+        ServerState::Serving.set();
+        let (_, body) = readiness(&RequestHandlerOpts {
+            health: true,
+            maintenance_mode: true,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(failed(&body), ["maintenance_mode", "root"]);
+
+        let opts = RequestHandlerOpts {
+            advanced_opts: None,
+            ..opts
+        };
+        let (status, body) = readiness(&opts).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ready");
+        assert!(failed(&body).is_empty());
+
+        ServerState::ShuttingDown.set();
+        let (status, body) = readiness(&opts).await;
+        assert_eq!(status, 503);
+        assert_eq!(failed(&body), ["shutdown"]);
+    }
 }
@@ -3,11 +3,54 @@
 // See https://static-web-server.net/ for more information
 // Copyright (C) 2019-present Jose Quintana <joseluisq.net>
 
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use hyper::StatusCode;
+use percent_encoding::percent_decode_str;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use crate::{Context, Result};
 
+/// Compile a list of request path glob patterns, returning `None` if the list is empty.
+pub(crate) fn compile_path_globs(patterns: &[String], name: &str) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("can not compile glob pattern for {name} path: {pattern}"))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Decode and normalize a request path before matching it against path rules,
+/// so that it refers to the same file the request is served with.
+/// Empty and `.` segments are dropped and paths with `..` segments are rejected.
+pub(crate) fn normalize_request_path(path: &str) -> Result<String, StatusCode> {
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    let mut normalized = String::with_capacity(decoded.len());
+    let mut trailing_slash = true;
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => trailing_slash = true,
+            ".." => return Err(StatusCode::BAD_REQUEST),
+            segment => {
+                normalized.push('/');
+                normalized.push_str(segment);
+                trailing_slash = false;
+            }
+        }
+    }
+    if trailing_slash {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
 /// Validate and return a directory path.
 pub fn get_valid_dirpath<P: AsRef<Path>>(path: P) -> Result<PathBuf>
 where
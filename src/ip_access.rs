@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module providing the IP access control via allow and deny lists of CIDRs.
+//!
+//! The server-wide lists can be overridden per path and host via the `[[advanced.ip-access]]` rules.
+//!
+
+use hyper::{header::HOST, Body, Request, Response, StatusCode};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{
+    error_page, handler::RequestHandlerOpts, helpers, log_addr, settings::cli::General,
+    settings::IpAccess, virtual_hosts, Error,
+};
+
+/// Initializes the server-wide IP access control lists.
+pub(crate) fn init(general: &General, handler_opts: &mut RequestHandlerOpts) {
+    handler_opts.allow_ips = general.allow_ips.clone();
+    handler_opts.deny_ips = general.deny_ips.clone();
+
+    server_info!(
+        "ip access control: enabled={}",
+        !general.allow_ips.is_empty() || !general.deny_ips.is_empty()
+    );
+    if !general.allow_ips.is_empty() {
+        server_info!("ip access control allowed IPs: {:?}", general.allow_ips);
+    }
+    if !general.deny_ips.is_empty() {
+        server_info!("ip access control denied IPs: {:?}", general.deny_ips);
+    }
+}
+
+/// Returns the IP access control rules of the advanced options.
+fn ip_access_rules(opts: &RequestHandlerOpts) -> &[IpAccess] {
+    opts.advanced_opts
+        .as_ref()
+        .and_then(|advanced| advanced.ip_access.as_deref())
+        .unwrap_or_default()
+}
+
+/// Finds the first IP access control rule matching the normalized request path and the optional host.
+fn find_rule<'a>(
+    opts: &'a RequestHandlerOpts,
+    path: &str,
+    host: Option<&str>,
+) -> Option<&'a IpAccess> {
+    ip_access_rules(opts).iter().find(|rule| {
+        rule.source.is_match(path)
+            && rule.host.as_deref().map_or(true, |pattern| {
+                host.is_some_and(|host| virtual_hosts::host_matches(pattern, host))
+            })
+    })
+}
+
+/// Checks whether a client IP is allowed, the denied IPs taking precedence over the allowed ones.
+/// An unknown client IP (e.g. Unix domain socket connections) is only allowed if there is no allow list.
+fn is_allowed(allow: &[IpNet], deny: &[IpNet], client_ip: Option<IpAddr>) -> bool {
+    match client_ip {
+        Some(ip) => {
+            !deny.iter().any(|net| net.contains(&ip))
+                && (allow.is_empty() || allow.iter().any(|net| net.contains(&ip)))
+        }
+        None => allow.is_empty(),
+    }
+}
+
+/// Rejects the requests of clients not allowed by the matching rule or the server-wide lists.
+pub(crate) fn pre_process<T>(
+    opts: &RequestHandlerOpts,
+    req: &Request<T>,
+    remote_addr: Option<SocketAddr>,
+) -> Option<Result<Response<Body>, Error>> {
+    let uri = req.uri();
+    let is_enabled = !ip_access_rules(opts).is_empty()
+        || !opts.allow_ips.is_empty()
+        || !opts.deny_ips.is_empty();
+    // Match the normalized path so that equivalent paths can't bypass the rules
+    let path = match helpers::normalize_request_path(uri.path()) {
+        Ok(path) => path,
+        Err(_) if !is_enabled => return None,
+        Err(status) => {
+            return Some(error_page::error_response(
+                uri,
+                req.method(),
+                &status,
+                &opts.page404,
+                &opts.page50x,
+            ))
+        }
+    };
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(virtual_hosts::strip_port);
+
+    let (allow, deny) = match find_rule(opts, &path, host) {
+        Some(rule) => (rule.allow.as_slice(), rule.deny.as_slice()),
+        None => (opts.allow_ips.as_slice(), opts.deny_ips.as_slice()),
+    };
+    if allow.is_empty() && deny.is_empty() {
+        return None;
+    }
+
+    let client_ip = log_addr::client_ip(opts, req, remote_addr);
+    if is_allowed(allow, deny, client_ip) {
+        return None;
+    }
+
+    tracing::debug!("ip access: client {client_ip:?} denied access to {uri}");
+    Some(error_page::error_response(
+        uri,
+        req.method(),
+        &StatusCode::FORBIDDEN,
+        &opts.page404,
+        &opts.page50x,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_allowed, pre_process};
+    use crate::handler::RequestHandlerOpts;
+    use crate::settings::{Advanced, IpAccess};
+    use globset::Glob;
+    use hyper::{Body, Request};
+    use ipnet::IpNet;
+
+    fn nets(nets: &[&str]) -> Vec<IpNet> {
+        nets.iter().map(|net| net.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let allow = nets(&["10.0.0.0/8", "2001:db8::/32"]);
+        let deny = nets(&["10.0.0.13/32"]);
+        let ip = |ip: &str| ip.parse().ok();
+
+        assert!(is_allowed(&allow, &deny, ip("10.1.2.3")));
+        assert!(is_allowed(&allow, &deny, ip("2001:db8::1")));
+        assert!(!is_allowed(&allow, &deny, ip("10.0.0.13")));
+        assert!(!is_allowed(&allow, &deny, ip("192.0.2.1")));
+        assert!(!is_allowed(&allow, &deny, None));
+        assert!(is_allowed(&[], &deny, ip("192.0.2.1")));
+        assert!(!is_allowed(&[], &deny, ip("10.0.0.13")));
+        assert!(is_allowed(&[], &deny, None));
+    }
+
+    #[test]
+    fn test_ip_access_rules() {
+        let rule = |source: &str, host: Option<&str>, allow: &[&str]| IpAccess {
+            source: Glob::new(source).unwrap().compile_matcher(),
+            host: host.map(str::to_owned),
+            allow: nets(allow),
+            deny: vec![],
+        };
+        let opts = RequestHandlerOpts {
+            deny_ips: nets(&["198.51.100.0/24"]),
+            advanced_opts: Some(Advanced {
+                ip_access: Some(vec![
+                    rule("/admin/**", None, &["10.0.0.0/8"]),
+                    rule(
+                        "/reports/**",
+                        Some("intranet.example.com"),
+                        &["192.168.0.0/16"],
+                    ),
+                    rule("/public/**", None, &[]),
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let status = |uri: &str, host: &str, addr: &str| {
+            let req = Request::get(uri)
+                .header("host", host)
+                .body(Body::empty())
+                .unwrap();
+            pre_process(&opts, &req, addr.parse().ok()).map(|resp| resp.unwrap().status().as_u16())
+        };
+
+        assert_eq!(status("/", "example.com", "192.0.2.1:80"), None);
+        assert_eq!(status("/", "example.com", "198.51.100.7:80"), Some(403));
+        assert_eq!(status("/admin/", "example.com", "10.0.0.1:80"), None);
+        assert_eq!(status("/admin/", "example.com", "192.0.2.1:80"), Some(403));
+        for uri in ["/%61dmin/", "//admin/", "/./admin/", "/admin/./a.txt"] {
+            assert_eq!(
+                status(uri, "example.com", "192.0.2.1:80"),
+                Some(403),
+                "{uri}"
+            );
+        }
+        assert_eq!(
+            status("/public/../admin/", "example.com", "192.0.2.1:80"),
+            Some(400)
+        );
+        assert_eq!(
+            status("/reports/", "intranet.example.com:8080", "192.168.1.1:80"),
+            None
+        );
+        assert_eq!(
+            status("/reports/", "intranet.example.com", "192.0.2.1:80"),
+            Some(403)
+        );
+        assert_eq!(status("/reports/", "example.com", "192.0.2.1:80"), None);
+        assert_eq!(
+            status("/public/a.txt", "example.com", "198.51.100.7:80"),
+            None
+        );
+        assert_eq!(
+            status("/", "example.com", "[::ffff:198.51.100.7]:80"),
+            Some(403)
+        );
+    }
+}
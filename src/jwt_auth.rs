@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module validating JSON Web Tokens (JWT) sent as `Bearer` tokens or via a cookie.
+//!
+
+use headers::{authorization::Bearer, Authorization, Cookie, HeaderMap, HeaderMapExt};
+use hyper::StatusCode;
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, EllipticCurve, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{handler::RequestHandlerOpts, settings::cli::General, Context as _, Result};
+
+/// The signature algorithms accepted for the tokens.
+const ALGORITHMS: [Algorithm; 3] = [Algorithm::HS256, Algorithm::RS256, Algorithm::ES256];
+
+/// A key verifying the token signatures.
+struct Key {
+    /// The key ID matched against the `kid` header of the tokens if both are defined.
+    id: Option<String>,
+    /// The signature algorithm of the key.
+    algorithm: Algorithm,
+    /// The decoding key.
+    key: DecodingKey,
+}
+
+/// The JWT authentication options.
+pub struct JwtAuth {
+    keys: Vec<Key>,
+    cookie: Option<String>,
+    audience: Vec<String>,
+    issuer: Vec<String>,
+    leeway: u64,
+}
+
+/// The claims of a validated token.
+pub type Claims = Map<String, Value>;
+
+/// Initializes the JWT authentication if any key is defined.
+pub(crate) fn init(general: &General, handler_opts: &mut RequestHandlerOpts) -> Result {
+    let jwt_auth = JwtAuth::new(general)?;
+    server_info!(
+        "jwt authentication: enabled={}, keys={}, cookie={:?}",
+        jwt_auth.is_some(),
+        jwt_auth.as_ref().map_or(0, |auth| auth.keys.len()),
+        general.jwt_auth_cookie
+    );
+    handler_opts.jwt_auth = jwt_auth.map(Arc::new);
+    Ok(())
+}
+
+impl JwtAuth {
+    /// Creates the JWT authentication options if any key is defined.
+    pub(crate) fn new(general: &General) -> Result<Option<Self>> {
+        let mut keys = Vec::new();
+        if let Some(secret) = &general.jwt_auth_secret {
+            if secret.is_empty() {
+                bail!("jwt authentication secret can not be empty")
+            }
+            keys.push(Key {
+                id: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+            });
+        }
+        if let Some(path) = &general.jwt_auth_public_key {
+            keys.push(load_public_key(path).with_context(|| {
+                format!(
+                    "failed to load the jwt authentication public key \"{}\"",
+                    path.display()
+                )
+            })?);
+        }
+        if let Some(path) = &general.jwt_auth_jwks_file {
+            keys.extend(load_jwks(path).with_context(|| {
+                format!(
+                    "failed to load the jwt authentication JWKS file \"{}\"",
+                    path.display()
+                )
+            })?);
+        }
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            keys,
+            cookie: general.jwt_auth_cookie.clone(),
+            audience: general.jwt_auth_audience.clone(),
+            issuer: general.jwt_auth_issuer.clone(),
+            leeway: general.jwt_auth_leeway,
+        }))
+    }
+
+    /// Returns the token of the request sent via the `Authorization: Bearer` header or the cookie.
+    pub(crate) fn token(&self, headers: &HeaderMap) -> Option<String> {
+        if let Some(auth) = headers.typed_get::<Authorization<Bearer>>() {
+            return Some(auth.token().to_owned());
+        }
+        let cookie = self.cookie.as_deref()?;
+        headers
+            .typed_get::<Cookie>()
+            .and_then(|cookies| cookies.get(cookie).map(str::to_owned))
+    }
+
+    /// Validates a token signature and its `exp`, `nbf`, `aud` and `iss` claims, returning its claims.
+    pub(crate) fn validate(&self, token: &str) -> Result<Claims, StatusCode> {
+        let header = jsonwebtoken::decode_header(token).map_err(|err| {
+            tracing::debug!("jwt authentication: invalid token header: {err}");
+            StatusCode::UNAUTHORIZED
+        })?;
+        if !ALGORITHMS.contains(&header.alg) {
+            tracing::debug!("jwt authentication: unsupported algorithm {:?}", header.alg);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.leeway;
+        validation.validate_nbf = true;
+        if self.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audience);
+            validation.required_spec_claims.insert("aud".to_owned());
+        }
+        if !self.issuer.is_empty() {
+            validation.set_issuer(&self.issuer);
+            validation.required_spec_claims.insert("iss".to_owned());
+        }
+
+        let keys = self.keys.iter().filter(|key| {
+            key.algorithm == header.alg
+                && match (&key.id, &header.kid) {
+                    (Some(id), Some(kid)) => id == kid,
+                    _ => true,
+                }
+        });
+        for key in keys {
+            match jsonwebtoken::decode::<Claims>(token, &key.key, &validation) {
+                Ok(data) => return Ok(data.claims),
+                Err(err) => tracing::debug!("jwt authentication: invalid token: {err}"),
+            }
+        }
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Loads an RSA (RS256) or EC P-256 (ES256) public key from a PEM file.
+fn load_public_key(path: &Path) -> Result<Key> {
+    let pem = std::fs::read(path)?;
+    let (algorithm, key) = match DecodingKey::from_rsa_pem(&pem) {
+        Ok(key) => (Algorithm::RS256, key),
+        Err(_) => (
+            Algorithm::ES256,
+            DecodingKey::from_ec_pem(&pem)
+                .with_context(|| "the key is neither an RSA nor an EC public key")?,
+        ),
+    };
+    Ok(Key {
+        id: None,
+        algorithm,
+        key,
+    })
+}
+
+/// Loads the HS256, RS256 and ES256 keys of a JSON Web Key Set file, skipping the other keys.
+fn load_jwks(path: &Path) -> Result<Vec<Key>> {
+    let jwks: JwkSet = serde_json::from_slice(&std::fs::read(path)?)?;
+    let mut keys = Vec::new();
+    for jwk in &jwks.keys {
+        let algorithm = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+            (Some(KeyAlgorithm::HS256), _) => Some(Algorithm::HS256),
+            (Some(KeyAlgorithm::RS256), _) => Some(Algorithm::RS256),
+            (Some(KeyAlgorithm::ES256), _) => Some(Algorithm::ES256),
+            (Some(_), _) => None,
+            (None, AlgorithmParameters::OctetKey(_)) => Some(Algorithm::HS256),
+            (None, AlgorithmParameters::RSA(_)) => Some(Algorithm::RS256),
+            (None, AlgorithmParameters::EllipticCurve(params))
+                if params.curve == EllipticCurve::P256 =>
+            {
+                Some(Algorithm::ES256)
+            }
+            (None, _) => None,
+        };
+        let Some(algorithm) = algorithm else {
+            server_warn!(
+                "jwt authentication: skipping the unsupported key {:?} of the JWKS file",
+                jwk.common.key_id
+            );
+            continue;
+        };
+        keys.push(Key {
+            id: jwk.common.key_id.clone(),
+            algorithm,
+            key: DecodingKey::from_jwk(jwk)?,
+        });
+    }
+    Ok(keys)
+}
+
+/// Checks whether the claims match all the expected values.
+/// A claim matches if it equals any of the expected values or, being an array, contains any of them.
+pub(crate) fn claims_match(claims: &Claims, expected: &[(String, Vec<String>)]) -> bool {
+    expected
+        .iter()
+        .all(|(name, values)| match claims.get(name) {
+            Some(Value::String(claim)) => values.contains(claim),
+            Some(Value::Array(claim)) => claim
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|v| values.iter().any(|value| value == v)),
+            Some(Value::Bool(claim)) => values.contains(&claim.to_string()),
+            Some(Value::Number(claim)) => values.contains(&claim.to_string()),
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn jwt_auth() -> JwtAuth {
+        JwtAuth {
+            keys: vec![Key {
+                id: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(b"s3cr3t"),
+            }],
+            cookie: Some("session".to_owned()),
+            audience: vec!["sws".to_owned()],
+            issuer: vec!["portal".to_owned()],
+            leeway: 0,
+        }
+    }
+
+    fn token(claims: Value) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"s3cr3t"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn validates_the_token_claims() {
+        let auth = jwt_auth();
+        let exp = now() + 60;
+
+        let claims = auth
+            .validate(&token(
+                json!({"sub": "alice", "aud": "sws", "iss": "portal", "exp": exp}),
+            ))
+            .unwrap();
+        assert_eq!(claims["sub"], "alice");
+
+        for claims in [
+            json!({"aud": "sws", "iss": "portal", "exp": now() - 60}),
+            json!({"aud": "sws", "iss": "portal", "exp": exp, "nbf": exp}),
+            json!({"aud": "other", "iss": "portal", "exp": exp}),
+            json!({"iss": "portal", "exp": exp}),
+            json!({"aud": "sws", "iss": "other", "exp": exp}),
+            json!({"aud": "sws", "iss": "portal"}),
+        ] {
+            assert!(auth.validate(&token(claims.clone())).is_err(), "{claims}");
+        }
+
+        let other_key = encode(
+            &Header::default(),
+            &json!({"aud": "sws", "iss": "portal", "exp": exp}),
+            &EncodingKey::from_secret(b"other"),
+        )
+        .unwrap();
+        assert!(auth.validate(&other_key).is_err());
+        assert!(auth.validate("not-a-token").is_err());
+    }
+
+    #[test]
+    fn reads_the_token_from_the_header_or_cookie() {
+        let auth = jwt_auth();
+        let mut headers = HeaderMap::new();
+        assert_eq!(auth.token(&headers), None);
+        headers.insert("cookie", "theme=dark; session=abc".parse().unwrap());
+        assert_eq!(auth.token(&headers).as_deref(), Some("abc"));
+        headers.insert("authorization", "Bearer xyz".parse().unwrap());
+        assert_eq!(auth.token(&headers).as_deref(), Some("xyz"));
+    }
+
+    #[test]
+    fn matches_the_claims() {
+        let claims = json!({"role": "admin", "groups": ["staff", "ops"], "level": 3});
+        let claims = claims.as_object().unwrap();
+        let expected = |name: &str, values: &[&str]| {
+            (
+                name.to_owned(),
+                values.iter().map(|v| v.to_string()).collect(),
+            )
+        };
+
+        assert!(claims_match(
+            claims,
+            &[expected("role", &["admin", "editor"])]
+        ));
+        assert!(claims_match(
+            claims,
+            &[expected("groups", &["ops"]), expected("level", &["3"])]
+        ));
+        assert!(!claims_match(
+            claims,
+            &[expected("role", &["admin"]), expected("groups", &["dev"])]
+        ));
+        assert!(!claims_match(claims, &[expected("missing", &["x"])]));
+    }
+}
@@ -110,6 +110,8 @@ extern crate serde;
 // Public modules
 #[macro_use]
 pub mod logger;
+pub mod access_log;
+pub(crate) mod admin;
 #[cfg(feature = "basic-auth")]
 #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
 pub mod basic_auth;
@@ -157,6 +159,7 @@ pub mod custom_headers;
 #[cfg_attr(docsrs, doc(cfg(feature = "directory-listing")))]
 pub mod directory_listing;
 pub mod error_page;
+pub mod etag;
 #[cfg(feature = "fallback-page")]
 #[cfg_attr(docsrs, doc(cfg(feature = "fallback-page")))]
 pub mod fallback_page;
@@ -167,13 +170,29 @@ pub(crate) mod health;
 #[cfg(feature = "http2")]
 #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
 pub mod https_redirect;
-pub(crate) mod log_addr;
+pub(crate) mod ip_access;
+#[cfg(feature = "jwt-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+pub mod jwt_auth;
+pub mod log_addr;
+#[cfg(unix)]
+pub(crate) mod log_sinks;
 pub mod maintenance_mode;
 #[cfg(feature = "experimental")]
 pub(crate) mod mem_cache;
-#[cfg(all(unix, feature = "experimental"))]
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
 pub(crate) mod metrics;
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub mod mtls;
+#[cfg(feature = "otlp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "otlp")))]
+pub mod otlp;
+pub mod proxy_protocol;
 pub mod redirects;
+pub(crate) mod reload;
+pub mod request_id;
 pub(crate) mod response;
 pub mod rewrites;
 pub mod security_headers;
@@ -183,11 +202,15 @@ pub mod settings;
 #[cfg(any(unix, windows))]
 #[cfg_attr(docsrs, doc(cfg(any(unix, windows))))]
 pub mod signals;
+pub mod signed_urls;
 pub mod static_files;
 #[cfg(feature = "http2")]
 #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
 pub mod tls;
 pub mod transport;
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub mod unix_socket;
 pub(crate) mod virtual_hosts;
 #[cfg(windows)]
 #[cfg_attr(docsrs, doc(cfg(windows)))]
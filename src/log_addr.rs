@@ -6,11 +6,22 @@
 //! A module to log remote and real IP addresses.
 //!
 
-use hyper::Request;
+use clap::ValueEnum;
+use hyper::{header::FORWARDED, Request};
 use std::net::{IpAddr, SocketAddr};
 
 use crate::{handler::RequestHandlerOpts, health};
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+/// Defines the header the client IP is taken from behind a trusted proxy.
+pub enum ForwardedHeader {
+    /// The `X-Forwarded-For` header (default).
+    XForwardedFor,
+    /// The `Forwarded` header (RFC 7239).
+    Forwarded,
+}
+
 /// Initializes the log address module.
 pub(crate) fn init(enabled: bool, handler_opts: &mut RequestHandlerOpts) {
     handler_opts.log_remote_address = enabled;
@@ -26,6 +37,65 @@ pub(crate) fn init(enabled: bool, handler_opts: &mut RequestHandlerOpts) {
         handler_opts.log_forwarded_for
     );
     server_info!("trusted IPs for X-Forwarded-For: {trusted}");
+    server_info!(
+        "trusted proxies client IP header: {:?}",
+        handler_opts.trusted_proxies_header
+    );
+}
+
+/// Returns the client IP of the request.
+///
+/// Behind a trusted proxy, the entries of the trusted proxies header are walked from right to left,
+/// the client IP being the first one which is not a trusted proxy. The client IP is unknown
+/// if an entry without a valid IP (e.g. `for=unknown`) is found before it.
+pub(crate) fn client_ip<T>(
+    opts: &RequestHandlerOpts,
+    req: &Request<T>,
+    remote_addr: Option<SocketAddr>,
+) -> Option<IpAddr> {
+    // IPv4 clients of dual-stack listeners come as IPv4-mapped IPv6 addresses
+    let remote_ip = remote_addr?.ip().to_canonical();
+    if !opts.trusted_proxies.contains(&remote_ip) {
+        return Some(remote_ip);
+    }
+    let (name, parse): (_, fn(&str) -> Option<IpAddr>) = match opts.trusted_proxies_header {
+        ForwardedHeader::XForwardedFor => ("x-forwarded-for", |ip| ip.trim().parse().ok()),
+        ForwardedHeader::Forwarded => (FORWARDED.as_str(), forwarded_for),
+    };
+    let mut elements = Vec::new();
+    for value in req.headers().get_all(name) {
+        elements.extend(value.to_str().ok()?.split(','));
+    }
+
+    let mut client_ip = remote_ip;
+    for element in elements.into_iter().rev() {
+        if element.trim().is_empty() {
+            continue;
+        }
+        client_ip = parse(element)?.to_canonical();
+        if !opts.trusted_proxies.contains(&client_ip) {
+            break;
+        }
+    }
+    Some(client_ip)
+}
+
+/// Parses the IP of the `for` parameter of a `Forwarded` header element (RFC 7239),
+/// e.g. `for=192.0.2.60;proto=http` or `for="[2001:db8::1]:4711"`.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let value = element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("for")
+            .then(|| value.trim().trim_matches('"'))
+    })?;
+    match value.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?.parse().ok(),
+        None => value
+            .parse()
+            .ok()
+            .or_else(|| value.split_once(':')?.0.parse().ok()),
+    }
 }
 
 /// It logs remote and real IP addresses if available.
@@ -40,6 +110,14 @@ pub(crate) fn pre_process<T>(
         if let Some(addr) = remote_addr {
             remote_addrs.push_str(format!(" remote_addr={addr}").as_str());
         }
+        // Unix domain socket connections have no remote address but peer credentials
+        #[cfg(unix)]
+        if let Some(cred) = req.extensions().get::<crate::unix_socket::PeerCred>() {
+            remote_addrs.push_str(format!(" peer_uid={} peer_gid={}", cred.uid, cred.gid).as_str());
+            if let Some(pid) = cred.pid {
+                remote_addrs.push_str(format!(" peer_pid={pid}").as_str());
+            }
+        }
     }
     if opts.log_forwarded_for
         && (opts.trusted_proxies.is_empty()
@@ -55,9 +133,13 @@ pub(crate) fn pre_process<T>(
             remote_addrs.push_str(format!(" real_remote_ip={real_ip}").as_str());
         }
     }
+    #[cfg(feature = "http2")]
+    if let Some(cert) = req.extensions().get::<crate::mtls::ClientCert>() {
+        remote_addrs.push_str(format!(" client_cert_subject=\"{}\"", cert.subject).as_str());
+    }
 
     // Log incoming requests in debug mode only if the health option is enabled
-    if opts.health && health::is_health_endpoint(req) {
+    if health::is_health_endpoint(opts, req) {
         tracing::debug!(
             "incoming request: method={} uri={}{remote_addrs}",
             req.method(),
@@ -72,3 +154,135 @@ pub(crate) fn pre_process<T>(
         req.uri(),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{client_ip, ForwardedHeader};
+    use crate::handler::RequestHandlerOpts;
+    use hyper::{Body, Request};
+    use std::net::{IpAddr, SocketAddr};
+
+    fn opts(header: ForwardedHeader) -> RequestHandlerOpts {
+        RequestHandlerOpts {
+            trusted_proxies: vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()],
+            trusted_proxies_header: header,
+            ..Default::default()
+        }
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        ip.parse().ok()
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let opts = opts(ForwardedHeader::XForwardedFor);
+        let client_ip = |remote: &str, headers: &[(&'static str, &str)]| {
+            let mut req = Request::get("/").body(Body::empty()).unwrap();
+            for (name, value) in headers {
+                req.headers_mut().append(*name, value.parse().unwrap());
+            }
+            client_ip(&opts, &req, remote.parse::<SocketAddr>().ok())
+        };
+
+        assert_eq!(client_ip("", &[]), None);
+        assert_eq!(
+            client_ip("192.0.2.1:80", &[("x-forwarded-for", "203.0.113.7")]),
+            ip("192.0.2.1")
+        );
+        assert_eq!(client_ip("10.0.0.1:80", &[]), ip("10.0.0.1"));
+        assert_eq!(
+            client_ip(
+                "10.0.0.1:80",
+                &[("x-forwarded-for", "198.51.100.1, 203.0.113.7, 10.0.0.2")]
+            ),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            client_ip(
+                "10.0.0.1:80",
+                &[
+                    ("x-forwarded-for", "198.51.100.1"),
+                    ("x-forwarded-for", "203.0.113.7, 10.0.0.2")
+                ]
+            ),
+            ip("203.0.113.7")
+        );
+        // A client supplied `Forwarded` header is ignored
+        assert_eq!(
+            client_ip(
+                "10.0.0.1:80",
+                &[
+                    ("forwarded", "for=198.51.100.1"),
+                    ("x-forwarded-for", "203.0.113.7"),
+                ]
+            ),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            client_ip("10.0.0.1:80", &[("x-forwarded-for", "10.0.0.2")]),
+            ip("10.0.0.2")
+        );
+        // The walk stops at the first entry which is not an IP
+        assert_eq!(
+            client_ip(
+                "10.0.0.1:80",
+                &[("x-forwarded-for", "198.51.100.1, unknown, 10.0.0.2")]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_client_ip_forwarded() {
+        let opts = opts(ForwardedHeader::Forwarded);
+        let client_ip = |remote: &str, headers: &[(&'static str, &str)]| {
+            let mut req = Request::get("/").body(Body::empty()).unwrap();
+            for (name, value) in headers {
+                req.headers_mut().append(*name, value.parse().unwrap());
+            }
+            client_ip(&opts, &req, remote.parse::<SocketAddr>().ok())
+        };
+
+        assert_eq!(
+            client_ip(
+                "10.0.0.1:80",
+                &[
+                    (
+                        "forwarded",
+                        "for=198.51.100.1;proto=https, for=\"203.0.113.7:4711\""
+                    ),
+                    ("x-forwarded-for", "198.51.100.2"),
+                ]
+            ),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            client_ip(
+                "10.0.0.1:80",
+                &[
+                    ("forwarded", "For=\"[2001:db8::1]:4711\""),
+                    ("forwarded", "for=10.0.0.2")
+                ]
+            ),
+            ip("2001:db8::1")
+        );
+        assert_eq!(
+            client_ip("192.0.2.1:80", &[("forwarded", "for=203.0.113.7")]),
+            ip("192.0.2.1")
+        );
+        // Obfuscated or unknown entries aren't skipped
+        for forwarded in [
+            "for=unknown",
+            "for=198.51.100.1, for=unknown",
+            "for=198.51.100.1, for=_hidden;proto=https, for=10.0.0.2",
+            "for=198.51.100.1, proto=https",
+        ] {
+            assert_eq!(
+                client_ip("10.0.0.1:80", &[("forwarded", forwarded)]),
+                None,
+                "{forwarded}"
+            );
+        }
+    }
+}
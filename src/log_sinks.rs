@@ -0,0 +1,323 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Log sinks sending the diagnostic log events to a local syslog daemon or to journald.
+//!
+
+use chrono::{SecondsFormat, Utc};
+use std::fmt::{self, Write as _};
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{field::Field, field::Visit, Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::{Context as _, Result};
+
+/// Identifier of the log events sent to syslog and journald.
+const IDENTIFIER: &str = "static-web-server";
+
+/// Path of the journald native protocol socket.
+pub(crate) const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Collects the message and the fields of an event.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_owned();
+        } else {
+            self.fields
+                .push((field.name().to_owned(), value.to_owned()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields
+                .push((field.name().to_owned(), format!("{value:?}")));
+        }
+    }
+}
+
+impl Fields {
+    fn from_event(event: &Event<'_>) -> Self {
+        let mut fields = Self::default();
+        event.record(&mut fields);
+        fields
+    }
+}
+
+/// Returns the syslog severity of a level.
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+/// Opens an unbound non-blocking datagram socket to send events to the given socket path.
+fn connect(path: &Path) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.set_nonblocking(true)?;
+    socket.connect(path)?;
+    Ok(socket)
+}
+
+/// A datagram socket sending events without blocking the thread they are emitted from.
+///
+/// Events are dropped while the daemon is not keeping up with them and
+/// the socket connects again once the daemon is restarted.
+struct LogSocket {
+    path: PathBuf,
+    socket: Mutex<UnixDatagram>,
+}
+
+impl LogSocket {
+    fn new(path: &Path) -> Result<Self> {
+        let socket = connect(path)
+            .with_context(|| format!("unable to connect to the log socket {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+            socket: Mutex::new(socket),
+        })
+    }
+
+    fn send(&self, buf: &[u8]) {
+        let mut socket = self
+            .socket
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Events can't be reported anywhere else when they can't be sent
+        match socket.send(buf) {
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::ConnectionRefused | io::ErrorKind::NotConnected
+                ) =>
+            {
+                // The daemon was restarted and listens on a new socket
+                if let Ok(new_socket) = connect(&self.path) {
+                    *socket = new_socket;
+                    let _ = socket.send(buf);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A layer sending events to a local syslog socket in RFC 5424 format.
+pub(crate) struct SyslogLayer {
+    socket: LogSocket,
+    hostname: String,
+    pid: u32,
+}
+
+impl SyslogLayer {
+    /// Creates a layer sending events to the syslog socket at the given path.
+    pub(crate) fn new(path: &Path) -> Result<Self> {
+        let hostname = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty() && !name.contains(' '))
+            .unwrap_or_else(|| "-".to_owned());
+        Ok(Self {
+            socket: LogSocket::new(path)?,
+            hostname,
+            pid: std::process::id(),
+        })
+    }
+
+    /// Formats an event as an RFC 5424 message of the `daemon` facility.
+    fn format(&self, level: &Level, target: &str, fields: &Fields) -> String {
+        let mut msg = format!(
+            "<{}>1 {} {} {IDENTIFIER} {} - - {target}: {}",
+            3 * 8 + severity(level),
+            Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.hostname,
+            self.pid,
+            fields.message,
+        );
+        for (name, value) in &fields.fields {
+            let _ = write!(msg, " {name}={value}");
+        }
+        msg
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SyslogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        let msg = self.format(meta.level(), meta.target(), &Fields::from_event(event));
+        self.socket.send(msg.as_bytes());
+    }
+}
+
+/// A layer sending events to journald with structured fields using its native protocol.
+pub(crate) struct JournaldLayer {
+    socket: LogSocket,
+}
+
+impl JournaldLayer {
+    /// Creates a layer sending events to the journald socket at the given path.
+    pub(crate) fn new(path: &Path) -> Result<Self> {
+        Ok(Self {
+            socket: LogSocket::new(path)?,
+        })
+    }
+}
+
+/// Appends a field using the journald native protocol serialization.
+fn put_field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+/// Converts a field name into a valid journald one like `SELF_WORKER_THREADS`.
+fn field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .collect();
+    // Names starting with an underscore are reserved to journald
+    format!("F_{}", name.trim_start_matches('_'))
+}
+
+impl<S: Subscriber> Layer<S> for JournaldLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        let fields = Fields::from_event(event);
+
+        let mut buf = Vec::with_capacity(256);
+        put_field(&mut buf, "MESSAGE", &fields.message);
+        put_field(&mut buf, "PRIORITY", &severity(meta.level()).to_string());
+        put_field(&mut buf, "SYSLOG_IDENTIFIER", IDENTIFIER);
+        put_field(&mut buf, "TARGET", meta.target());
+        if let Some(file) = meta.file() {
+            put_field(&mut buf, "CODE_FILE", file);
+        }
+        if let Some(line) = meta.line() {
+            put_field(&mut buf, "CODE_LINE", &line.to_string());
+        }
+        for (name, value) in &fields.fields {
+            put_field(&mut buf, &field_name(name), value);
+        }
+        self.socket.send(&buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tracing_subscriber::prelude::*;
+
+    fn bind(name: &str) -> (PathBuf, UnixDatagram) {
+        let path = std::env::temp_dir().join(format!("sws-{name}-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        (path, socket)
+    }
+
+    fn receive(socket: &UnixDatagram) -> String {
+        let mut buf = vec![0; 4096];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn sends_rfc5424_messages() {
+        let (path, socket) = bind("syslog");
+        let layer = SyslogLayer::new(&path).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "sws::test", port = 8787, "server bound");
+        });
+
+        let msg = receive(&socket);
+        let pid = std::process::id();
+        assert!(msg.starts_with("<28>1 "), "{msg}");
+        assert!(
+            msg.ends_with(&format!(
+                " static-web-server {pid} - - sws::test: server bound port=8787"
+            )),
+            "{msg}"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sends_journald_fields() {
+        let (path, socket) = bind("journald");
+        let layer = JournaldLayer::new(&path).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(target: "sws::test", worker_threads = 4, "multi\nline");
+        });
+
+        let msg = receive(&socket);
+        assert!(
+            msg.starts_with("MESSAGE\n\x0a\0\0\0\0\0\0\0multi\nline\n"),
+            "{msg:?}"
+        );
+        assert!(msg.contains("\nPRIORITY=3\n"), "{msg:?}");
+        assert!(
+            msg.contains("\nSYSLOG_IDENTIFIER=static-web-server\n"),
+            "{msg:?}"
+        );
+        assert!(msg.contains("\nTARGET=sws::test\n"), "{msg:?}");
+        assert!(msg.ends_with("\nF_WORKER_THREADS=4\n"), "{msg:?}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn drops_events_instead_of_blocking() {
+        let (path, socket) = bind("log-socket-full");
+        let log_socket = LogSocket::new(&path).unwrap();
+        // Nothing is received, so the socket buffer gets full
+        for _ in 0..10_000 {
+            log_socket.send(&[b'x'; 1024]);
+        }
+        assert_eq!(receive(&socket), "x".repeat(1024));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reconnects_to_a_restarted_daemon() {
+        let (path, socket) = bind("log-socket-restart");
+        let log_socket = LogSocket::new(&path).unwrap();
+        log_socket.send(b"one");
+        assert_eq!(receive(&socket), "one");
+
+        drop(socket);
+        let (path, socket) = bind("log-socket-restart");
+        log_socket.send(b"two");
+        assert_eq!(receive(&socket), "two");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -6,51 +6,135 @@
 //! Provides logging initialization for the web server.
 //!
 
+use clap::ValueEnum;
+use std::path::Path;
 use tracing::Level;
-use tracing_subscriber::{filter::Targets, fmt::format::FmtSpan, prelude::*};
+use tracing_subscriber::{filter::LevelFilter, fmt::format::FmtSpan, prelude::*, EnvFilter};
 
+#[cfg(unix)]
+use crate::log_sinks;
 use crate::{Context, Result};
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Output format of the diagnostic log events.
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per event.
+    Json,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Destination of the diagnostic log events.
+pub enum LogOutput {
+    /// The standard error output.
+    Stderr,
+    /// A local syslog socket using the RFC 5424 format (Unix-like systems only).
+    Syslog,
+    /// The systemd journal (Linux only).
+    Journald,
+}
+
 /// Logging system initialization
-pub fn init(log_level: &str) -> Result {
+pub fn init(
+    log_level: &str,
+    log_format: LogFormat,
+    log_filter: &str,
+    log_output: LogOutput,
+    log_syslog_socket: &Path,
+) -> Result {
     let log_level = log_level.to_lowercase();
 
-    configure(&log_level).with_context(|| "failed to initialize logging")?;
+    configure(
+        &log_level,
+        log_format,
+        log_filter,
+        log_output,
+        log_syslog_socket,
+    )
+    .with_context(|| "failed to initialize logging")?;
 
     Ok(())
 }
 
 /// Initialize logging builder with its levels.
-fn configure(level: &str) -> Result {
-    let level = level
-        .parse::<Level>()
-        .with_context(|| "failed to parse log level")?;
+fn configure(
+    level: &str,
+    format: LogFormat,
+    filter: &str,
+    output: LogOutput,
+    #[cfg_attr(not(unix), allow(unused_variables))] syslog_socket: &Path,
+) -> Result {
+    let filter = build_filter(level, filter)?;
 
     #[cfg(not(windows))]
     let enable_ansi = true;
     #[cfg(windows)]
     let enable_ansi = false;
 
-    let filtered_layer = tracing_subscriber::fmt::layer()
+    let layer = tracing_subscriber::fmt::layer()
         .with_writer(std::io::stderr)
-        .with_span_events(FmtSpan::CLOSE)
-        .with_ansi(enable_ansi)
-        .with_filter(
-            Targets::default()
-                .with_default(level)
-                .with_target("static_web_server::info", Level::INFO)
-                .with_target("static_web_server::warn", Level::WARN),
-        );
-
-    match tracing_subscriber::registry()
-        .with(filtered_layer)
-        .try_init()
-    {
+        .with_span_events(FmtSpan::CLOSE);
+    let filtered_layer = match (output, format) {
+        (LogOutput::Stderr, LogFormat::Text) => {
+            layer.with_ansi(enable_ansi).with_filter(filter).boxed()
+        }
+        (LogOutput::Stderr, LogFormat::Json) => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_filter(filter)
+            .boxed(),
+        #[cfg(unix)]
+        (LogOutput::Syslog, _) => log_sinks::SyslogLayer::new(syslog_socket)?
+            .with_filter(filter)
+            .boxed(),
+        #[cfg(unix)]
+        (LogOutput::Journald, _) => {
+            log_sinks::JournaldLayer::new(Path::new(log_sinks::JOURNALD_SOCKET))?
+                .with_filter(filter)
+                .boxed()
+        }
+        #[cfg(not(unix))]
+        (output, _) => bail!("log output {output:?} is only supported on Unix-like systems"),
+    };
+
+    let registry = tracing_subscriber::registry().with(filtered_layer);
+
+    // Spans exported to an OpenTelemetry collector regardless of the log level
+    #[cfg(feature = "otlp")]
+    let registry = registry.with(crate::otlp::layer());
+
+    match registry.try_init() {
         Err(err) => Err(anyhow!(err)),
         _ => Ok(()),
     }
 }
 
+/// Builds the events filter from the default level and the per-target directives
+/// like `static_web_server=debug,hyper=warn`.
+fn build_filter(level: &str, directives: &str) -> Result<EnvFilter> {
+    let level = level
+        .parse::<Level>()
+        .with_context(|| "failed to parse log level")?;
+
+    // The server information and warnings are always logged
+    let mut filter = EnvFilter::default()
+        .add_directive(LevelFilter::from_level(level).into())
+        .add_directive("static_web_server::info=info".parse()?)
+        .add_directive("static_web_server::warn=warn".parse()?);
+    for directive in directives.split(',').filter(|d| !d.trim().is_empty()) {
+        let directive = directive
+            .trim()
+            .parse()
+            .with_context(|| format!("failed to parse log filter directive \"{directive}\""))?;
+        filter = filter.add_directive(directive);
+    }
+    Ok(filter)
+}
+
 /// Custom info level macro.
 #[macro_export]
 macro_rules! server_info {
@@ -72,3 +156,22 @@ macro_rules! server_warn {
         )
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_log_filter() {
+        let filter = build_filter("error", "static_web_server=debug, hyper=warn").unwrap();
+        let filter = filter.to_string();
+        assert!(filter.contains("static_web_server=debug"), "{filter}");
+        assert!(filter.contains("hyper=warn"), "{filter}");
+        assert!(filter.contains("static_web_server::info=info"), "{filter}");
+        assert!(filter.split(',').any(|d| d == "error"), "{filter}");
+
+        assert!(build_filter("error", "").is_ok());
+        assert!(build_filter("loud", "").is_err());
+        assert!(build_filter("error", "hyper=loud").is_err());
+    }
+}
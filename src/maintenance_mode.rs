@@ -5,26 +5,59 @@
 
 //! Provides maintenance mode functionality.
 //!
+//! The maintenance mode can be toggled at runtime via the admin listener, the `SIGUSR2` signal
+//! or the presence of a trigger file, optionally scoped to some hosts or paths.
+//!
 
+use globset::GlobSet;
 use headers::{AcceptRanges, ContentLength, ContentType, HeaderMapExt};
-use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::{header::HOST, Body, Method, Request, Response, StatusCode};
+use lazy_static::lazy_static;
 use mime_guess::mime;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
-use crate::{handler::RequestHandlerOpts, helpers, http_ext::MethodExt, Error, Result};
+use crate::{
+    handler::RequestHandlerOpts, helpers, http_ext::MethodExt, log_addr, settings::cli::General,
+    virtual_hosts, Error, Result,
+};
 
 const DEFAULT_BODY_CONTENT: &str = "The server is in maintenance mode.";
 
+/// The runtime state values.
+const RUNTIME_UNSET: u8 = 0;
+const RUNTIME_ENABLED: u8 = 1;
+const RUNTIME_DISABLED: u8 = 2;
+
+/// The maintenance mode state set at runtime which takes precedence over the configured one.
+static RUNTIME_STATE: AtomicU8 = AtomicU8::new(RUNTIME_UNSET);
+
+/// The configured maintenance mode state, used to toggle it at runtime.
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+
+/// Minimum interval between two checks of the same trigger file.
+const TRIGGER_FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    /// Whether the trigger files exist along with the time they were checked, by path.
+    static ref TRIGGER_FILES: RwLock<HashMap<PathBuf, (Instant, bool)>> = RwLock::default();
+}
+
 /// Initializes maintenance mode handling
-pub(crate) fn init(
-    maintenance_mode: bool,
-    maintenance_mode_status: StatusCode,
-    maintenance_mode_file: PathBuf,
-    handler_opts: &mut RequestHandlerOpts,
-) {
-    handler_opts.maintenance_mode = maintenance_mode;
-    handler_opts.maintenance_mode_status = maintenance_mode_status;
-    handler_opts.maintenance_mode_file = maintenance_mode_file;
+pub(crate) fn init(general: &General, handler_opts: &mut RequestHandlerOpts) -> Result {
+    handler_opts.maintenance_mode = general.maintenance_mode;
+    handler_opts.maintenance_mode_status = general.maintenance_mode_status;
+    handler_opts.maintenance_mode_file = general.maintenance_mode_file.clone();
+    handler_opts.maintenance_mode_trigger_file = general.maintenance_mode_trigger_file.clone();
+    handler_opts.maintenance_mode_hosts = general.maintenance_mode_hosts.clone();
+    handler_opts.maintenance_mode_paths = compile_paths(&general.maintenance_mode_paths)?;
+    handler_opts.maintenance_mode_allow_ips = general.maintenance_mode_allow_ips.clone();
+    CONFIGURED.store(general.maintenance_mode, Ordering::Relaxed);
+
     server_info!(
         "maintenance mode: enabled={}",
         handler_opts.maintenance_mode
@@ -37,22 +70,179 @@ pub(crate) fn init(
         "maintenance mode file: \"{}\"",
         handler_opts.maintenance_mode_file.display()
     );
+    if let Some(trigger_file) = &handler_opts.maintenance_mode_trigger_file {
+        server_info!(
+            "maintenance mode trigger file: \"{}\"",
+            trigger_file.display()
+        );
+    }
+    if !general.maintenance_mode_hosts.is_empty() || !general.maintenance_mode_paths.is_empty() {
+        server_info!(
+            "maintenance mode scope: hosts={:?}, paths={:?}",
+            general.maintenance_mode_hosts,
+            general.maintenance_mode_paths
+        );
+    }
+    if !handler_opts.maintenance_mode_allow_ips.is_empty() {
+        server_info!(
+            "maintenance mode allowed IPs: {:?}",
+            handler_opts.maintenance_mode_allow_ips
+        );
+    }
+    Ok(())
+}
+
+/// Compiles the path glob patterns the maintenance mode is scoped to.
+pub(crate) fn compile_paths(patterns: &[String]) -> Result<Option<GlobSet>> {
+    helpers::compile_path_globs(patterns, "maintenance mode")
+}
+
+/// Sets the maintenance mode state at runtime, overriding the configured one until the server restarts.
+pub fn set_runtime_state(enabled: bool) {
+    let state = if enabled {
+        RUNTIME_ENABLED
+    } else {
+        RUNTIME_DISABLED
+    };
+    RUNTIME_STATE.store(state, Ordering::Relaxed);
+    tracing::info!("maintenance mode {} at runtime", enabled_str(enabled));
+}
+
+/// Returns the maintenance mode state set at runtime if any.
+pub fn runtime_state() -> Option<bool> {
+    match RUNTIME_STATE.load(Ordering::Relaxed) {
+        RUNTIME_ENABLED => Some(true),
+        RUNTIME_DISABLED => Some(false),
+        _ => None,
+    }
+}
+
+/// Toggles the maintenance mode at runtime, returning the new state.
+#[cfg(unix)]
+pub(crate) fn toggle() -> bool {
+    let enabled = !runtime_state().unwrap_or_else(|| CONFIGURED.load(Ordering::Relaxed));
+    set_runtime_state(enabled);
+    enabled
+}
+
+fn enabled_str(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
+
+/// Checks whether the maintenance mode is switched on, either via the runtime state or the configuration.
+pub(crate) fn is_switched_on(opts: &RequestHandlerOpts) -> bool {
+    runtime_state().unwrap_or(opts.maintenance_mode)
+}
+
+/// Checks whether the maintenance mode applies to the whole root directory,
+/// which is what the readiness endpoint reports.
+pub(crate) fn is_enabled(opts: &RequestHandlerOpts) -> bool {
+    opts.maintenance_mode_hosts.is_empty()
+        && opts.maintenance_mode_paths.is_none()
+        && (is_switched_on(opts) || trigger_file_exists(opts, &opts.root_dir))
+}
+
+/// Checks the trigger file, a relative path being resolved against the given root directory.
+/// The file system is checked at most once per `TRIGGER_FILE_CHECK_INTERVAL` for every path.
+fn trigger_file_exists(opts: &RequestHandlerOpts, root: &Path) -> bool {
+    let path = match &opts.maintenance_mode_trigger_file {
+        Some(file) => root.join(file),
+        None => return false,
+    };
+
+    let checked = TRIGGER_FILES
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&path)
+        .copied();
+    if let Some((checked_at, exists)) = checked {
+        if checked_at.elapsed() < TRIGGER_FILE_CHECK_INTERVAL {
+            return exists;
+        }
+    }
+
+    let exists = path.exists();
+    TRIGGER_FILES
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(path, (Instant::now(), exists));
+    exists
+}
+
+/// Checks whether the request is in the scope of the maintenance mode.
+fn is_in_scope<T>(opts: &RequestHandlerOpts, req: &Request<T>) -> bool {
+    let host_matches = opts.maintenance_mode_hosts.is_empty()
+        || req
+            .headers()
+            .get(HOST)
+            .and_then(|v| v.to_str().ok())
+            .map(virtual_hosts::strip_port)
+            .is_some_and(|host| {
+                opts.maintenance_mode_hosts
+                    .iter()
+                    .any(|pattern| virtual_hosts::host_matches(pattern, host))
+            });
+    // Paths which can't be normalized are kept in the scope
+    let path_matches = opts.maintenance_mode_paths.as_ref().map_or(true, |paths| {
+        helpers::normalize_request_path(req.uri().path()).map_or(true, |path| paths.is_match(path))
+    });
+    host_matches && path_matches
+}
+
+/// Checks whether the client is allowed to bypass the maintenance mode.
+///
+/// The client address is the remote one, or the one forwarded by the trusted proxies
+/// when the request is sent by one of them (see [`log_addr::client_ip`]).
+fn is_client_allowed<T>(
+    opts: &RequestHandlerOpts,
+    req: &Request<T>,
+    remote_addr: Option<SocketAddr>,
+) -> bool {
+    let Some(client_ip) = log_addr::client_ip(opts, req, remote_addr) else {
+        return false;
+    };
+    opts.maintenance_mode_allow_ips
+        .iter()
+        .any(|net| net.contains(&client_ip))
 }
 
 /// Produces maintenance mode response if necessary
 pub(crate) fn pre_process<T>(
     opts: &RequestHandlerOpts,
     req: &Request<T>,
+    remote_addr: Option<SocketAddr>,
 ) -> Option<Result<Response<Body>, Error>> {
-    if opts.maintenance_mode {
-        Some(get_response(
-            req.method(),
-            &opts.maintenance_mode_status,
-            &opts.maintenance_mode_file,
-        ))
-    } else {
-        None
+    let switched_on = is_switched_on(opts);
+    if !switched_on && opts.maintenance_mode_trigger_file.is_none() {
+        return None;
+    }
+    if !is_in_scope(opts, req) {
+        return None;
+    }
+    if !switched_on {
+        let root = opts
+            .advanced_opts
+            .as_ref()
+            .and_then(|advanced| virtual_hosts::get_vhost(req, advanced.virtual_hosts.as_deref()))
+            .map_or(&opts.root_dir, |vhost| &vhost.root);
+        if !trigger_file_exists(opts, root) {
+            return None;
+        }
     }
+    if is_client_allowed(opts, req, remote_addr) {
+        tracing::debug!("client allowed to bypass the maintenance mode");
+        return None;
+    }
+
+    Some(get_response(
+        req.method(),
+        &opts.maintenance_mode_status,
+        &opts.maintenance_mode_file,
+    ))
 }
 
 /// Get the a server maintenance mode response.
@@ -94,9 +284,10 @@ pub fn get_response(
 
 #[cfg(test)]
 mod tests {
-    use super::pre_process;
+    use super::{compile_paths, pre_process};
     use crate::{handler::RequestHandlerOpts, Error};
     use hyper::{Body, Request, Response, StatusCode};
+    use std::path::PathBuf;
 
     fn make_request() -> Request<Body> {
         Request::builder()
@@ -121,7 +312,8 @@ mod tests {
                 maintenance_mode: false,
                 ..Default::default()
             },
-            &make_request()
+            &make_request(),
+            None
         )
         .is_none());
     }
@@ -134,7 +326,8 @@ mod tests {
                     maintenance_mode: true,
                     ..Default::default()
                 },
-                &make_request()
+                &make_request(),
+                None
             )),
             Some(StatusCode::SERVICE_UNAVAILABLE)
         );
@@ -149,9 +342,111 @@ mod tests {
                     maintenance_mode_status: StatusCode::IM_A_TEAPOT,
                     ..Default::default()
                 },
-                &make_request()
+                &make_request(),
+                None
             )),
             Some(StatusCode::IM_A_TEAPOT)
         );
     }
+
+    #[test]
+    fn test_maintenance_scope() {
+        let opts = RequestHandlerOpts {
+            maintenance_mode: true,
+            maintenance_mode_hosts: vec!["*.example.com".to_owned()],
+            maintenance_mode_paths: compile_paths(&["/shop/**".to_owned()]).unwrap(),
+            ..Default::default()
+        };
+        let request = |host: &str, uri: &str| {
+            Request::get(uri)
+                .header("host", host)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let req = request("www.example.com:8787", "/shop/cart.html");
+        assert!(pre_process(&opts, &req, None).is_some());
+        let req = request("www.example.com", "/index.html");
+        assert!(pre_process(&opts, &req, None).is_none());
+        let req = request("example.org", "/shop/cart.html");
+        assert!(pre_process(&opts, &req, None).is_none());
+        for uri in [
+            "/%73hop/cart.html",
+            "//shop/cart.html",
+            "/./shop/cart.html",
+            "/index/../shop/cart.html",
+        ] {
+            let req = request("www.example.com", uri);
+            assert!(pre_process(&opts, &req, None).is_some(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_maintenance_allowed_ips() {
+        let opts = RequestHandlerOpts {
+            maintenance_mode: true,
+            maintenance_mode_allow_ips: vec!["10.0.0.0/8".parse().unwrap()],
+            trusted_proxies: vec!["192.168.1.1".parse().unwrap()],
+            ..Default::default()
+        };
+        let request = |forwarded_for: &str| {
+            Request::get("/")
+                .header("x-forwarded-for", forwarded_for)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let staff = "10.1.2.3:4000".parse().ok();
+        let client = "172.16.0.1:4000".parse().ok();
+        let proxy = "192.168.1.1:4000".parse().ok();
+        assert!(pre_process(&opts, &make_request(), staff).is_none());
+        assert!(pre_process(&opts, &make_request(), client).is_some());
+        assert!(pre_process(&opts, &make_request(), None).is_some());
+        // The rightmost forwarded address not belonging to a trusted proxy is used
+        assert!(pre_process(&opts, &request("10.1.2.3, 192.168.1.1"), proxy).is_none());
+        assert!(pre_process(&opts, &request("10.1.2.3, 172.16.0.1"), proxy).is_some());
+        assert!(pre_process(&opts, &request("10.1.2.3"), client).is_some());
+    }
+
+    #[test]
+    fn test_maintenance_trigger_file() {
+        let opts = RequestHandlerOpts {
+            root_dir: PathBuf::from("docker/public"),
+            maintenance_mode_trigger_file: Some(PathBuf::from("index.html")),
+            ..Default::default()
+        };
+        assert!(pre_process(&opts, &make_request(), None).is_some());
+        assert!(super::is_enabled(&opts));
+
+        let opts = RequestHandlerOpts {
+            root_dir: PathBuf::from("docker/public"),
+            maintenance_mode_trigger_file: Some(PathBuf::from(".maintenance")),
+            ..Default::default()
+        };
+        assert!(pre_process(&opts, &make_request(), None).is_none());
+        assert!(!super::is_enabled(&opts));
+    }
+
+    #[test]
+    fn test_maintenance_trigger_file_check_interval() {
+        let root = std::env::temp_dir().join(format!("sws-maintenance-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let trigger_file = root.join(".maintenance");
+        let opts = RequestHandlerOpts {
+            root_dir: root.clone(),
+            maintenance_mode_trigger_file: Some(PathBuf::from(".maintenance")),
+            ..Default::default()
+        };
+
+        std::fs::write(&trigger_file, "").unwrap();
+        assert!(super::is_enabled(&opts));
+
+        // The removal is only noticed once the check interval elapsed
+        std::fs::remove_file(&trigger_file).unwrap();
+        assert!(super::is_enabled(&opts));
+        std::thread::sleep(super::TRIGGER_FILE_CHECK_INTERVAL);
+        assert!(!super::is_enabled(&opts));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
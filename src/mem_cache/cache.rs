@@ -13,7 +13,8 @@
 use bytes::Bytes;
 use compact_str::CompactString;
 use headers::{
-    AcceptRanges, ContentLength, ContentRange, ContentType, HeaderMap, HeaderMapExt, LastModified,
+    AcceptRanges, ContentLength, ContentRange, ContentType, ETag, HeaderMap, HeaderMapExt,
+    LastModified,
 };
 use hyper::{Body, Response, StatusCode};
 use mini_moka::sync::Cache;
@@ -26,7 +27,8 @@ use tokio::sync::Semaphore;
 use crate::conditional_headers::{ConditionalBody, ConditionalHeaders};
 use crate::fs::stream::FileStream;
 use crate::handler::RequestHandlerOpts;
-use crate::response::{bytes_range, BadRangeError};
+use crate::metrics;
+use crate::response::{bytes_range, multipart_response, range_not_satisfiable, BadRangeError};
 use crate::Result;
 
 /// Global cache that stores all files in memory.
@@ -37,6 +39,7 @@ pub(crate) static CACHE_STORE: OnceLock<Cache<CompactString, Arc<MemFile>>> = On
 static CACHE_PERMIT: Semaphore = Semaphore::const_new(1);
 
 /// It defines the in-memory files cache options.
+#[derive(Clone)]
 pub struct MemCacheOpts {
     /// The maximum size per file in bytes.
     pub max_file_size: u64,
@@ -116,6 +119,7 @@ pub(crate) async fn get_or_acquire(
                 "file `{}` found in the in-memory cache store, returning it directly",
                 file_path_str
             );
+            metrics::memory_cache_hit();
             Some(mem_file.response_body(headers_opt))
         }
         _ => {
@@ -123,6 +127,7 @@ pub(crate) async fn get_or_acquire(
                 "file `{}` was not found in the in-memory cache store, continuing",
                 file_path_str
             );
+            metrics::memory_cache_miss();
             // If a file is not found in the store then continue
             // with the normal flow and wait on first file read
             if let Err(err) = CACHE_PERMIT.acquire().await {
@@ -139,6 +144,7 @@ pub(crate) struct MemFileTempOpts {
     pub(crate) file_path: String,
     pub(crate) content_type: ContentType,
     pub(crate) last_modified: Option<LastModified>,
+    pub(crate) etag: Option<ETag>,
 }
 
 impl MemFileTempOpts {
@@ -146,11 +152,13 @@ impl MemFileTempOpts {
         file_path: String,
         content_type: ContentType,
         last_modified: Option<LastModified>,
+        etag: Option<ETag>,
     ) -> Self {
         Self {
             file_path,
             content_type,
             last_modified,
+            etag,
         }
     }
 }
@@ -166,6 +174,8 @@ pub(crate) struct MemFile {
     content_type: ContentType,
     /// `Last Modified` header for the current file.
     last_modified: Option<LastModified>,
+    /// `ETag` header for the current file.
+    etag: Option<ETag>,
 }
 
 impl MemFile {
@@ -175,12 +185,14 @@ impl MemFile {
         buf_size: usize,
         content_type: ContentType,
         last_modified: Option<LastModified>,
+        etag: Option<ETag>,
     ) -> Self {
         Self {
             data,
             buf_size,
             content_type,
             last_modified,
+            etag,
         }
     }
 
@@ -188,7 +200,7 @@ impl MemFile {
         let conditionals = ConditionalHeaders::new(headers);
         let modified = self.last_modified;
 
-        match conditionals.check(modified) {
+        match conditionals.check(modified, self.etag.as_ref()) {
             ConditionalBody::NoBody(resp) => Ok(resp),
             ConditionalBody::WithBody(range) => {
                 let mem_buf = self.data.clone();
@@ -196,59 +208,62 @@ impl MemFile {
                 let mut reader = std::io::Cursor::new(mem_buf);
                 let buf_size = self.buf_size;
 
-                bytes_range(range, len)
-                    .map(|(start, end)| {
-                        match reader.seek(SeekFrom::Start(start)) {
-                            Ok(_) => (),
+                let (start, end) = match bytes_range(range, len) {
+                    Ok(ranges) if ranges.len() > 1 => {
+                        let mut resp =
+                            multipart_response(reader, buf_size, &ranges, len, &self.content_type)?;
+                        if let Some(last_modified) = modified {
+                            resp.headers_mut().typed_insert(last_modified);
+                        }
+                        if let Some(etag) = &self.etag {
+                            resp.headers_mut().typed_insert(etag.clone());
+                        }
+                        return Ok(resp);
+                    }
+                    Ok(ranges) => ranges[0],
+                    Err(BadRangeError) => return Ok(range_not_satisfiable(len)),
+                };
+
+                match reader.seek(SeekFrom::Start(start)) {
+                    Ok(_) => (),
+                    Err(err) => {
+                        tracing::error!("seek file from start error: {:?}", err);
+                        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    }
+                };
+
+                let sub_len = end - start;
+                let reader = reader.take(sub_len);
+
+                let body = Body::wrap_stream(FileStream { reader, buf_size });
+                let mut resp = Response::new(body);
+
+                if sub_len != len {
+                    *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    resp.headers_mut()
+                        .typed_insert(match ContentRange::bytes(start..end, len) {
+                            Ok(range) => range,
                             Err(err) => {
-                                tracing::error!("seek file from start error: {:?}", err);
-                                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                                tracing::error!("invalid content range error: {:?}", err);
+                                return Ok(range_not_satisfiable(len));
                             }
-                        };
-
-                        let sub_len = end - start;
-                        let reader = reader.take(sub_len);
-
-                        let body = Body::wrap_stream(FileStream { reader, buf_size });
-                        let mut resp = Response::new(body);
-
-                        if sub_len != len {
-                            *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
-                            resp.headers_mut().typed_insert(
-                                match ContentRange::bytes(start..end, len) {
-                                    Ok(range) => range,
-                                    Err(err) => {
-                                        tracing::error!("invalid content range error: {:?}", err);
-                                        let mut resp = Response::new(Body::empty());
-                                        *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-                                        resp.headers_mut()
-                                            .typed_insert(ContentRange::unsatisfied_bytes(len));
-                                        return Ok(resp);
-                                    }
-                                },
-                            );
-
-                            len = sub_len;
-                        }
+                        });
 
-                        resp.headers_mut().typed_insert(ContentLength(len));
-                        resp.headers_mut().typed_insert(self.content_type.clone());
-                        resp.headers_mut().typed_insert(AcceptRanges::bytes());
+                    len = sub_len;
+                }
 
-                        if let Some(last_modified) = modified {
-                            resp.headers_mut().typed_insert(last_modified);
-                        }
+                resp.headers_mut().typed_insert(ContentLength(len));
+                resp.headers_mut().typed_insert(self.content_type.clone());
+                resp.headers_mut().typed_insert(AcceptRanges::bytes());
+
+                if let Some(last_modified) = modified {
+                    resp.headers_mut().typed_insert(last_modified);
+                }
+                if let Some(etag) = &self.etag {
+                    resp.headers_mut().typed_insert(etag.clone());
+                }
 
-                        Ok(resp)
-                    })
-                    .unwrap_or_else(|BadRangeError| {
-                        // bad byte range
-                        let mut resp = Response::new(Body::empty());
-                        *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-                        resp.headers_mut()
-                            .typed_insert(ContentRange::unsatisfied_bytes(len));
-                        Ok(resp)
-                    })
+                Ok(resp)
             }
         }
     }
@@ -47,6 +47,7 @@ impl<T: Read + Unpin> Stream for MemCacheFileStream<T> {
                                 buf_size,
                                 mem_file_opts.content_type.to_owned(),
                                 mem_file_opts.last_modified,
+                                mem_file_opts.etag.clone(),
                             ));
 
                             let file_path = mem_file_opts.file_path.as_str();
@@ -59,6 +60,7 @@ impl<T: Read + Unpin> Stream for MemCacheFileStream<T> {
                                 .get()
                                 .unwrap()
                                 .insert(file_path.into(), mem_file);
+                            crate::metrics::memory_cache_insert();
                         }
                     }
 
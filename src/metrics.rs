@@ -3,26 +3,139 @@
 // See https://static-web-server.net/ for more information
 // Copyright (C) 2019-present Jose Quintana <joseluisq.net>
 
-//! Module providing the experimental metrics endpoint.
+//! Module providing the Prometheus metrics endpoint and the HTTP metrics it exposes.
 //!
 
 use headers::{ContentType, HeaderMapExt};
-use hyper::{Body, Request, Response};
-use prometheus::{default_registry, Encoder, TextEncoder};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use lazy_static::lazy_static;
+use prometheus::{
+    default_registry, exponential_buckets, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, Encoder, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, TextEncoder,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-use crate::{handler::RequestHandlerOpts, http_ext::MethodExt, Error};
+use crate::{
+    handler::RequestHandlerOpts, http_ext::MethodExt, response, virtual_hosts, Error, Result,
+};
+
+#[cfg(feature = "experimental")]
+use {
+    crate::mem_cache::cache::CACHE_STORE, mini_moka::sync::ConcurrentCacheExt,
+    std::sync::atomic::AtomicU64,
+};
+
+/// Label of the requests not matching any virtual host.
+const DEFAULT_VHOST: &str = "default";
+
+/// Whether the metrics are enabled, for the places where no handler options are available.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "sws_http_requests_total",
+        "Number of HTTP requests handled.",
+        &["method", "status", "vhost"]
+    )
+    .unwrap();
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "sws_http_request_duration_seconds",
+        "Time elapsed until the HTTP response was completed.",
+        &["method", "vhost"]
+    )
+    .unwrap();
+    static ref HTTP_RESPONSE_SIZE: HistogramVec = register_histogram_vec!(
+        "sws_http_response_size_bytes",
+        "Size of the HTTP response bodies sent.",
+        &["method", "vhost"],
+        // From 64 bytes to 64 MiB
+        exponential_buckets(64.0, 4.0, 11).unwrap()
+    )
+    .unwrap();
+    static ref HTTP_RESPONSE_BYTES: IntCounterVec = register_int_counter_vec!(
+        "sws_http_response_bytes_total",
+        "Number of HTTP response body bytes sent.",
+        &["vhost"]
+    )
+    .unwrap();
+    static ref COMPRESSION_INPUT_BYTES: IntCounterVec = register_int_counter_vec!(
+        "sws_compression_input_bytes_total",
+        "Number of response body bytes compressed on the fly.",
+        &["encoding"]
+    )
+    .unwrap();
+    static ref COMPRESSION_OUTPUT_BYTES: IntCounterVec = register_int_counter_vec!(
+        "sws_compression_output_bytes_total",
+        "Number of response body bytes produced by the on the fly compression.",
+        &["encoding"]
+    )
+    .unwrap();
+    static ref CONNECTIONS_ACTIVE: IntGauge = register_int_gauge!(
+        "sws_connections_active",
+        "Number of client connections currently open."
+    )
+    .unwrap();
+    static ref CONNECTIONS: IntCounter = register_int_counter!(
+        "sws_connections_total",
+        "Number of client connections accepted."
+    )
+    .unwrap();
+    static ref TLS_HANDSHAKE_FAILURES: IntCounter = register_int_counter!(
+        "sws_tls_handshake_failures_total",
+        "Number of failed TLS handshakes."
+    )
+    .unwrap();
+}
+
+#[cfg(feature = "experimental")]
+lazy_static! {
+    static ref MEMORY_CACHE_HITS: IntCounter = register_int_counter!(
+        "sws_memory_cache_hits_total",
+        "Number of files served from the in-memory cache."
+    )
+    .unwrap();
+    static ref MEMORY_CACHE_MISSES: IntCounter = register_int_counter!(
+        "sws_memory_cache_misses_total",
+        "Number of files not found in the in-memory cache."
+    )
+    .unwrap();
+    static ref MEMORY_CACHE_ENTRIES: IntGauge = register_int_gauge!(
+        "sws_memory_cache_entries",
+        "Number of files currently stored in the in-memory cache."
+    )
+    .unwrap();
+    static ref MEMORY_CACHE_EVICTIONS: IntCounter = register_int_counter!(
+        "sws_memory_cache_evictions_total",
+        "Number of files removed from the in-memory cache because of its capacity or expiration."
+    )
+    .unwrap();
+}
+
+/// Number of files inserted into the in-memory cache, used to compute the evictions.
+#[cfg(feature = "experimental")]
+static MEMORY_CACHE_INSERTS: AtomicU64 = AtomicU64::new(0);
 
 /// Initializes the metrics endpoint.
 pub fn init(enabled: bool, handler_opts: &mut RequestHandlerOpts) {
-    handler_opts.experimental_metrics = enabled;
-    server_info!("metrics endpoint (experimental): enabled={enabled}");
+    handler_opts.metrics = enabled;
+    ENABLED.store(enabled, Ordering::Relaxed);
+    server_info!("metrics endpoint: enabled={enabled}");
+}
+
+/// Adds the Tokio runtime metrics to the metrics endpoint, enabling it if needed.
+#[cfg(all(unix, feature = "experimental"))]
+pub fn init_runtime_metrics(enabled: bool, handler_opts: &mut RequestHandlerOpts) {
+    server_info!("tokio runtime metrics (experimental): enabled={enabled}");
 
     if enabled {
-        default_registry()
-            .register(Box::new(
-                tokio_metrics_collector::default_runtime_collector(),
-            ))
-            .unwrap();
+        handler_opts.metrics = true;
+        ENABLED.store(true, Ordering::Relaxed);
+        // NOTE: the collector is registered only once, so config reloads keep it
+        let _ = default_registry().register(Box::new(
+            tokio_metrics_collector::default_runtime_collector(),
+        ));
     }
 }
 
@@ -31,21 +144,22 @@ pub fn pre_process<T>(
     opts: &RequestHandlerOpts,
     req: &Request<T>,
 ) -> Option<Result<Response<Body>, Error>> {
-    if !opts.experimental_metrics {
-        return None;
-    }
-
-    let uri = req.uri();
-    if uri.path() != "/metrics" {
+    if !opts.metrics || opts.admin_listener || req.uri().path() != "/metrics" {
         return None;
     }
+    response(req.method()).map(Ok)
+}
 
-    let method = req.method();
+/// Returns the metrics endpoint response if the method is supported.
+pub(crate) fn response(method: &Method) -> Option<Response<Body>> {
     if !method.is_get() && !method.is_head() {
         return None;
     }
 
     let body = if method.is_get() {
+        #[cfg(feature = "experimental")]
+        update_memory_cache_metrics();
+
         let encoder = TextEncoder::new();
         let mut buffer = Vec::new();
         encoder
@@ -59,14 +173,189 @@ pub fn pre_process<T>(
     let mut resp = Response::new(body);
     resp.headers_mut()
         .typed_insert(ContentType::from(mime_guess::mime::TEXT_PLAIN_UTF_8));
-    Some(Ok(resp))
+    Some(resp)
+}
+
+/// The metrics of a request which are recorded once its response is completed.
+pub(crate) struct RequestMetrics {
+    started: Instant,
+    method: Method,
+    vhost: String,
+}
+
+impl RequestMetrics {
+    fn record(self, status: StatusCode, bytes: u64) {
+        let method = method_label(&self.method);
+        let vhost = self.vhost.as_str();
+        HTTP_REQUESTS
+            .with_label_values(&[method, status.as_str(), vhost])
+            .inc();
+        HTTP_REQUEST_DURATION
+            .with_label_values(&[method, vhost])
+            .observe(self.started.elapsed().as_secs_f64());
+        HTTP_RESPONSE_SIZE
+            .with_label_values(&[method, vhost])
+            .observe(bytes as f64);
+        HTTP_RESPONSE_BYTES
+            .with_label_values(&[vhost])
+            .inc_by(bytes);
+    }
+}
+
+/// Returns the method label, grouping the non-standard methods to keep the label values bounded.
+fn method_label(method: &Method) -> &str {
+    match *method {
+        Method::GET
+        | Method::HEAD
+        | Method::POST
+        | Method::PUT
+        | Method::DELETE
+        | Method::OPTIONS
+        | Method::PATCH
+        | Method::TRACE
+        | Method::CONNECT => method.as_str(),
+        _ => "OTHER",
+    }
+}
+
+/// Starts measuring the incoming request if the metrics are enabled.
+pub(crate) fn start<T>(opts: &RequestHandlerOpts, req: &Request<T>) -> Option<RequestMetrics> {
+    if !opts.metrics {
+        return None;
+    }
+    let vhost = opts
+        .advanced_opts
+        .as_ref()
+        .and_then(|advanced| virtual_hosts::get_vhost(req, advanced.virtual_hosts.as_deref()))
+        .map_or(DEFAULT_VHOST, |vhost| vhost.host.as_str());
+    Some(RequestMetrics {
+        started: Instant::now(),
+        method: req.method().clone(),
+        vhost: vhost.to_owned(),
+    })
+}
+
+/// Records the request metrics once the response body was sent or dropped.
+pub(crate) fn post_process(
+    metrics: Option<RequestMetrics>,
+    result: Result<Response<Body>>,
+) -> Result<Response<Body>> {
+    let Some(metrics) = metrics else {
+        return result;
+    };
+    match result {
+        Ok(resp) => {
+            let status = resp.status();
+            Ok(response::on_body_complete(resp, move |bytes| {
+                metrics.record(status, bytes)
+            }))
+        }
+        Err(err) => {
+            metrics.record(StatusCode::INTERNAL_SERVER_ERROR, 0);
+            Err(err)
+        }
+    }
+}
+
+/// Compresses a response with the given encoder counting its body bytes before and after.
+#[cfg(any(
+    feature = "compression",
+    feature = "compression-gzip",
+    feature = "compression-brotli",
+    feature = "compression-zstd",
+    feature = "compression-deflate"
+))]
+pub(crate) fn compression(
+    encoding: &str,
+    resp: Response<Body>,
+    encoder: impl FnOnce(Response<Body>) -> Response<Body>,
+) -> Response<Body> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return encoder(resp);
+    }
+    let input = COMPRESSION_INPUT_BYTES.with_label_values(&[encoding]);
+    let output = COMPRESSION_OUTPUT_BYTES.with_label_values(&[encoding]);
+    encoder(resp.map(|body| counted(body, input))).map(|body| counted(body, output))
+}
+
+/// Wraps a body adding the size of its chunks to the given counter.
+#[cfg(any(
+    feature = "compression",
+    feature = "compression-gzip",
+    feature = "compression-brotli",
+    feature = "compression-zstd",
+    feature = "compression-deflate"
+))]
+fn counted(body: Body, counter: IntCounter) -> Body {
+    use futures_util::TryStreamExt;
+
+    Body::wrap_stream(body.inspect_ok(move |chunk| counter.inc_by(chunk.len() as u64)))
+}
+
+/// A client connection counted as active until dropped.
+pub(crate) struct Connection(());
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        CONNECTIONS_ACTIVE.dec();
+    }
+}
+
+/// Counts a new client connection if the metrics are enabled.
+pub(crate) fn connection_opened() -> Option<Connection> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    CONNECTIONS.inc();
+    CONNECTIONS_ACTIVE.inc();
+    Some(Connection(()))
+}
+
+/// Counts a failed TLS handshake.
+#[cfg(feature = "http2")]
+pub(crate) fn tls_handshake_failed() {
+    TLS_HANDSHAKE_FAILURES.inc();
+}
+
+/// Counts a file served from the in-memory cache.
+#[cfg(feature = "experimental")]
+pub(crate) fn memory_cache_hit() {
+    MEMORY_CACHE_HITS.inc();
+}
+
+/// Counts a file not found in the in-memory cache.
+#[cfg(feature = "experimental")]
+pub(crate) fn memory_cache_miss() {
+    MEMORY_CACHE_MISSES.inc();
+}
+
+/// Counts a file inserted into the in-memory cache.
+#[cfg(feature = "experimental")]
+pub(crate) fn memory_cache_insert() {
+    MEMORY_CACHE_INSERTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Updates the in-memory cache size and evictions which are only known by the cache store.
+#[cfg(feature = "experimental")]
+fn update_memory_cache_metrics() {
+    let Some(store) = CACHE_STORE.get() else {
+        return;
+    };
+    // Apply the pending evictions first so the entry count is accurate
+    store.sync();
+    let entries = store.entry_count();
+    MEMORY_CACHE_ENTRIES.set(entries as i64);
+
+    let evicted = MEMORY_CACHE_INSERTS
+        .load(Ordering::Relaxed)
+        .saturating_sub(entries);
+    MEMORY_CACHE_EVICTIONS.inc_by(evicted.saturating_sub(MEMORY_CACHE_EVICTIONS.get()));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::pre_process;
-    use crate::handler::RequestHandlerOpts;
-    use hyper::{Body, Request};
+    use super::*;
+    use crate::settings::{Advanced, VirtualHosts};
 
     fn make_request(method: &str, uri: &str) -> Request<Body> {
         Request::builder()
@@ -76,11 +365,26 @@ mod tests {
             .unwrap()
     }
 
+    fn metrics_opts() -> RequestHandlerOpts {
+        RequestHandlerOpts {
+            metrics: true,
+            ..Default::default()
+        }
+    }
+
+    async fn gather() -> String {
+        let resp = pre_process(&metrics_opts(), &make_request("GET", "/metrics"))
+            .unwrap()
+            .unwrap();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
     #[test]
     fn test_metrics_disabled() {
         assert!(pre_process(
             &RequestHandlerOpts {
-                experimental_metrics: false,
+                metrics: false,
                 ..Default::default()
             },
             &make_request("GET", "/metrics")
@@ -92,7 +396,7 @@ mod tests {
     fn test_wrong_uri() {
         assert!(pre_process(
             &RequestHandlerOpts {
-                experimental_metrics: true,
+                metrics: true,
                 ..Default::default()
             },
             &make_request("GET", "/metrics2")
@@ -104,7 +408,7 @@ mod tests {
     fn test_wrong_method() {
         assert!(pre_process(
             &RequestHandlerOpts {
-                experimental_metrics: true,
+                metrics: true,
                 ..Default::default()
             },
             &make_request("POST", "/metrics")
@@ -116,11 +420,81 @@ mod tests {
     fn test_correct_request() {
         assert!(pre_process(
             &RequestHandlerOpts {
-                experimental_metrics: true,
+                metrics: true,
                 ..Default::default()
             },
             &make_request("GET", "/metrics")
         )
         .is_some());
     }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label(&Method::GET), "GET");
+        assert_eq!(
+            method_label(&Method::from_bytes(b"PURGE").unwrap()),
+            "OTHER"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_metrics() {
+        let mut opts = metrics_opts();
+        opts.advanced_opts = Some(Advanced {
+            virtual_hosts: Some(vec![VirtualHosts {
+                host: "metrics.test".to_owned(),
+                root: "docker/public".into(),
+                #[cfg(feature = "http2")]
+                tls_cert_key: None,
+            }]),
+            ..Default::default()
+        });
+        let req = Request::get("/")
+            .header("host", "metrics.test")
+            .body(Body::empty())
+            .unwrap();
+
+        let metrics = start(&opts, &req);
+        let resp = Response::builder()
+            .status(StatusCode::IM_A_TEAPOT)
+            .body(Body::from("x".repeat(100)))
+            .unwrap();
+        let resp = post_process(metrics, Ok(resp)).unwrap();
+        assert_eq!(resp.headers()["content-length"], "100");
+        hyper::body::to_bytes(resp.into_body()).await.unwrap();
+
+        let metrics = gather().await;
+        assert!(metrics.contains(
+            r#"sws_http_requests_total{method="GET",status="418",vhost="metrics.test"} 1"#
+        ));
+        assert!(metrics.contains(
+            r#"sws_http_response_size_bytes_bucket{method="GET",vhost="metrics.test",le="256"} 1"#
+        ));
+        assert!(metrics.contains(
+            r#"sws_http_request_duration_seconds_count{method="GET",vhost="metrics.test"} 1"#
+        ));
+        assert!(metrics.contains(r#"sws_http_response_bytes_total{vhost="metrics.test"} 100"#));
+    }
+
+    #[tokio::test]
+    #[cfg(any(
+        feature = "compression",
+        feature = "compression-gzip",
+        feature = "compression-brotli",
+        feature = "compression-zstd",
+        feature = "compression-deflate"
+    ))]
+    async fn test_compression_metrics() {
+        use futures_util::TryStreamExt;
+
+        ENABLED.store(true, Ordering::Relaxed);
+        let resp = compression("test", Response::new(Body::from("abcd")), |resp| {
+            resp.map(|body| Body::wrap_stream(body.map_ok(|chunk| chunk.slice(..1))))
+        });
+        hyper::body::to_bytes(resp.into_body()).await.unwrap();
+
+        let metrics = gather().await;
+        assert!(metrics.contains(r#"sws_compression_input_bytes_total{encoding="test"} 4"#));
+        assert!(metrics.contains(r#"sws_compression_output_bytes_total{encoding="test"} 1"#));
+    }
 }
@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module that provides TLS client certificate (mutual TLS) authentication.
+//!
+
+use clap::ValueEnum;
+use hyper::{Body, Request, Response, StatusCode};
+use std::sync::{Arc, OnceLock};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::{error_page, handler::RequestHandlerOpts, helpers, settings::ClientCertAuth, Error};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Defines whether TLS clients have to present a certificate.
+pub enum ClientAuthMode {
+    /// Client certificates are not requested (default).
+    None,
+    /// Client certificates are verified if presented but not required.
+    Optional,
+    /// Connections without a valid client certificate are rejected.
+    Required,
+}
+
+/// The verified TLS client certificate of a connection.
+/// It's available as a request extension once the TLS handshake is completed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCert {
+    /// The certificate subject distinguished name. E.g. `CN=alice, O=Example`.
+    pub subject: String,
+}
+
+impl ClientCert {
+    /// Parses the client certificate information out of a DER-encoded certificate.
+    pub(crate) fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+        Some(Self {
+            subject: cert.subject().to_string(),
+        })
+    }
+}
+
+/// A connection slot which holds the client certificate once the TLS handshake is completed.
+pub type ClientCertSlot = Arc<OnceLock<ClientCert>>;
+
+/// Checks the client certificate authorization rules of the request path.
+pub(crate) fn pre_process<T>(
+    opts: &RequestHandlerOpts,
+    req: &Request<T>,
+) -> Option<Result<Response<Body>, Error>> {
+    let rules = opts.advanced_opts.as_ref()?.client_cert_auth.as_deref()?;
+    let status = match helpers::normalize_request_path(req.uri().path()) {
+        Ok(path) => {
+            let rule = rules.iter().find(|r| r.source.is_match(&path))?;
+            let cert = req.extensions().get::<ClientCert>();
+            if is_authorized(rule, cert) {
+                return None;
+            }
+            tracing::debug!(
+                "client certificate not authorized: uri={} subject={}",
+                req.uri(),
+                cert.map_or("-", |c| c.subject.as_str())
+            );
+            StatusCode::FORBIDDEN
+        }
+        Err(status) => status,
+    };
+
+    Some(error_page::error_response(
+        req.uri(),
+        req.method(),
+        &status,
+        &opts.page404,
+        &opts.page50x,
+    ))
+}
+
+/// Checks whether a client certificate is allowed by the given rule.
+/// A rule without subject patterns accepts any verified certificate.
+fn is_authorized(rule: &ClientCertAuth, cert: Option<&ClientCert>) -> bool {
+    match cert {
+        Some(cert) => {
+            rule.subjects.is_empty() || rule.subjects.iter().any(|s| s.is_match(&cert.subject))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pre_process, ClientCert};
+    use crate::{
+        handler::RequestHandlerOpts,
+        settings::{Advanced, ClientCertAuth},
+    };
+    use globset::Glob;
+    use hyper::{Request, StatusCode};
+
+    fn opts() -> RequestHandlerOpts {
+        let glob = |s: &str| Glob::new(s).unwrap().compile_matcher();
+        RequestHandlerOpts {
+            advanced_opts: Some(Advanced {
+                client_cert_auth: Some(vec![
+                    ClientCertAuth {
+                        source: glob("/admin/**"),
+                        subjects: vec![glob("CN=admin*")],
+                    },
+                    ClientCertAuth {
+                        source: glob("/private/**"),
+                        subjects: vec![],
+                    },
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn req(path: &str, subject: Option<&str>) -> Request<()> {
+        let mut req = Request::get(path).body(()).unwrap();
+        if let Some(subject) = subject {
+            req.extensions_mut().insert(ClientCert {
+                subject: subject.to_owned(),
+            });
+        }
+        req
+    }
+
+    fn status(path: &str, subject: Option<&str>) -> Option<StatusCode> {
+        pre_process(&opts(), &req(path, subject)).map(|r| r.unwrap().status())
+    }
+
+    #[test]
+    fn client_cert_rules() {
+        assert_eq!(status("/index.html", None), None);
+        assert_eq!(status("/private/a.txt", Some("CN=alice")), None);
+        assert_eq!(status("/admin/a.txt", Some("CN=admin-1, O=Org")), None);
+
+        assert_eq!(status("/private/a.txt", None), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status("/admin/a.txt", None), Some(StatusCode::FORBIDDEN));
+        assert_eq!(
+            status("/admin/a.txt", Some("CN=alice")),
+            Some(StatusCode::FORBIDDEN)
+        );
+        for path in ["/%61dmin/a.txt", "//admin/a.txt", "/./admin/a.txt"] {
+            assert_eq!(
+                status(path, Some("CN=alice")),
+                Some(StatusCode::FORBIDDEN),
+                "{path}"
+            );
+        }
+        assert_eq!(
+            status("/private/../admin/a.txt", Some("CN=alice")),
+            Some(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn client_cert_from_der() {
+        let pem = std::fs::read("tests/tls/local.dev_cert.sec1_ec.pem").unwrap();
+        let der = rustls_pemfile::certs(&mut pem.as_slice())
+            .next()
+            .unwrap()
+            .unwrap();
+        let cert = ClientCert::from_der(&der).unwrap();
+        assert!(cert.subject.contains("CN="), "{}", cert.subject);
+        assert!(ClientCert::from_der(b"invalid").is_none());
+    }
+}
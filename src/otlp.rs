@@ -0,0 +1,372 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module exporting the request tracing spans to an OpenTelemetry collector via OTLP
+//! and continuing the traces received in the W3C `traceparent` header.
+//!
+
+use clap::ValueEnum;
+use hyper::{header::HeaderMap, Body, Request, Response};
+use opentelemetry::{
+    propagation::Extractor, propagation::TextMapPropagator, trace::TracerProvider,
+};
+use opentelemetry_otlp::{
+    tonic_types::transport::ClientTlsConfig, SpanExporter, WithExportConfig, WithTonicConfig,
+};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tracing::{Level, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{filter::filter_fn, registry::LookupSpan, Layer};
+
+use crate::{handler::RequestHandlerOpts, Context, Result};
+
+/// Name of the tracer creating the exported spans.
+const TRACER_NAME: &str = "static-web-server";
+
+/// The traces exporter used by the logging system, if configured.
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Transport protocol used to export the traces to the collector.
+pub enum OtlpProtocol {
+    /// OTLP/gRPC, usually on port 4317.
+    Grpc,
+    /// OTLP/HTTP with binary Protobuf payloads, usually on port 4318.
+    Http,
+}
+
+/// A tracer provider exporting spans in batches to the collector.
+struct Exporter {
+    provider: SdkTracerProvider,
+    /// Runtime driving the gRPC client when created outside of the server one.
+    _runtime: Option<Runtime>,
+}
+
+impl Exporter {
+    /// Creates an exporter sending the spans to the collector at the given endpoint.
+    fn new(endpoint: &str, protocol: OtlpProtocol, service_name: &str) -> Result<Self> {
+        let uri = endpoint
+            .parse::<hyper::Uri>()
+            .with_context(|| format!("invalid OTLP endpoint \"{endpoint}\""))?;
+        if !matches!(uri.scheme_str(), Some("http" | "https")) {
+            bail!("invalid OTLP endpoint \"{endpoint}\", an http or https URL is expected");
+        }
+
+        let mut runtime = None;
+        let exporter = match protocol {
+            OtlpProtocol::Grpc => {
+                // The gRPC client spawns its connection tasks so it needs a Tokio runtime
+                let handle = match tokio::runtime::Handle::try_current() {
+                    Ok(handle) => handle,
+                    Err(_) => runtime
+                        .insert(
+                            tokio::runtime::Builder::new_multi_thread()
+                                .worker_threads(1)
+                                .thread_name("sws-otlp")
+                                .enable_all()
+                                .build()?,
+                        )
+                        .handle()
+                        .clone(),
+                };
+                let _guard = handle.enter();
+                let builder = SpanExporter::builder().with_tonic().with_endpoint(endpoint);
+                if uri.scheme_str() == Some("https") {
+                    builder
+                        .with_tls_config(ClientTlsConfig::new().with_native_roots())
+                        .build()
+                } else {
+                    builder.build()
+                }
+            }
+            OtlpProtocol::Http => SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+                .build(),
+        }
+        .with_context(|| "failed to create the OTLP traces exporter")?;
+
+        let resource = Resource::builder()
+            .with_service_name(service_name.to_owned())
+            .build();
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build();
+
+        Ok(Self {
+            provider,
+            _runtime: runtime,
+        })
+    }
+
+    /// Returns a layer turning the server spans into OpenTelemetry ones.
+    fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.provider.tracer(TRACER_NAME))
+            .with_filter(filter_fn(|meta| {
+                // All the server spans but only the relevant events within them
+                meta.target().starts_with("static_web_server")
+                    && (meta.is_span() || *meta.level() <= Level::INFO)
+            }))
+    }
+}
+
+/// Creates the traces exporter if an endpoint is set. It must be called before the logging initialization.
+pub fn init_exporter(endpoint: Option<&str>, protocol: OtlpProtocol, service_name: &str) -> Result {
+    if let Some(endpoint) = endpoint {
+        let exporter = Exporter::new(endpoint, protocol, service_name)?;
+        if EXPORTER.set(exporter).is_err() {
+            bail!("the OTLP traces exporter is already initialized");
+        }
+    }
+    Ok(())
+}
+
+/// Returns the layer exporting the spans for the logging system if the exporter is initialized.
+pub(crate) fn layer<S>() -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    EXPORTER.get().map(Exporter::layer)
+}
+
+/// Exports the pending spans and stops the exporter.
+pub fn shutdown() {
+    if let Some(exporter) = EXPORTER.get() {
+        if let Err(err) = exporter.provider.shutdown() {
+            tracing::error!("unable to export the pending spans: {err}");
+        }
+    }
+}
+
+/// Initializes the request spans export.
+pub(crate) fn init(
+    endpoint: Option<&str>,
+    protocol: OtlpProtocol,
+    service_name: &str,
+    handler_opts: &mut RequestHandlerOpts,
+) {
+    handler_opts.otlp = endpoint.is_some() && EXPORTER.get().is_some();
+    server_info!(
+        "OTLP traces export: enabled={}, endpoint={}, protocol={protocol:?}, service name={service_name}",
+        handler_opts.otlp,
+        endpoint.unwrap_or("-"),
+    );
+}
+
+/// Reads the W3C trace context propagation headers of a request.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Records the request attributes on its span and continues the trace received in the `traceparent` header.
+pub(crate) fn pre_process<T>(
+    opts: &RequestHandlerOpts,
+    req: &Request<T>,
+    remote_addr: Option<SocketAddr>,
+    span: &Span,
+) {
+    if !opts.otlp || span.is_none() {
+        return;
+    }
+
+    // Attributes set on the OpenTelemetry span only to keep the log lines short
+    let method = req.method().as_str();
+    span.record("otel.name", method);
+    span.record("otel.kind", "server");
+    span.set_attribute("http.request.method", method.to_owned());
+    span.set_attribute("url.path", req.uri().path().to_owned());
+    let headers = req.headers();
+    for (name, key) in [
+        (hyper::header::HOST, "server.address"),
+        (hyper::header::USER_AGENT, "user_agent.original"),
+    ] {
+        if let Some(value) = headers.get(name).and_then(|value| value.to_str().ok()) {
+            span.set_attribute(key, value.to_owned());
+        }
+    }
+    if let Some(addr) = remote_addr {
+        span.set_attribute("client.address", addr.ip().to_canonical().to_string());
+    }
+
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    if let Err(err) = span.set_parent(parent) {
+        tracing::debug!("unable to continue the trace of the request: {err}");
+    }
+}
+
+/// Records the response status on the request span.
+pub(crate) fn post_process(opts: &RequestHandlerOpts, span: &Span, resp: &Response<Body>) {
+    if !opts.otlp {
+        return;
+    }
+    let status = resp.status();
+    span.set_attribute("http.response.status_code", i64::from(status.as_u16()));
+    if status.is_server_error() {
+        span.set_status(opentelemetry::trace::Status::error(status.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body::Bytes, Server};
+    use std::convert::Infallible;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+    use tracing_subscriber::prelude::*;
+
+    use crate::handler::RequestHandler;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    /// An export request received by the mock collector.
+    struct Received {
+        path: String,
+        content_type: String,
+        body: Bytes,
+    }
+
+    /// Starts a collector answering any OTLP/HTTP or OTLP/gRPC export successfully.
+    fn mock_collector() -> (SocketAddr, mpsc::UnboundedReceiver<Received>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let make_svc = make_service_fn(move |_| {
+            let tx = tx.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let tx = tx.clone();
+                    async move {
+                        let path = req.uri().path().to_owned();
+                        let content_type =
+                            req.headers()["content-type"].to_str().unwrap().to_owned();
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let grpc = content_type.starts_with("application/grpc");
+                        tx.send(Received {
+                            path,
+                            content_type,
+                            body,
+                        })
+                        .unwrap();
+
+                        if !grpc {
+                            return Ok::<_, Infallible>(Response::new(Body::empty()));
+                        }
+                        // An empty export response message followed by an OK status
+                        let (mut sender, body) = Body::channel();
+                        tokio::spawn(async move {
+                            let _ = sender.send_data(Bytes::from_static(&[0; 5])).await;
+                            let mut trailers = HeaderMap::new();
+                            trailers.insert("grpc-status", "0".parse().unwrap());
+                            let _ = sender.send_trailers(trailers).await;
+                        });
+                        Ok(Response::builder()
+                            .header("content-type", "application/grpc")
+                            .body(body)
+                            .unwrap())
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        (addr, rx)
+    }
+
+    async fn export_request_spans(protocol: OtlpProtocol) -> Received {
+        let (addr, mut rx) = mock_collector();
+        let exporter = Exporter::new(&format!("http://{addr}"), protocol, "sws-test").unwrap();
+        let subscriber = tracing_subscriber::registry().with(exporter.layer());
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: PathBuf::from("docker/public"),
+                otlp: true,
+                ..Default::default()
+            }),
+        };
+
+        let mut req = Request::get("/")
+            .header("traceparent", TRACEPARENT)
+            .body(Body::empty())
+            .unwrap();
+        {
+            let _guard = tracing::subscriber::set_default(subscriber);
+            let resp = handler.handle(&mut req, None).await.unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+
+        // Exporting blocks until the collector answers
+        tokio::task::spawn_blocking(move || {
+            exporter.provider.shutdown().unwrap();
+            drop(exporter);
+        })
+        .await
+        .unwrap();
+        rx.recv().await.unwrap()
+    }
+
+    fn assert_spans(received: &Received) {
+        let body = &received.body;
+        let contains = |bytes: &[u8]| body.windows(bytes.len()).any(|window| window == bytes);
+        let trace_id: Vec<u8> = (0..TRACE_ID.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&TRACE_ID[i..i + 2], 16).unwrap())
+            .collect();
+
+        assert!(
+            contains(&trace_id),
+            "the trace of the traceparent header is continued"
+        );
+        assert!(contains(b"sws-test"));
+        assert!(contains(b"static_files"));
+        assert!(contains(b"http.request.method"));
+        assert!(contains(b"url.path"));
+        assert!(contains(b"http.response.status_code"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exports_spans_over_http() {
+        let received = export_request_spans(OtlpProtocol::Http).await;
+        assert_eq!(received.path, "/v1/traces");
+        assert_eq!(received.content_type, "application/x-protobuf");
+        assert_spans(&received);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exports_spans_over_grpc() {
+        let received = export_request_spans(OtlpProtocol::Grpc).await;
+        assert_eq!(
+            received.path,
+            "/opentelemetry.proto.collector.trace.v1.TraceService/Export"
+        );
+        assert_eq!(received.content_type, "application/grpc");
+        assert_spans(&received);
+    }
+
+    #[test]
+    fn rejects_invalid_endpoints() {
+        assert!(Exporter::new("localhost:4318", OtlpProtocol::Http, "sws").is_err());
+        assert!(Exporter::new("ftp://localhost", OtlpProtocol::Http, "sws").is_err());
+    }
+}
@@ -0,0 +1,416 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! The module decodes the [PROXY protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt)
+//! (v1 and v2) header sent by trusted load balancers.
+//!
+
+use hyper::server::accept::Accept;
+use ipnet::IpNet;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+
+use crate::transport::Transport;
+
+#[cfg(feature = "http2")]
+use crate::mtls::ClientCertSlot;
+#[cfg(unix)]
+use crate::unix_socket::PeerCred;
+
+/// Maximum time to wait for the PROXY protocol header of a trusted connection.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The v2 binary header signature.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Maximum length of a v1 text header including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Errors decoding a PROXY protocol header.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The data doesn't start with a PROXY protocol signature.
+    Missing,
+    /// The header is malformed or uses an unsupported version.
+    Invalid(&'static str),
+}
+
+impl std::fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderError::Missing => f.write_str("proxy protocol header missing"),
+            HeaderError::Invalid(reason) => write!(f, "invalid proxy protocol header: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// Decodes a PROXY protocol header at the start of `buf`.
+///
+/// It returns the source address carried by the header (`None` for `LOCAL` or `UNKNOWN`
+/// connections which keep the peer address) and the header length,
+/// or `Ok(None)` if more data is needed.
+pub fn parse_header(buf: &[u8]) -> Result<Option<(Option<SocketAddr>, usize)>, HeaderError> {
+    let prefix = &buf[..buf.len().min(V2_SIGNATURE.len())];
+    if V2_SIGNATURE.starts_with(prefix) {
+        return parse_v2(buf);
+    }
+    if b"PROXY ".starts_with(&buf[..buf.len().min(6)]) {
+        return parse_v1(buf);
+    }
+    Err(HeaderError::Missing)
+}
+
+fn parse_v1(buf: &[u8]) -> Result<Option<(Option<SocketAddr>, usize)>, HeaderError> {
+    let end = match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(pos) => pos,
+        None if buf.len() >= V1_MAX_LEN => return Err(HeaderError::Invalid("v1 header too long")),
+        None => return Ok(None),
+    };
+    if end + 2 > V1_MAX_LEN {
+        return Err(HeaderError::Invalid("v1 header too long"));
+    }
+
+    let line = std::str::from_utf8(&buf[..end])
+        .map_err(|_| HeaderError::Invalid("v1 header is not valid text"))?;
+    let mut parts = line.split(' ').skip(1);
+    let source = match parts.next() {
+        Some("UNKNOWN") => None,
+        Some(proto @ ("TCP4" | "TCP6")) => {
+            let fields = parts.collect::<Vec<_>>();
+            if fields.len() != 4 {
+                return Err(HeaderError::Invalid(
+                    "v1 header has a wrong number of fields",
+                ));
+            }
+            let ip = fields[0]
+                .parse::<IpAddr>()
+                .map_err(|_| HeaderError::Invalid("v1 source address"))?;
+            if ip.is_ipv4() != (proto == "TCP4") {
+                return Err(HeaderError::Invalid("v1 address family mismatch"));
+            }
+            let port = fields[2]
+                .parse::<u16>()
+                .map_err(|_| HeaderError::Invalid("v1 source port"))?;
+            Some(SocketAddr::new(ip, port))
+        }
+        _ => return Err(HeaderError::Invalid("v1 unsupported protocol")),
+    };
+    Ok(Some((source, end + 2)))
+}
+
+fn parse_v2(buf: &[u8]) -> Result<Option<(Option<SocketAddr>, usize)>, HeaderError> {
+    if buf.len() < 16 {
+        return Ok(None);
+    }
+    let version_command = buf[12];
+    if version_command >> 4 != 2 {
+        return Err(HeaderError::Invalid("v2 unsupported version"));
+    }
+    let len = 16 + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < len {
+        return Ok(None);
+    }
+
+    let addresses = &buf[16..len];
+    let source = match (version_command & 0x0F, buf[13]) {
+        // LOCAL command, e.g. health checks of the load balancer itself
+        (0x0, _) => None,
+        // PROXY command over TCP or UDP on IPv4
+        (0x1, 0x11 | 0x12) => {
+            if addresses.len() < 12 {
+                return Err(HeaderError::Invalid("v2 truncated IPv4 addresses"));
+            }
+            let ip = Ipv4Addr::from([addresses[0], addresses[1], addresses[2], addresses[3]]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Some(SocketAddr::new(ip.into(), port))
+        }
+        // PROXY command over TCP or UDP on IPv6
+        (0x1, 0x21 | 0x22) => {
+            if addresses.len() < 36 {
+                return Err(HeaderError::Invalid("v2 truncated IPv6 addresses"));
+            }
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port))
+        }
+        // Unspecified or Unix socket addresses keep the peer address
+        (0x1, _) => None,
+        _ => return Err(HeaderError::Invalid("v2 unsupported command")),
+    };
+    Ok(Some((source, len)))
+}
+
+/// Reads the PROXY protocol header off a connection returning the source address
+/// and the data received right after the header.
+async fn read_header<C: AsyncRead + Unpin>(
+    conn: &mut C,
+) -> io::Result<(Option<SocketAddr>, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(V1_MAX_LEN);
+    let mut chunk = [0u8; 512];
+    loop {
+        let n = conn.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        match parse_header(&buf) {
+            Ok(Some((source, len))) => return Ok((source, buf.split_off(len))),
+            Ok(None) => continue,
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+    }
+}
+
+/// Type to decode the PROXY protocol header of incoming connections from trusted sources.
+///
+/// Headers are read on their own tasks so slow connections don't hold up the accepting ones.
+/// Connections from untrusted sources are passed through as they are.
+pub struct ProxyProtocolIncoming<I: Accept> {
+    incoming: I,
+    trusted: Arc<Vec<IpNet>>,
+    tx: mpsc::UnboundedSender<ProxyProtocolStream<I::Conn>>,
+    rx: mpsc::UnboundedReceiver<ProxyProtocolStream<I::Conn>>,
+}
+
+impl<I: Accept> ProxyProtocolIncoming<I> {
+    /// Creates a new PROXY protocol decoder of the given incoming connections.
+    /// No header is decoded if `trusted` is empty.
+    pub fn new(incoming: I, trusted: Vec<IpNet>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            incoming,
+            trusted: Arc::new(trusted),
+            tx,
+            rx,
+        }
+    }
+}
+
+impl<I> Accept for ProxyProtocolIncoming<I>
+where
+    I: Accept<Error = io::Error> + Unpin,
+    I::Conn: Transport + Unpin + Send + 'static,
+{
+    type Conn = ProxyProtocolStream<I::Conn>;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let pin = self.get_mut();
+
+        loop {
+            match Pin::new(&mut pin.incoming).poll_accept(cx) {
+                Poll::Ready(Some(Ok(conn))) => {
+                    let peer_addr = conn.remote_addr();
+                    // NOTE: IPv4-mapped IPv6 addresses of dual-stack sockets are checked as IPv4
+                    let trusted = peer_addr.is_some_and(|addr| {
+                        let ip = addr.ip().to_canonical();
+                        pin.trusted.iter().any(|net| net.contains(&ip))
+                    });
+                    if !trusted {
+                        return Poll::Ready(Some(Ok(ProxyProtocolStream::new(conn, peer_addr))));
+                    }
+
+                    let tx = pin.tx.clone();
+                    tokio::spawn(async move {
+                        let mut conn = conn;
+                        match tokio::time::timeout(HEADER_TIMEOUT, read_header(&mut conn)).await {
+                            Ok(Ok((source, rest))) => {
+                                let stream = ProxyProtocolStream {
+                                    inner: conn,
+                                    remote_addr: source.or(peer_addr),
+                                    rest,
+                                };
+                                let _ = tx.send(stream);
+                            }
+                            Ok(Err(err)) => {
+                                tracing::debug!(
+                                    "proxy protocol header from {:?} rejected: {}",
+                                    peer_addr,
+                                    err
+                                );
+                            }
+                            Err(_) => {
+                                tracing::debug!(
+                                    "proxy protocol header from {:?} timed out",
+                                    peer_addr
+                                );
+                            }
+                        }
+                    });
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => break,
+            }
+        }
+
+        match pin.rx.poll_recv(cx) {
+            Poll::Ready(Some(stream)) => Poll::Ready(Some(Ok(stream))),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// A connection whose remote address is the one carried by its PROXY protocol header.
+pub struct ProxyProtocolStream<C> {
+    inner: C,
+    remote_addr: Option<SocketAddr>,
+    /// Data received along with the header which has to be read first.
+    rest: Vec<u8>,
+}
+
+impl<C> ProxyProtocolStream<C> {
+    fn new(inner: C, remote_addr: Option<SocketAddr>) -> Self {
+        Self {
+            inner,
+            remote_addr,
+            rest: Vec::new(),
+        }
+    }
+}
+
+impl<C: Transport + Unpin> Transport for ProxyProtocolStream<C> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    #[cfg(unix)]
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.inner.peer_cred()
+    }
+
+    #[cfg(feature = "http2")]
+    fn client_cert(&self) -> Option<ClientCertSlot> {
+        self.inner.client_cert()
+    }
+}
+
+impl<C: AsyncRead + Unpin> AsyncRead for ProxyProtocolStream<C> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        if !pin.rest.is_empty() {
+            let n = pin.rest.len().min(buf.remaining());
+            buf.put_slice(&pin.rest[..n]);
+            pin.rest.drain(..n);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut pin.inner).poll_read(cx, buf)
+    }
+}
+
+impl<C: AsyncWrite + Unpin> AsyncWrite for ProxyProtocolStream<C> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> Option<SocketAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn parse_v1_header() {
+        let header = b"PROXY TCP4 192.0.2.10 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n";
+        assert_eq!(
+            parse_header(header),
+            Ok(Some((addr("192.0.2.10:56324"), 46)))
+        );
+
+        let header = b"PROXY TCP6 2001:db8::1 2001:db8::2 4000 80\r\n";
+        assert_eq!(
+            parse_header(header),
+            Ok(Some((addr("[2001:db8::1]:4000"), header.len())))
+        );
+
+        assert_eq!(parse_header(b"PROXY UNKNOWN\r\n"), Ok(Some((None, 15))));
+        assert_eq!(parse_header(b"PROXY TCP4 192.0.2.10"), Ok(None));
+        assert_eq!(parse_header(b"PRO"), Ok(None));
+        assert!(parse_header(b"PROXY TCP4 2001:db8::1 192.0.2.1 1 2\r\n").is_err());
+        assert!(parse_header(b"PROXY TCP4 192.0.2.10 192.0.2.1 x 2\r\n").is_err());
+        assert!(parse_header(&[b'A'; 120]).is_err());
+        assert_eq!(
+            parse_header(b"GET / HTTP/1.1\r\n"),
+            Err(HeaderError::Missing)
+        );
+    }
+
+    #[test]
+    fn parse_v2_header() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        header.extend_from_slice(&[192, 0, 2, 10, 198, 51, 100, 1]);
+        header.extend_from_slice(&56324u16.to_be_bytes());
+        header.extend_from_slice(&443u16.to_be_bytes());
+        assert_eq!(parse_header(&header[..20]), Ok(None));
+        header.extend_from_slice(b"GET /");
+        assert_eq!(
+            parse_header(&header),
+            Ok(Some((addr("192.0.2.10:56324"), 28)))
+        );
+
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x21, 0, 36]);
+        header.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        header.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        header.extend_from_slice(&4000u16.to_be_bytes());
+        header.extend_from_slice(&80u16.to_be_bytes());
+        assert_eq!(
+            parse_header(&header),
+            Ok(Some((addr("[2001:db8::1]:4000"), 52)))
+        );
+
+        // LOCAL command
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        assert_eq!(parse_header(&header), Ok(Some((None, 16))));
+
+        // Unsupported version
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x11, 0x11, 0, 0]);
+        assert!(parse_header(&header).is_err());
+    }
+
+    #[tokio::test]
+    async fn read_header_keeps_the_request_data() {
+        let data = b"PROXY TCP4 192.0.2.10 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n\r\n";
+        let (source, rest) = read_header(&mut &data[..]).await.unwrap();
+        assert_eq!(source, addr("192.0.2.10:56324"));
+        assert_eq!(rest, b"GET / HTTP/1.1\r\n\r\n");
+
+        assert!(read_header(&mut &b"GET / HTTP/1.1\r\n"[..]).await.is_err());
+    }
+}
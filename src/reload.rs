@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module that reloads the server configuration at runtime.
+//!
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch::Receiver};
+
+use crate::{
+    handler::RequestHandler,
+    server::build_handler_opts,
+    service::SharedRequestHandler,
+    settings::{file::Settings as FileSettings, Settings},
+    Context, Result,
+};
+
+/// Config file options which are only applied on server startup.
+const RESTART_REQUIRED: &[&str] = &[
+    "general.host",
+    "general.port",
+    "general.fd",
+    "general.unix-socket",
+    "general.unix-socket-mode",
+    "general.unix-socket-owner",
+    "general.proxy-protocol",
+    "general.proxy-protocol-trusted-cidrs",
+    "general.log-level",
+    "general.log-format",
+    "general.log-filter",
+    "general.log-output",
+    "general.log-syslog-socket",
+    "general.otlp-endpoint",
+    "general.otlp-protocol",
+    "general.otlp-service-name",
+    "general.threads-multiplier",
+    "general.max-blocking-threads",
+    "general.grace-period",
+    "general.http2",
+    "general.http2-tls-cert",
+    "general.http2-tls-key",
+    "general.tls",
+    "general.tls-cert",
+    "general.tls-key",
+    "general.tls-watch-interval",
+    "general.tls-client-auth",
+    "general.tls-client-ca",
+    "general.https-redirect",
+    "general.https-redirect-host",
+    "general.https-redirect-from-port",
+    "general.https-redirect-from-hosts",
+    "general.windows-service",
+    "general.admin-port",
+    "general.admin-host",
+    "general.admin-health-path",
+    "general.admin-ready-path",
+    "general.admin-maintenance-path",
+    "general.admin-metrics-path",
+    "general.admin-auth-token",
+    "advanced.listeners",
+    "advanced.memory-cache",
+];
+
+/// Reloads the configuration file and swaps the request handler of the running server,
+/// either on `SIGHUP` (Unix only) or when the optional `reload_recv` receiver gets notified.
+/// The settings are read via the given `load` function and the current request handler
+/// is kept if the new configuration fails validation.
+pub(crate) fn spawn_reloader<F>(
+    handler: SharedRequestHandler,
+    load: F,
+    config_file: &Path,
+    reload_recv: Option<Receiver<()>>,
+) -> Result
+where
+    F: Fn() -> Result<Settings> + Send + Sync + 'static,
+{
+    // NOTE: reload requests coming in while reloading are coalesced into a single one
+    let (tx, mut rx) = mpsc::channel::<&'static str>(1);
+
+    #[cfg(unix)]
+    {
+        use futures_util::stream::StreamExt;
+
+        let mut signals = crate::signals::create_reload_signals()?;
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while signals.next().await.is_some() {
+                if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send("SIGHUP caught") {
+                    break;
+                }
+            }
+        });
+    }
+
+    if let Some(mut recv) = reload_recv {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while recv.changed().await.is_ok() {
+                if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send("reload requested") {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let mut current = file_entries(config_file).unwrap_or_default();
+    tokio::spawn(async move {
+        while let Some(reason) = rx.recv().await {
+            server_info!("{reason}, reloading the configuration");
+            reload(&handler, &load, &mut current);
+        }
+    });
+
+    Ok(())
+}
+
+fn reload<F>(handler: &SharedRequestHandler, load: &F, current: &mut BTreeMap<String, String>)
+where
+    F: Fn() -> Result<Settings>,
+{
+    match swap_handler(handler, load) {
+        Ok(entries) => {
+            let changes = diff(current, &entries);
+            if changes.is_empty() {
+                server_info!("config reload: no changes detected in the config file");
+            }
+            for (key, change) in changes {
+                if RESTART_REQUIRED.contains(&key.as_str()) {
+                    server_warn!("config reload: {key} {change} but requires a server restart");
+                } else {
+                    server_info!("config reload: {key} {change}");
+                }
+            }
+            *current = entries;
+            server_info!("configuration reloaded successfully");
+        }
+        Err(err) => {
+            tracing::error!("failed to reload the configuration, keeping the current one: {err:#}");
+        }
+    }
+}
+
+/// Builds a new request handler out of the loaded settings and replaces the current one.
+/// It returns the config file entries used to report the changes.
+fn swap_handler<F>(handler: &SharedRequestHandler, load: &F) -> Result<BTreeMap<String, String>>
+where
+    F: Fn() -> Result<Settings>,
+{
+    let settings = load()?;
+    let entries = file_entries(&settings.general.config_file)?;
+    let mut opts = build_handler_opts(&settings.general, settings.advanced)?;
+    let current = handler.get();
+
+    // The in-memory cache store is kept as it's only initialized on startup
+    #[cfg(feature = "experimental")]
+    {
+        opts.memory_cache = current.opts.memory_cache.clone();
+    }
+
+    // The admin listener is only started on startup as well
+    opts.admin_listener = current.opts.admin_listener;
+
+    handler.set(RequestHandler {
+        opts: Arc::new(opts),
+    });
+    Ok(entries)
+}
+
+/// Reads the config file options as a flat map of `section.key` entries.
+fn file_entries(config_file: &Path) -> Result<BTreeMap<String, String>> {
+    let mut entries = BTreeMap::new();
+    if !config_file.is_file() {
+        return Ok(entries);
+    }
+
+    let settings = FileSettings::read(config_file)?;
+    let value = toml::Value::try_from(&settings)
+        .with_context(|| "error serializing the configuration file options")?;
+    if let toml::Value::Table(sections) = value {
+        for (section, options) in sections {
+            if let toml::Value::Table(options) = options {
+                for (key, value) in options {
+                    entries.insert(format!("{section}.{key}"), value.to_string());
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the changed entries between two sets of config file options.
+/// Values of the `advanced` section are omitted since they are usually long lists.
+fn diff(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<(String, String)> {
+    let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let (old_value, new_value) = (old.get(key), new.get(key));
+            if old_value == new_value {
+                return None;
+            }
+            let change = if key.starts_with("advanced.") {
+                match (old_value, new_value) {
+                    (None, _) => "added".to_owned(),
+                    (_, None) => "removed".to_owned(),
+                    _ => "changed".to_owned(),
+                }
+            } else {
+                format!(
+                    "changed: {} -> {}",
+                    old_value.map_or("(unset)", |v| v.as_str()),
+                    new_value.map_or("(unset)", |v| v.as_str())
+                )
+            };
+            Some((key.to_owned(), change))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, swap_handler};
+    use crate::{
+        handler::{RequestHandler, RequestHandlerOpts},
+        service::SharedRequestHandler,
+        Settings,
+    };
+    use std::{collections::BTreeMap, sync::Arc};
+
+    fn entries(items: &[(&str, &str)]) -> BTreeMap<String, String> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_config_entries() {
+        let old = entries(&[
+            ("general.port", "80"),
+            ("general.etag", "\"weak\""),
+            ("advanced.headers", "[...]"),
+        ]);
+        let new = entries(&[
+            ("general.port", "8080"),
+            ("general.health", "true"),
+            ("general.etag", "\"weak\""),
+            ("advanced.headers", "[..]"),
+            ("advanced.redirects", "[...]"),
+        ]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                ("advanced.headers".to_owned(), "changed".to_owned()),
+                ("advanced.redirects".to_owned(), "added".to_owned()),
+                (
+                    "general.health".to_owned(),
+                    "changed: (unset) -> true".to_owned()
+                ),
+                ("general.port".to_owned(), "changed: 80 -> 8080".to_owned()),
+            ]
+        );
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn swap_handler_keeps_current_on_error() {
+        let handler = SharedRequestHandler::new(RequestHandler {
+            opts: Arc::new(RequestHandlerOpts::default()),
+        });
+        let current = handler.get();
+
+        let result = swap_handler(&handler, &|| bail!("invalid config"));
+        assert!(result.is_err());
+        assert!(Arc::ptr_eq(&current, &handler.get()));
+
+        let result = swap_handler(&handler, &|| {
+            let mut settings = Settings::get_unparsed(false)?;
+            settings.general.root = "docker/public".into();
+            settings.general.health = true;
+            Ok(settings)
+        });
+        assert!(result.is_ok());
+        assert!(!Arc::ptr_eq(&current, &handler.get()));
+        assert!(handler.get().opts.health);
+    }
+}
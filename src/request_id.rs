@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module assigning an ID to every request to correlate its log entries.
+//!
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Body, Request, Response};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::{handler::RequestHandlerOpts, Result};
+
+/// Maximum length of a request ID received from a trusted proxy.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    /// The ID of the request being handled by the current task.
+    static CURRENT: Option<RequestId>;
+}
+
+/// The ID of a request, available in the request extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(Arc<str>);
+
+impl RequestId {
+    /// Generates a new random request ID.
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string().into())
+    }
+
+    /// Returns the request ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Accepts a request ID received from a proxy only if it's safe to log and to echo.
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.:/+=@".contains(c));
+        valid.then(|| Self(value.into()))
+    }
+}
+
+/// Initializes the request ID feature.
+pub(crate) fn init(enabled: bool, header: &str, handler_opts: &mut RequestHandlerOpts) -> Result {
+    if enabled {
+        handler_opts.request_id_header = Some(
+            HeaderName::try_from(header)
+                .map_err(|_| anyhow!("invalid request ID header name \"{header}\""))?,
+        );
+    }
+    server_info!("request ID: enabled={enabled}, header={header}");
+    Ok(())
+}
+
+/// Assigns an ID to the incoming request, reusing the one received from a trusted proxy if valid.
+pub(crate) fn pre_process<T>(
+    opts: &RequestHandlerOpts,
+    req: &mut Request<T>,
+    remote_addr: Option<SocketAddr>,
+) -> Option<RequestId> {
+    let header = opts.request_id_header.as_ref()?;
+    // No proxy is trusted when the list is empty, like for the client IP
+    let trusted =
+        remote_addr.is_some_and(|addr| opts.trusted_proxies.contains(&addr.ip().to_canonical()));
+    let id = req
+        .headers()
+        .get(header)
+        .filter(|_| trusted)
+        .and_then(RequestId::from_header)
+        .unwrap_or_else(RequestId::generate);
+    req.extensions_mut().insert(id.clone());
+    Some(id)
+}
+
+/// Runs a future making the request ID available to the [`current`] function.
+pub(crate) fn scope<F: Future>(id: Option<RequestId>, fut: F) -> impl Future<Output = F::Output> {
+    CURRENT.scope(id, fut)
+}
+
+/// Returns the ID of the request being handled by the current task if any.
+pub(crate) fn current() -> Option<RequestId> {
+    CURRENT.try_with(|id| id.clone()).ok().flatten()
+}
+
+/// Echoes the request ID in the response headers.
+pub(crate) fn post_process(
+    opts: &RequestHandlerOpts,
+    id: Option<&RequestId>,
+    mut resp: Response<Body>,
+) -> Response<Body> {
+    if let (Some(header), Some(id)) = (&opts.request_id_header, id) {
+        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+            resp.headers_mut().insert(header.clone(), value);
+        }
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler_opts(trusted_proxies: &[&str]) -> RequestHandlerOpts {
+        RequestHandlerOpts {
+            request_id_header: Some(HeaderName::from_static("x-request-id")),
+            trusted_proxies: trusted_proxies
+                .iter()
+                .map(|ip| ip.parse().unwrap())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn request(id: &str) -> Request<Body> {
+        Request::get("/")
+            .header("x-request-id", id)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn reuses_ids_from_trusted_proxies() {
+        let opts = handler_opts(&["10.0.0.1"]);
+        let proxy = "10.0.0.1:4000".parse().ok();
+        let client = "10.0.0.2:4000".parse().ok();
+
+        let mut req = request("abc-123");
+        let id = pre_process(&opts, &mut req, proxy).unwrap();
+        assert_eq!(id.as_str(), "abc-123");
+        assert_eq!(req.extensions().get::<RequestId>(), Some(&id));
+
+        // Untrusted clients and invalid IDs get a new one
+        let id = pre_process(&opts, &mut request("abc-123"), client).unwrap();
+        assert_eq!(id.as_str().len(), 36);
+        let id = pre_process(&opts, &mut request("a b"), proxy).unwrap();
+        assert_ne!(id.as_str(), "a b");
+
+        // IPv4-mapped IPv6 addresses of trusted proxies are trusted too
+        let mapped_proxy = "[::ffff:10.0.0.1]:4000".parse().ok();
+        let id = pre_process(&opts, &mut request("abc-123"), mapped_proxy).unwrap();
+        assert_eq!(id.as_str(), "abc-123");
+
+        // No proxy is trusted by default
+        let id = pre_process(&handler_opts(&[]), &mut request("abc-123"), proxy).unwrap();
+        assert_ne!(id.as_str(), "abc-123");
+
+        let resp = post_process(&opts, Some(&id), Response::new(Body::empty()));
+        assert_eq!(resp.headers()["x-request-id"], id.as_str());
+
+        let opts = RequestHandlerOpts::default();
+        assert!(pre_process(&opts, &mut request("abc-123"), proxy).is_none());
+    }
+
+    #[tokio::test]
+    async fn current_request_id() {
+        assert_eq!(current(), None);
+        let id = RequestId::generate();
+        let current = scope(Some(id.clone()), async { current() }).await;
+        assert_eq!(current, Some(id));
+    }
+}
@@ -6,17 +6,27 @@
 //! Module to transition files into HTTP responses.
 //!
 
+use bytes::Bytes;
+use futures_util::Stream;
 use headers::{
-    AcceptRanges, ContentLength, ContentRange, ContentType, HeaderMapExt, LastModified, Range,
+    AcceptRanges, ContentLength, ContentRange, ContentType, ETag, HeaderMapExt, LastModified, Range,
 };
-use hyper::{Body, Response, StatusCode};
+use hyper::body::HttpBody;
+use hyper::{header, Body, Response, StatusCode};
+use mime_guess::mime::Mime;
+use std::collections::VecDeque;
 use std::fs::{File, Metadata};
 use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::ops::Bound;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::conditional_headers::{ConditionalBody, ConditionalHeaders};
-use crate::fs::stream::{optimal_buf_size, FileStream};
+use crate::etag::{self, ETagMode};
+use crate::fs::stream::{optimal_buf_size, FileStream, MultipartPart, MultipartStream};
 
 #[cfg(feature = "experimental")]
 use {
@@ -34,6 +44,7 @@ pub(crate) fn response_body(
     path: &PathBuf,
     meta: &Metadata,
     conditionals: ConditionalHeaders,
+    etag: Option<ETag>,
     #[cfg(feature = "experimental")] memory_cache: Option<&MemCacheOpts>,
 ) -> Result<Response<Body>, StatusCode> {
     let mut len = meta.len();
@@ -42,176 +53,366 @@ pub(crate) fn response_body(
     let modified = meta
         .modified()
         .ok()
-        .filter(|&t| t != std::time::UNIX_EPOCH)
+        .filter(|&t| t != UNIX_EPOCH)
         .map(LastModified::from);
 
-    match conditionals.check(modified) {
+    match conditionals.check(modified, etag.as_ref()) {
         ConditionalBody::NoBody(resp) => Ok(resp),
         ConditionalBody::WithBody(range) => {
             let buf_size = optimal_buf_size(meta);
 
-            bytes_range(range, len)
-                .map(|(start, end)| {
-                    match file.seek(SeekFrom::Start(start)) {
-                        Ok(_) => (),
-                        Err(err) => {
-                            tracing::error!("seek file from start error: {:?}", err);
-                            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-                        }
-                    };
-
-                    let sub_len = end - start;
-                    let reader = BufReader::new(file).take(sub_len);
-
-                    let mime = mime_guess::from_path(path).first_or_octet_stream();
-                    let content_type = ContentType::from(mime);
-
-                    // Add the file to the in-memory cache only under these conditions:
-                    // - if the feature is enabled and
-                    // - if the file size does not exceed the maximum permitted and
-                    // - if the file is not found in the cache store
-                    // TODO: make this a feature
-                    #[cfg(feature = "experimental")]
-                    let body = match memory_cache {
-                        // Cache the file only if does not exceed the max size
-                        Some(mem_cache_opts) if len <= mem_cache_opts.max_file_size => {
-                            match path.to_str() {
-                                Some(path_str) => {
-                                    let content_type = content_type.clone();
-                                    let file_path = path_str.to_owned();
-
-                                    let mem_buf = Some(BytesMut::with_capacity(len as usize));
-                                    let mem_opts = Some(MemFileTempOpts::new(
-                                        file_path,
-                                        content_type,
-                                        modified,
-                                    ));
-                                    tracing::debug!(
-                                        "preparing `{}` to be inserted in-memory cache store",
-                                        path_str,
-                                    );
-                                    Body::wrap_stream(MemCacheFileStream {
-                                        reader,
-                                        buf_size,
-                                        mem_opts,
-                                        mem_buf,
-                                    })
-                                }
-                                _ => Body::wrap_stream(FileStream { reader, buf_size }),
-                            }
+            let mime = mime_guess::from_path(path).first_or_octet_stream();
+            let content_type = ContentType::from(mime);
+
+            let (start, end) = match bytes_range(range, len) {
+                Ok(ranges) if ranges.len() > 1 => {
+                    let reader = BufReader::new(file);
+                    let mut resp =
+                        multipart_response(reader, buf_size, &ranges, len, &content_type)?;
+                    if let Some(last_modified) = modified {
+                        resp.headers_mut().typed_insert(last_modified);
+                    }
+                    if let Some(etag) = etag {
+                        resp.headers_mut().typed_insert(etag);
+                    }
+                    return Ok(resp);
+                }
+                Ok(ranges) => ranges[0],
+                Err(BadRangeError) => return Ok(range_not_satisfiable(len)),
+            };
+
+            match file.seek(SeekFrom::Start(start)) {
+                Ok(_) => (),
+                Err(err) => {
+                    tracing::error!("seek file from start error: {:?}", err);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            };
+
+            let sub_len = end - start;
+            let reader = BufReader::new(file).take(sub_len);
+
+            // Add the file to the in-memory cache only under these conditions:
+            // - if the feature is enabled and
+            // - if the file size does not exceed the maximum permitted and
+            // - if the file is not found in the cache store
+            // TODO: make this a feature
+            #[cfg(feature = "experimental")]
+            let body = match memory_cache {
+                // Cache the file only if does not exceed the max size
+                Some(mem_cache_opts) if len <= mem_cache_opts.max_file_size => {
+                    match path.to_str() {
+                        Some(path_str) => {
+                            let content_type = content_type.clone();
+                            let file_path = path_str.to_owned();
+
+                            let mem_buf = Some(BytesMut::with_capacity(len as usize));
+                            let mem_opts = Some(MemFileTempOpts::new(
+                                file_path,
+                                content_type,
+                                modified,
+                                etag.clone(),
+                            ));
+                            tracing::debug!(
+                                "preparing `{}` to be inserted in-memory cache store",
+                                path_str,
+                            );
+                            Body::wrap_stream(MemCacheFileStream {
+                                reader,
+                                buf_size,
+                                mem_opts,
+                                mem_buf,
+                            })
                         }
                         _ => Body::wrap_stream(FileStream { reader, buf_size }),
-                    };
-
-                    #[cfg(not(feature = "experimental"))]
-                    let body = Body::wrap_stream(FileStream { reader, buf_size });
-
-                    let mut resp = Response::new(body);
-
-                    if sub_len != len {
-                        *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
-                        resp.headers_mut().typed_insert(
-                            match ContentRange::bytes(start..end, len) {
-                                Ok(range) => range,
-                                Err(err) => {
-                                    tracing::error!("invalid content range error: {:?}", err);
-                                    let mut resp = Response::new(Body::empty());
-                                    *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-                                    resp.headers_mut()
-                                        .typed_insert(ContentRange::unsatisfied_bytes(len));
-                                    return Ok(resp);
-                                }
-                            },
-                        );
-
-                        len = sub_len;
                     }
+                }
+                _ => Body::wrap_stream(FileStream { reader, buf_size }),
+            };
 
-                    resp.headers_mut().typed_insert(ContentLength(len));
-                    resp.headers_mut().typed_insert(content_type);
-                    resp.headers_mut().typed_insert(AcceptRanges::bytes());
+            #[cfg(not(feature = "experimental"))]
+            let body = Body::wrap_stream(FileStream { reader, buf_size });
 
-                    if let Some(last_modified) = modified {
-                        resp.headers_mut().typed_insert(last_modified);
-                    }
+            let mut resp = Response::new(body);
+
+            if sub_len != len {
+                *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+                resp.headers_mut()
+                    .typed_insert(match ContentRange::bytes(start..end, len) {
+                        Ok(range) => range,
+                        Err(err) => {
+                            tracing::error!("invalid content range error: {:?}", err);
+                            return Ok(range_not_satisfiable(len));
+                        }
+                    });
+
+                len = sub_len;
+            }
 
-                    Ok(resp)
-                })
-                .unwrap_or_else(|BadRangeError| {
-                    // bad byte range
-                    let mut resp = Response::new(Body::empty());
-                    *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-                    resp.headers_mut()
-                        .typed_insert(ContentRange::unsatisfied_bytes(len));
-                    Ok(resp)
-                })
+            resp.headers_mut().typed_insert(ContentLength(len));
+            resp.headers_mut().typed_insert(content_type);
+            resp.headers_mut().typed_insert(AcceptRanges::bytes());
+
+            if let Some(last_modified) = modified {
+                resp.headers_mut().typed_insert(last_modified);
+            }
+            if let Some(etag) = etag {
+                resp.headers_mut().typed_insert(etag);
+            }
+
+            Ok(resp)
         }
     }
 }
 
+/// It computes the `ETag` of an opened file according to the given mode.
+/// Note that the strong mode reads the whole file unless its `ETag` is cached, so it rewinds it afterwards.
+pub(crate) async fn file_etag(
+    file: &File,
+    path: &Path,
+    meta: &Metadata,
+    mode: ETagMode,
+) -> Result<Option<ETag>, StatusCode> {
+    match mode {
+        ETagMode::Off => Ok(None),
+        ETagMode::Weak => Ok(etag::weak(meta)),
+        ETagMode::Strong => etag::strong(path, meta, file).await.map_err(|err| {
+            tracing::error!("unable to compute the file strong etag: {:?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }),
+    }
+}
+
+/// The maximum number of ranges accepted in a single `Range` request header.
+/// Requests exceeding it get the full representation instead.
+pub(crate) const MAX_RANGES: usize = 100;
+
 pub(crate) struct BadRangeError;
 
 /// It handles the `Range` header returning the corresponding start/end-range bytes
 /// or returns an error for bad ranges otherwise.
-pub(crate) fn bytes_range(range: Option<Range>, max_len: u64) -> Result<(u64, u64), BadRangeError> {
+///
+/// Unsatisfiable ranges are skipped while the remaining ones are sorted and
+/// overlapping or adjacent ones are coalesced.
+pub(crate) fn bytes_range(
+    range: Option<Range>,
+    max_len: u64,
+) -> Result<Vec<(u64, u64)>, BadRangeError> {
     let range = if let Some(range) = range {
         range
     } else {
-        return Ok((0, max_len));
+        return Ok(vec![(0, max_len)]);
     };
 
-    let resp = range
+    let count = range.iter().count();
+    if count > MAX_RANGES {
+        tracing::debug!(
+            "range request with {} ranges exceeds the maximum of {}, ignoring it",
+            count,
+            MAX_RANGES
+        );
+        return Ok(vec![(0, max_len)]);
+    }
+
+    let mut ranges = range
         .iter()
-        .map(|(start, end)| {
-            tracing::trace!("range request received, {:?}-{:?}-{}", start, end, max_len);
-
-            let (start, end) = match (start, end) {
-                (Bound::Unbounded, Bound::Unbounded) => (0, max_len),
-                (Bound::Included(a), Bound::Included(b)) => {
-                    // `start` can not be greater than `end`
-                    if a > b {
-                        return Err(BadRangeError);
-                    }
-                    // For the special case where b == the file size
-                    (a, if b == max_len { b } else { b + 1 })
-                }
-                (Bound::Included(a), Bound::Unbounded) => (a, max_len),
-                (Bound::Unbounded, Bound::Included(b)) => {
-                    if b > max_len {
-                        // `Range` request out of bounds, return only what's available
-                        tracing::trace!("unsatisfiable byte range: -{}/{}", b, max_len);
-                        tracing::trace!("returning only what's available: 0-{}", max_len);
-                        (0, max_len)
-                    } else {
-                        (max_len - b, max_len)
-                    }
-                }
-                _ => unreachable!(),
-            };
+        .filter_map(|(start, end)| single_range(start, end, max_len).ok())
+        .collect::<Vec<_>>();
 
-            if start < end && end <= max_len {
-                tracing::trace!("range request to return: {}-{}/{}", start, end, max_len);
-                return Ok((start, end));
-            }
+    // NOTE: default to `BadRangeError` in case of wrong `Range` bytes format
+    if ranges.is_empty() {
+        return Err(BadRangeError);
+    }
+
+    ranges.sort_unstable();
+    let mut coalesced: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match coalesced.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => coalesced.push((start, end)),
+        }
+    }
+
+    Ok(coalesced)
+}
 
-            tracing::trace!("unsatisfiable byte range: {}-{}/{}", start, end, max_len);
+/// It resolves a single range bounds into start/end-range bytes.
+fn single_range(
+    start: Bound<u64>,
+    end: Bound<u64>,
+    max_len: u64,
+) -> Result<(u64, u64), BadRangeError> {
+    tracing::trace!("range request received, {:?}-{:?}-{}", start, end, max_len);
 
-            if start < end && start <= max_len {
+    let (start, end) = match (start, end) {
+        (Bound::Unbounded, Bound::Unbounded) => (0, max_len),
+        (Bound::Included(a), Bound::Included(b)) => {
+            // `start` can not be greater than `end`
+            if a > b {
+                return Err(BadRangeError);
+            }
+            // For the special case where b == the file size
+            (a, if b == max_len { b } else { b + 1 })
+        }
+        (Bound::Included(a), Bound::Unbounded) => (a, max_len),
+        (Bound::Unbounded, Bound::Included(b)) => {
+            if b > max_len {
                 // `Range` request out of bounds, return only what's available
-                tracing::trace!(
-                    "returning only what's available: {}-{}/{}",
-                    start,
-                    max_len,
-                    max_len
-                );
-                return Ok((start, max_len));
+                tracing::trace!("unsatisfiable byte range: -{}/{}", b, max_len);
+                tracing::trace!("returning only what's available: 0-{}", max_len);
+                (0, max_len)
+            } else {
+                (max_len - b, max_len)
             }
+        }
+        _ => unreachable!(),
+    };
 
-            Err(BadRangeError)
-        })
-        .next()
-        // NOTE: default to `BadRangeError` in case of wrong `Range` bytes format
-        .unwrap_or(Err(BadRangeError));
+    if start < end && end <= max_len {
+        tracing::trace!("range request to return: {}-{}/{}", start, end, max_len);
+        return Ok((start, end));
+    }
+
+    tracing::trace!("unsatisfiable byte range: {}-{}/{}", start, end, max_len);
 
+    if start < end && start <= max_len {
+        // `Range` request out of bounds, return only what's available
+        tracing::trace!(
+            "returning only what's available: {}-{}/{}",
+            start,
+            max_len,
+            max_len
+        );
+        return Ok((start, max_len));
+    }
+
+    Err(BadRangeError)
+}
+
+/// It returns a `416 Range Not Satisfiable` response for the given length.
+pub(crate) fn range_not_satisfiable(len: u64) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+    resp.headers_mut()
+        .typed_insert(ContentRange::unsatisfied_bytes(len));
     resp
 }
+
+/// It creates a `206 Partial Content` response holding a `multipart/byteranges`
+/// body that streams every given range from the reader.
+pub(crate) fn multipart_response<R>(
+    reader: R,
+    buf_size: usize,
+    ranges: &[(u64, u64)],
+    len: u64,
+    content_type: &ContentType,
+) -> Result<Response<Body>, StatusCode>
+where
+    R: Read + Seek + Unpin + Send + 'static,
+{
+    let boundary = multipart_boundary();
+    let multipart_type = format!("multipart/byteranges; boundary={boundary}")
+        .parse::<Mime>()
+        .map_err(|err| {
+            tracing::error!("invalid multipart content type error: {:?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut body_len = 0;
+    let parts = ranges
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, end))| {
+            let delimiter = if i == 0 { "" } else { "\r\n" };
+            let header = format!(
+                "{delimiter}--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {}-{}/{len}\r\n\r\n",
+                start,
+                end - 1,
+            );
+            body_len += header.len() as u64 + (end - start);
+            MultipartPart {
+                header: Bytes::from(header),
+                start,
+                end,
+            }
+        })
+        .collect::<VecDeque<_>>();
+
+    let closing = Bytes::from(format!("\r\n--{boundary}--\r\n"));
+    body_len += closing.len() as u64;
+
+    let body = Body::wrap_stream(MultipartStream::new(reader, buf_size, parts, closing));
+    let mut resp = Response::new(body);
+    *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+    resp.headers_mut().typed_insert(ContentLength(body_len));
+    resp.headers_mut()
+        .typed_insert(ContentType::from(multipart_type));
+    resp.headers_mut().typed_insert(AcceptRanges::bytes());
+
+    Ok(resp)
+}
+
+/// It generates a boundary delimiter that is unique per response.
+fn multipart_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("sws-{nanos:016x}{count:08x}")
+}
+
+/// It calls `on_complete` with the number of body bytes sent once the response body
+/// was fully sent or dropped, e.g. when the client went away in the middle of it.
+pub(crate) fn on_body_complete(
+    mut resp: Response<Body>,
+    on_complete: impl FnOnce(u64) + Send + 'static,
+) -> Response<Body> {
+    if resp.body().is_end_stream() {
+        on_complete(0);
+        return resp;
+    }
+
+    // Keep the body length since wrapping the body loses its size hint
+    if let Some(len) = HttpBody::size_hint(resp.body()).exact() {
+        resp.headers_mut()
+            .entry(header::CONTENT_LENGTH)
+            .or_insert_with(|| len.into());
+    }
+
+    resp.map(|body| {
+        Body::wrap_stream(ObservedBody {
+            body,
+            bytes: 0,
+            on_complete: Some(Box::new(on_complete)),
+        })
+    })
+}
+
+/// A response body which counts the bytes sent and reports them when dropped.
+struct ObservedBody {
+    body: Body,
+    bytes: u64,
+    on_complete: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl Stream for ObservedBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.body).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            self.bytes += chunk.len() as u64;
+        }
+        poll
+    }
+}
+
+impl Drop for ObservedBody {
+    fn drop(&mut self) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(self.bytes);
+        }
+    }
+}
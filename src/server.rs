@@ -6,24 +6,36 @@
 //! Server module intended to construct a multi-threaded HTTP or HTTP/2 web server.
 //!
 
+use hyper::server::conn::AddrIncoming;
 use hyper::server::Server as HyperServer;
 use listenfd::ListenFd;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::future::Future;
 use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::sync::Arc;
 use tokio::sync::{watch::Receiver, Mutex};
+use tokio::task::JoinHandle;
 
 use crate::handler::{RequestHandler, RequestHandlerOpts};
 
-#[cfg(all(unix, feature = "experimental"))]
+#[cfg(feature = "metrics")]
 use crate::metrics;
 #[cfg(any(unix, windows))]
 use crate::signals;
+#[cfg(any(unix, feature = "http2"))]
+use std::path::PathBuf;
+#[cfg(unix)]
+use {
+    crate::unix_socket::{self, UnixIncoming},
+    std::os::unix::net::UnixListener,
+};
 
 #[cfg(feature = "http2")]
 use {
-    crate::tls::{TlsAcceptor, TlsConfigBuilder},
+    crate::mtls::ClientAuthMode,
+    crate::tls::{self, SharedTlsConfig, TlsAcceptor, TlsConfigBuilder},
     crate::{error, error_page, https_redirect},
-    hyper::server::conn::{AddrIncoming, AddrStream},
+    hyper::server::conn::AddrStream,
     hyper::service::{make_service_fn, service_fn},
 };
 
@@ -44,19 +56,29 @@ use crate::{compression, compression_static};
 #[cfg(feature = "basic-auth")]
 use crate::basic_auth;
 
+#[cfg(feature = "jwt-auth")]
+use crate::jwt_auth;
+
 #[cfg(feature = "experimental")]
 use crate::mem_cache;
 
+#[cfg(feature = "otlp")]
+use crate::otlp;
+
 use crate::{
-    control_headers, cors, health, helpers, log_addr, maintenance_mode, security_headers, Settings,
+    access_log, admin, control_headers, cors, etag, health, helpers, ip_access, log_addr,
+    maintenance_mode, reload, request_id, security_headers,
+    settings::{cli::General, Advanced, Listeners},
+    signed_urls, Settings,
 };
-use crate::{service::RouterService, Context, Result};
+use crate::{proxy_protocol::ProxyProtocolIncoming, service::RouterService, Context, Result};
 
 /// Define a multi-threaded HTTP or HTTP/2 web server.
 pub struct Server {
     opts: Settings,
     worker_threads: usize,
     max_blocking_threads: usize,
+    reload_recv: Option<Receiver<()>>,
 }
 
 impl Server {
@@ -78,9 +100,19 @@ impl Server {
             opts,
             worker_threads,
             max_blocking_threads,
+            reload_recv: None,
         })
     }
 
+    /// Sets a [`reload_recv`] receiver to reload the configuration file on demand
+    /// as a complement to the `SIGHUP` signal handling on Unix.
+    ///
+    /// [`reload_recv`]: <https://docs.rs/tokio/latest/tokio/sync/watch/struct.Receiver.html>
+    pub fn with_reload(mut self, reload_recv: Receiver<()>) -> Self {
+        self.reload_recv = Some(reload_recv);
+        self
+    }
+
     /// Run the multi-threaded `Server` as standalone.
     /// This is a top-level function of [run_server_on_rt](#method.run_server_on_rt).
     ///
@@ -129,6 +161,10 @@ impl Server {
                 }
             });
 
+        // Export the spans of the last requests before exiting
+        #[cfg(feature = "otlp")]
+        otlp::shutdown();
+
         Ok(())
     }
 
@@ -139,17 +175,23 @@ impl Server {
         F: FnOnce(),
     {
         tracing::trace!("starting web server");
+        health::ServerState::Starting.set();
         server_info!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
         // Config "general" options
         let general = self.opts.general;
         // Config-file "advanced" options
-        let advanced_opts = self.opts.advanced;
+        let mut advanced_opts = self.opts.advanced;
 
         server_info!("log level: {}", general.log_level);
+        server_info!(
+            "log format: {:?}, filter: \"{}\"",
+            general.log_format,
+            general.log_filter
+        );
 
         // Config file option
-        let config_file = general.config_file;
+        let config_file = general.config_file.clone();
         if config_file.is_file() {
             server_info!("config file used: {}", config_file.display());
         } else {
@@ -159,32 +201,25 @@ impl Server {
             );
         }
 
-        // Determine TCP listener either file descriptor or TCP socket
-        let (tcp_listener, addr_str);
-        match general.fd {
-            Some(fd) => {
-                addr_str = format!("@FD({fd})");
-                tcp_listener = ListenFd::from_env()
-                    .take_tcp_listener(fd)?
-                    .with_context(|| "failed to convert inherited 'fd' into a 'tcp' listener")?;
-                server_info!(
-                    "converted inherited file descriptor {} to a 'tcp' listener",
-                    fd
-                );
-            }
-            None => {
-                let ip = general
-                    .host
-                    .parse::<IpAddr>()
-                    .with_context(|| format!("failed to parse {} address", general.host))?;
-                let addr = SocketAddr::from((ip, general.port));
-                tcp_listener = TcpListener::bind(addr)
-                    .with_context(|| format!("failed to bind to {addr} address"))?;
-                addr_str = addr.to_string();
-                server_info!("server bound to tcp socket {}", addr_str);
-            }
+        // Determine the listeners, the main one out of the general options plus the additional ones
+        let mut listeners = vec![main_listener(&general)];
+        if let Some(entries) = advanced_opts
+            .as_mut()
+            .and_then(|advanced| advanced.listeners.take())
+        {
+            listeners.extend(entries);
         }
 
+        // Bind all the listeners upfront so that the server doesn't start partially.
+        // The inherited file descriptors are read once since the environment is cleared afterwards.
+        let mut listenfd = ListenFd::from_env();
+        let mut bound_listeners = Vec::with_capacity(listeners.len());
+        for listener in listeners {
+            let (socket, addr_str) = bind_listener(&listener, &mut listenfd)?;
+            bound_listeners.push((listener, socket, addr_str));
+        }
+        let admin_listener = admin::bind(&general)?;
+
         // Number of worker threads option
         let threads = self.worker_threads;
         server_info!("runtime worker threads: {}", threads);
@@ -195,192 +230,214 @@ impl Server {
             general.max_blocking_threads
         );
 
-        // Check for a valid root directory
-        let root_dir = helpers::get_valid_dirpath(&general.root)
-            .with_context(|| "root directory was not found or inaccessible")?;
-
-        // Custom HTML error page files
-        // NOTE: in the case of relative paths, they're joined to the root directory
-        let mut page404 = general.page404;
-        if page404.is_relative() && !page404.starts_with(&root_dir) {
-            page404 = root_dir.join(page404);
-        }
-        if !page404.is_file() {
-            tracing::debug!(
-                "404 file path not found or not a regular file: {}",
-                page404.display()
-            );
-        }
-        let mut page50x = general.page50x;
-        if page50x.is_relative() && !page50x.starts_with(&root_dir) {
-            page50x = root_dir.join(page50x);
-        }
-        if !page50x.is_file() {
-            tracing::debug!(
-                "50x file path not found or not a regular file: {}",
-                page50x.display()
-            );
-        }
-
-        // Log remote address option
-        let log_remote_address = general.log_remote_address;
-
-        // Log the X-Forwarded-For header.
-        let log_forwarded_for = general.log_forwarded_for;
-
-        // Trusted IPs for remote addresses.
-        let trusted_proxies = general.trusted_proxies;
-
-        // Log redirect trailing slash option
-        let redirect_trailing_slash = general.redirect_trailing_slash;
-        server_info!(
-            "redirect trailing slash: enabled={}",
-            redirect_trailing_slash
-        );
-
-        // Ignore hidden files option
-        let ignore_hidden_files = general.ignore_hidden_files;
-        server_info!("ignore hidden files: enabled={}", ignore_hidden_files);
-
-        // Disable symlinks option
-        let disable_symlinks = general.disable_symlinks;
-        server_info!("disable symlinks: enabled={}", disable_symlinks);
-
         // Grace period option
         let grace_period = general.grace_period;
         server_info!("grace period before graceful shutdown: {}s", grace_period);
 
-        // Index files option
-        let index_files = general
-            .index_files
-            .split(',')
-            .map(|s| s.trim().to_owned())
-            .collect::<Vec<_>>();
-        if index_files.is_empty() {
-            bail!("index files list is empty, provide at least one index file")
-        }
-        server_info!("index files: {}", general.index_files);
-
         // Request handler options, some settings will be filled in by modules
-        let mut handler_opts = RequestHandlerOpts {
-            root_dir,
-            page404: page404.clone(),
-            page50x: page50x.clone(),
-            log_remote_address,
-            log_forwarded_for,
-            trusted_proxies,
-            redirect_trailing_slash,
-            ignore_hidden_files,
-            disable_symlinks,
-            index_files,
-            advanced_opts,
-            ..Default::default()
-        };
-
-        // Directory listing options
-        #[cfg(feature = "directory-listing")]
-        directory_listing::init(
-            general.directory_listing,
-            general.directory_listing_order,
-            general.directory_listing_format,
-            &mut handler_opts,
-        );
-
-        // Fallback page option
-        #[cfg(feature = "fallback-page")]
-        fallback_page::init(&general.page_fallback, &mut handler_opts);
-
-        // Health endpoint option
-        health::init(general.health, &mut handler_opts);
-
-        // Log remote address option
-        log_addr::init(general.log_remote_address, &mut handler_opts);
-
-        // Metrics endpoint option (experimental)
-        #[cfg(all(unix, feature = "experimental"))]
-        metrics::init(general.experimental_metrics, &mut handler_opts);
-
-        // CORS option
-        cors::init(
-            &general.cors_allow_origins,
-            &general.cors_allow_headers,
-            &general.cors_expose_headers,
-            &mut handler_opts,
-        );
-
-        // `Basic` HTTP Authentication Schema option
-        #[cfg(feature = "basic-auth")]
-        basic_auth::init(&general.basic_auth, &mut handler_opts);
-
-        // Maintenance mode option
-        maintenance_mode::init(
-            general.maintenance_mode,
-            general.maintenance_mode_status,
-            general.maintenance_mode_file,
-            &mut handler_opts,
-        );
-
-        // Check pre-compressed files based on the `Accept-Encoding` header
-        #[cfg(any(
-            feature = "compression",
-            feature = "compression-deflate",
-            feature = "compression-gzip",
-            feature = "compression-brotli",
-            feature = "compression-zstd",
-        ))]
-        compression_static::init(general.compression_static, &mut handler_opts);
-
-        // Auto compression based on the `Accept-Encoding` header
-        #[cfg(any(
-            feature = "compression",
-            feature = "compression-deflate",
-            feature = "compression-gzip",
-            feature = "compression-brotli",
-            feature = "compression-zstd",
-        ))]
-        compression::init(
-            general.compression,
-            general.compression_level,
-            &mut handler_opts,
-        );
-
-        // Cache control headers option
-        control_headers::init(general.cache_control_headers, &mut handler_opts);
-
-        // Security Headers option
-        security_headers::init(general.security_headers, &mut handler_opts);
+        let handler_opts = build_handler_opts(&general, advanced_opts)?;
 
         // In-Memory cache option
+        // NOTE: the cache store is initialized once so it's not part of the config reloads
         #[cfg(feature = "experimental")]
-        mem_cache::cache::init(&mut handler_opts)?;
+        let handler_opts = {
+            let mut handler_opts = handler_opts;
+            mem_cache::cache::init(&mut handler_opts)?;
+            handler_opts
+        };
+
+        // Custom HTML error pages used by the HTTP to HTTPS redirect server
+        #[cfg(feature = "http2")]
+        let (page404, page50x) = (handler_opts.page404.clone(), handler_opts.page50x.clone());
+
+        // TLS certificates of virtual hosts selected via SNI
+        #[cfg(feature = "http2")]
+        let vhosts_tls_certs = handler_opts
+            .advanced_opts
+            .as_ref()
+            .and_then(|advanced| advanced.virtual_hosts.as_ref())
+            .map(|vhosts| {
+                vhosts
+                    .iter()
+                    .filter_map(|vhost| {
+                        let (cert, key) = vhost.tls_cert_key.clone()?;
+                        Some((vhost.host.clone(), cert, key))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
 
         // Create a service router for Hyper
         let router_service = RouterService::new(RequestHandler {
             opts: Arc::from(handler_opts),
         });
 
-        #[cfg(windows)]
-        let (sender, receiver) = tokio::sync::watch::channel(());
+        // Configuration file reload on `SIGHUP` (Unix) or on demand
+        if config_file.is_file() {
+            reload::spawn_reloader(
+                router_service.shared_handler(),
+                || Settings::get(false),
+                &config_file,
+                self.reload_recv,
+            )
+            .with_context(|| "failed to initialize the configuration reloading")?;
+        }
 
-        // Windows ctrl+c listening
-        #[cfg(windows)]
-        let ctrlc_task = tokio::spawn(async move {
-            if !general.windows_service {
-                server_info!("installing graceful shutdown ctrl+c signal handler");
-                tokio::signal::ctrl_c()
-                    .await
-                    .expect("failed to install ctrl+c signal handler");
-                server_info!("installing graceful shutdown ctrl+c signal handler");
-                let _ = sender.send(());
+        // HTTP/2 and TLS options of the main listener, the latter follows the former if not specified
+        #[cfg(feature = "http2")]
+        {
+            let main_listener = &bound_listeners[0].0;
+            server_info!("http2: enabled={}", main_listener.http2);
+            server_info!("tls: enabled={}", main_listener.tls);
+            if general.https_redirect && !main_listener.tls {
+                bail!("https redirect requires tls to be enabled")
             }
-        });
+            if !vhosts_tls_certs.is_empty() && !bound_listeners.iter().any(|(l, _, _)| l.tls) {
+                tracing::warn!("virtual host tls certificates are ignored because tls is disabled");
+            }
+            for (listener, socket, addr_str) in &bound_listeners {
+                if listener.tls {
+                    server_info!(
+                        "tls client auth on {}: mode={:?}",
+                        addr_str,
+                        listener.tls_client_auth
+                    );
+                }
+                if listener.tls_client_auth != ClientAuthMode::None {
+                    if !listener.tls {
+                        bail!("tls client auth requires tls to be enabled")
+                    }
+                    if listener.tls_client_ca.is_none() {
+                        bail!("tls client auth requires a client CA certificates file")
+                    }
+                }
+                #[cfg(unix)]
+                if listener.tls && matches!(socket, Listener::Unix(..)) {
+                    bail!("tls is not supported on unix domain sockets")
+                }
+                #[cfg(not(unix))]
+                let _ = socket;
+            }
+        }
+
+        // PROXY protocol options
+        for (listener, socket, addr_str) in &bound_listeners {
+            if !listener.proxy_protocol {
+                continue;
+            }
+            server_info!(
+                "proxy protocol on {}: enabled=true, trusted cidrs={:?}",
+                addr_str,
+                listener.proxy_protocol_trusted_cidrs
+            );
+            if listener.proxy_protocol_trusted_cidrs.is_empty() {
+                bail!("proxy protocol requires at least one trusted CIDR")
+            }
+            #[cfg(unix)]
+            if matches!(socket, Listener::Unix(..)) {
+                bail!("proxy protocol is not supported on unix domain sockets")
+            }
+            #[cfg(not(unix))]
+            let _ = socket;
+        }
+
+        // Graceful shutdown of all the servers together
+        let shutdown_recv = spawn_shutdown_watcher(
+            _cancel_recv,
+            grace_period,
+            #[cfg(windows)]
+            general.windows_service,
+        )?;
 
-        // Run the corresponding HTTP Server asynchronously with its given options
+        let mut server_tasks = Vec::with_capacity(bound_listeners.len() + 1);
+        #[cfg(unix)]
+        let mut socket_files = Vec::new();
+
+        // Run the corresponding HTTP Server of every listener asynchronously with its given options
+        for (listener, socket, addr_str) in bound_listeners {
+            #[cfg(feature = "http2")]
+            let http2 = listener.http2;
+            #[cfg(feature = "http2")]
+            let proto = if http2 { "http1/h2c" } else { "http1" };
+            #[cfg(not(feature = "http2"))]
+            let proto = "http1";
+
+            let router_service = router_service.clone();
+            let span = tracing::info_span!("Server::start_server", ?addr_str, ?threads);
+
+            match socket {
+                // Unix domain socket listener (HTTP/1 and optionally HTTP/2 over cleartext only)
+                #[cfg(unix)]
+                Listener::Unix(unix_listener, socket_path) => {
+                    let unix_server = HyperServer::builder(UnixIncoming::from_std(unix_listener)?);
+                    #[cfg(feature = "http2")]
+                    let unix_server = unix_server.http1_only(!http2);
+                    let unix_server = unix_server
+                        .serve(router_service)
+                        .with_graceful_shutdown(wait_for_shutdown(shutdown_recv.clone()));
+
+                    server_info!(
+                        parent: span,
+                        "{} server is listening on {}",
+                        proto,
+                        addr_str
+                    );
+
+                    server_tasks.push(spawn_server(unix_server, addr_str));
+                    socket_files.extend(socket_path);
+                }
+                // HTTPS (HTTP/1.1 and optionally HTTP/2 over TLS)
+                #[cfg(feature = "http2")]
+                Listener::Tcp(tcp_listener) if listener.tls => {
+                    let incoming = tcp_incoming(tcp_listener, &listener)?;
+                    let tls_config =
+                        tls_config(&listener, &vhosts_tls_certs, general.tls_watch_interval)?;
+
+                    let http2_server =
+                        HyperServer::builder(TlsAcceptor::with_shared_config(tls_config, incoming))
+                            .http1_only(!http2)
+                            .serve(router_service)
+                            .with_graceful_shutdown(wait_for_shutdown(shutdown_recv.clone()));
+
+                    server_info!(
+                        parent: span,
+                        "{} server is listening on https://{}",
+                        if http2 { "http2" } else { "http1" },
+                        addr_str
+                    );
+
+                    server_tasks.push(spawn_server(http2_server, addr_str));
+                }
+                // HTTP/1 and optionally HTTP/2 over cleartext (h2c)
+                Listener::Tcp(tcp_listener) => {
+                    let incoming = tcp_incoming(tcp_listener, &listener)?;
+                    let http1_server = HyperServer::builder(incoming);
+
+                    // Restrict connections to HTTP/1 unless HTTP/2 is enabled, in which case
+                    // HTTP/2 is served over cleartext (h2c) with prior knowledge as well
+                    #[cfg(feature = "http2")]
+                    let http1_server = http1_server.http1_only(!http2);
+
+                    let http1_server = http1_server
+                        .serve(router_service)
+                        .with_graceful_shutdown(wait_for_shutdown(shutdown_recv.clone()));
+
+                    server_info!(
+                        parent: span,
+                        "{} server is listening on http://{}",
+                        proto,
+                        addr_str
+                    );
+
+                    server_tasks.push(spawn_server(http1_server, addr_str));
+                }
+            }
+        }
+
+        // HTTP to HTTPS redirect server
         #[cfg(feature = "http2")]
-        if general.http2 {
-            // HTTP to HTTPS redirect option
-            let https_redirect = general.https_redirect;
-            server_info!("http to https redirect: enabled={}", https_redirect);
+        if general.https_redirect {
+            server_info!("http to https redirect: enabled=true");
             server_info!(
                 "http to https redirect host: {}",
                 general.https_redirect_host
@@ -394,267 +451,626 @@ impl Server {
                 general.https_redirect_from_hosts
             );
 
-            // HTTP/2 + TLS
+            let ip = general
+                .host
+                .parse::<IpAddr>()
+                .with_context(|| format!("failed to parse {} address", general.host))?;
+            let addr = SocketAddr::from((ip, general.https_redirect_from_port));
+            let tcp_listener = TcpListener::bind(addr)
+                .with_context(|| format!("failed to bind to {addr} address"))?;
+            server_info!(
+                parent: tracing::info_span!("Server::start_server", ?addr, ?threads),
+                "http1 redirect server is listening on http://{}",
+                addr
+            );
             tcp_listener
                 .set_nonblocking(true)
                 .with_context(|| "failed to set TCP non-blocking mode")?;
-            let listener = tokio::net::TcpListener::from_std(tcp_listener)
-                .with_context(|| "failed to create tokio::net::TcpListener")?;
-            let mut incoming = AddrIncoming::from_listener(listener).with_context(|| {
-                "failed to create an AddrIncoming from the current tokio::net::TcpListener"
-            })?;
-            incoming.set_nodelay(true);
-
-            let http2_tls_cert = match general.http2_tls_cert {
-                Some(v) => v,
-                _ => bail!("failed to initialize TLS because cert file missing"),
-            };
-            let http2_tls_key = match general.http2_tls_key {
-                Some(v) => v,
-                _ => bail!("failed to initialize TLS because key file missing"),
-            };
-
-            let tls = TlsConfigBuilder::new()
-                .cert_path(&http2_tls_cert)
-                .key_path(&http2_tls_key)
-                .build()
-                .with_context(|| {
-                    "failed to initialize TLS probably because invalid cert or key file"
-                })?;
-
-            #[cfg(unix)]
-            let signals = signals::create_signals()
-                .with_context(|| "failed to register termination signals")?;
-            #[cfg(unix)]
-            let handle = signals.handle();
-
-            let http2_server =
-                HyperServer::builder(TlsAcceptor::new(tls, incoming)).serve(router_service);
 
-            #[cfg(unix)]
-            let http2_cancel_recv = Arc::new(Mutex::new(_cancel_recv));
-            #[cfg(unix)]
-            let redirect_cancel_recv = http2_cancel_recv.clone();
-
-            #[cfg(unix)]
-            let http2_server = http2_server.with_graceful_shutdown(signals::wait_for_signals(
-                signals,
-                grace_period,
-                http2_cancel_recv,
-            ));
+            // Allowed redirect hosts
+            let redirect_allowed_hosts = general
+                .https_redirect_from_hosts
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .collect::<Vec<_>>();
+            if redirect_allowed_hosts.is_empty() {
+                bail!("https redirect allowed hosts is empty, provide at least one host or IP")
+            }
 
-            #[cfg(windows)]
-            let http2_cancel_recv = Arc::new(Mutex::new(_cancel_recv));
-            #[cfg(windows)]
-            let redirect_cancel_recv = http2_cancel_recv.clone();
+            // Redirect options
+            let redirect_opts = Arc::new(https_redirect::RedirectOpts {
+                https_hostname: general.https_redirect_host,
+                https_port: general.port,
+                allowed_hosts: redirect_allowed_hosts,
+            });
 
-            #[cfg(windows)]
-            let http2_ctrlc_recv = Arc::new(Mutex::new(Some(receiver)));
-            #[cfg(windows)]
-            let redirect_ctrlc_recv = http2_ctrlc_recv.clone();
+            let server_redirect = HyperServer::from_tcp(tcp_listener)?
+                .tcp_nodelay(true)
+                .serve(make_service_fn(move |_: &AddrStream| {
+                    let redirect_opts = redirect_opts.clone();
+                    let page404 = page404.clone();
+                    let page50x = page50x.clone();
+                    async move {
+                        Ok::<_, error::Error>(service_fn(move |req| {
+                            let redirect_opts = redirect_opts.clone();
+                            let page404 = page404.clone();
+                            let page50x = page50x.clone();
+                            async move {
+                                let uri = req.uri();
+                                let method = req.method();
+                                match https_redirect::redirect_to_https(&req, redirect_opts) {
+                                    Ok(resp) => Ok(resp),
+                                    Err(status) => error_page::error_response(
+                                        uri, method, &status, &page404, &page50x,
+                                    ),
+                                }
+                            }
+                        }))
+                    }
+                }))
+                .with_graceful_shutdown(wait_for_shutdown(shutdown_recv.clone()));
 
-            #[cfg(windows)]
-            let http2_server = http2_server.with_graceful_shutdown(async move {
-                if general.windows_service {
-                    signals::wait_for_ctrl_c(http2_cancel_recv, grace_period).await;
-                } else {
-                    signals::wait_for_ctrl_c(http2_ctrlc_recv, grace_period).await;
-                }
-            });
+            server_tasks.push(spawn_server(server_redirect, addr.to_string()));
+        }
 
+        // Admin server for the health, readiness and metrics endpoints
+        if let Some((tcp_listener, admin_opts)) = admin_listener {
+            let addr = tcp_listener.local_addr()?;
+            let admin_server = admin::serve(
+                tcp_listener,
+                admin_opts,
+                router_service.shared_handler(),
+                wait_for_shutdown(shutdown_recv.clone()),
+            )?;
             server_info!(
-                parent: tracing::info_span!("Server::start_server", ?addr_str, ?threads),
-                "http2 server is listening on https://{}",
-                addr_str
+                parent: tracing::info_span!("Server::start_server", ?addr, ?threads),
+                "http1 admin server is listening on http://{}",
+                addr
             );
+            server_tasks.push(spawn_server(admin_server, addr.to_string()));
+        }
 
-            // HTTP to HTTPS redirect server
-            if general.https_redirect {
-                let ip = general
-                    .host
-                    .parse::<IpAddr>()
-                    .with_context(|| format!("failed to parse {} address", general.host))?;
-                let addr = SocketAddr::from((ip, general.https_redirect_from_port));
-                let tcp_listener = TcpListener::bind(addr)
-                    .with_context(|| format!("failed to bind to {addr} address"))?;
-                server_info!(
-                    parent: tracing::info_span!("Server::start_server", ?addr, ?threads),
-                    "http1 redirect server is listening on http://{}",
-                    addr
-                );
-                tcp_listener
-                    .set_nonblocking(true)
-                    .with_context(|| "failed to set TCP non-blocking mode")?;
+        // Report the server as ready once all the listeners are running
+        health::ServerState::Serving.set();
 
-                #[cfg(unix)]
-                let redirect_signals = signals::create_signals()
-                    .with_context(|| "failed to register termination signals")?;
-                #[cfg(unix)]
-                let redirect_handle = redirect_signals.handle();
-
-                // Allowed redirect hosts
-                let redirect_allowed_hosts = general
-                    .https_redirect_from_hosts
-                    .split(',')
-                    .map(|s| s.trim().to_owned())
-                    .collect::<Vec<_>>();
-                if redirect_allowed_hosts.is_empty() {
-                    bail!("https redirect allowed hosts is empty, provide at least one host or IP")
-                }
+        if server_tasks.len() > 1 {
+            server_info!("press ctrl+c to shut down the servers");
+        } else {
+            server_info!("press ctrl+c to shut down the server");
+        }
 
-                // Redirect options
-                let redirect_opts = Arc::new(https_redirect::RedirectOpts {
-                    https_hostname: general.https_redirect_host,
-                    https_port: general.port,
-                    allowed_hosts: redirect_allowed_hosts,
-                });
-
-                let server_redirect = HyperServer::from_tcp(tcp_listener)
-                    .unwrap()
-                    .tcp_nodelay(true)
-                    .serve(make_service_fn(move |_: &AddrStream| {
-                        let redirect_opts = redirect_opts.clone();
-                        let page404 = page404.clone();
-                        let page50x = page50x.clone();
-                        async move {
-                            Ok::<_, error::Error>(service_fn(move |req| {
-                                let redirect_opts = redirect_opts.clone();
-                                let page404 = page404.clone();
-                                let page50x = page50x.clone();
-                                async move {
-                                    let uri = req.uri();
-                                    let method = req.method();
-                                    match https_redirect::redirect_to_https(&req, redirect_opts) {
-                                        Ok(resp) => Ok(resp),
-                                        Err(status) => error_page::error_response(
-                                            uri, method, &status, &page404, &page50x,
-                                        ),
-                                    }
-                                }
-                            }))
-                        }
-                    }));
+        for server_task in server_tasks {
+            server_task.await?;
+        }
 
-                #[cfg(unix)]
-                let server_redirect = server_redirect.with_graceful_shutdown(
-                    signals::wait_for_signals(redirect_signals, grace_period, redirect_cancel_recv),
-                );
-                #[cfg(windows)]
-                let server_redirect = server_redirect.with_graceful_shutdown(async move {
-                    if general.windows_service {
-                        signals::wait_for_ctrl_c(redirect_cancel_recv, grace_period).await;
-                    } else {
-                        signals::wait_for_ctrl_c(redirect_ctrlc_recv, grace_period).await;
-                    }
-                });
+        // Clean up the socket files bound by the server
+        #[cfg(unix)]
+        for path in socket_files {
+            if let Err(err) = std::fs::remove_file(&path) {
+                tracing::debug!("unable to remove unix socket {}: {}", path.display(), err);
+            }
+        }
 
-                // HTTP/2 server task
-                let server_task = tokio::spawn(async move {
-                    if let Err(err) = http2_server.await {
-                        tracing::error!("http2 server failed to start up: {:?}", err);
-                        std::process::exit(1)
-                    }
-                });
+        #[cfg(windows)]
+        _cancel_fn();
 
-                // HTTP/1 redirect server task
-                let redirect_server_task = tokio::spawn(async move {
-                    if let Err(err) = server_redirect.await {
-                        tracing::error!("http1 redirect server failed to start up: {:?}", err);
-                        std::process::exit(1)
-                    }
-                });
+        server_warn!("termination signal caught, shutting down the server execution");
+        Ok(())
+    }
+}
 
-                server_info!("press ctrl+c to shut down the servers");
+/// Spawns a task which waits for the termination signals (or for the `cancel_recv` notification)
+/// and then notifies all the servers to shut down gracefully.
+fn spawn_shutdown_watcher(
+    cancel_recv: Option<Receiver<()>>,
+    grace_period: u8,
+    #[cfg(windows)] windows_service: bool,
+) -> Result<Receiver<()>> {
+    let (sender, receiver) = tokio::sync::watch::channel(());
 
-                #[cfg(windows)]
-                tokio::try_join!(ctrlc_task, server_task, redirect_server_task)?;
-                #[cfg(unix)]
-                tokio::try_join!(server_task, redirect_server_task)?;
+    #[cfg(unix)]
+    {
+        let signals =
+            signals::create_signals().with_context(|| "failed to register termination signals")?;
+        let handle = signals.handle();
+        tokio::spawn(async move {
+            signals::wait_for_signals(signals, grace_period, Arc::new(Mutex::new(cancel_recv)))
+                .await;
+            handle.close();
+            let _ = sender.send(());
+        });
+    }
 
-                #[cfg(unix)]
-                redirect_handle.close();
-            } else {
-                server_info!("press ctrl+c to shut down the server");
-                http2_server.await?;
-            }
+    #[cfg(windows)]
+    tokio::spawn(async move {
+        let cancel_recv = if windows_service {
+            cancel_recv
+        } else {
+            // Windows ctrl+c listening
+            let (ctrlc_sender, ctrlc_receiver) = tokio::sync::watch::channel(());
+            server_info!("installing graceful shutdown ctrl+c signal handler");
+            tokio::spawn(async move {
+                tokio::signal::ctrl_c()
+                    .await
+                    .expect("failed to install ctrl+c signal handler");
+                let _ = ctrlc_sender.send(());
+            });
+            Some(ctrlc_receiver)
+        };
+        signals::wait_for_ctrl_c(Arc::new(Mutex::new(cancel_recv)), grace_period).await;
+        let _ = sender.send(());
+    });
 
-            #[cfg(unix)]
-            handle.close();
+    Ok(receiver)
+}
 
-            #[cfg(windows)]
-            _cancel_fn();
+/// Resolves once the servers are notified to shut down.
+async fn wait_for_shutdown(mut shutdown_recv: Receiver<()>) {
+    shutdown_recv.changed().await.ok();
+}
 
-            server_warn!("termination signal caught, shutting down the server execution");
-            return Ok(());
+/// Runs a server on its own task, the process exits if the server fails.
+fn spawn_server<F>(server: F, addr_str: String) -> JoinHandle<()>
+where
+    F: Future<Output = hyper::Result<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            tracing::error!("server on {} failed to start up: {:?}", addr_str, err);
+            std::process::exit(1)
         }
+    })
+}
 
-        // HTTP/1
+/// Builds the shared TLS configuration of a listener and watches its files for changes.
+#[cfg(feature = "http2")]
+fn tls_config(
+    listener: &Listeners,
+    vhosts_tls_certs: &[(String, PathBuf, PathBuf)],
+    tls_watch_interval: u64,
+) -> Result<SharedTlsConfig> {
+    let tls_cert = match listener.tls_cert.clone() {
+        Some(v) => v,
+        _ => bail!("failed to initialize TLS because cert file missing"),
+    };
+    let tls_key = match listener.tls_key.clone() {
+        Some(v) => v,
+        _ => bail!("failed to initialize TLS because key file missing"),
+    };
+
+    // ALPN protocols to negotiate
+    let alpn_protocols: &'static [&'static str] = if listener.http2 {
+        &["h2", "http/1.1"]
+    } else {
+        &["http/1.1"]
+    };
+
+    for (host, _, _) in vhosts_tls_certs {
+        server_info!("tls certificate for virtual host: {}", host);
+    }
 
-        #[cfg(unix)]
-        let signals =
-            signals::create_signals().with_context(|| "failed to register termination signals")?;
-        #[cfg(unix)]
-        let handle = signals.handle();
+    // Files to watch for changes in order to reload the certificates
+    let mut tls_files = vec![tls_cert.clone(), tls_key.clone()];
+    for (_, cert, key) in vhosts_tls_certs {
+        tls_files.push(cert.clone());
+        tls_files.push(key.clone());
+    }
+    let tls_client_auth = listener.tls_client_auth;
+    let tls_client_ca = listener.tls_client_ca.clone();
+    if let Some(ca) = &tls_client_ca {
+        tls_files.push(ca.clone());
+    }
 
-        tcp_listener
-            .set_nonblocking(true)
-            .with_context(|| "failed to set TCP non-blocking mode")?;
+    let vhosts_tls_certs = vhosts_tls_certs.to_vec();
+    let build_tls_config = move || {
+        let mut tls_builder = TlsConfigBuilder::new()
+            .cert_path(&tls_cert)
+            .key_path(&tls_key)
+            .alpn_protocols(alpn_protocols);
+        for (host, cert, key) in &vhosts_tls_certs {
+            tls_builder = tls_builder.sni_cert_key_path(host, cert, key);
+        }
+        if let Some(ca) = &tls_client_ca {
+            tls_builder = tls_builder.client_auth(tls_client_auth).client_ca_path(ca);
+        }
+        tls_builder.build()
+    };
+
+    let tls_config =
+        SharedTlsConfig::new(build_tls_config().with_context(|| {
+            "failed to initialize TLS probably because invalid cert or key file"
+        })?);
+
+    // TLS certificates hot reload
+    tls::spawn_reloader(
+        tls_config.clone(),
+        build_tls_config,
+        tls_files,
+        tls_watch_interval,
+    )
+    .with_context(|| "failed to initialize the tls certificates reloading")?;
+
+    Ok(tls_config)
+}
 
-        let http1_server = HyperServer::from_tcp(tcp_listener)
-            .unwrap()
-            .tcp_nodelay(true)
-            .serve(router_service);
+/// Creates the incoming connections of a TCP listener decoding their PROXY protocol
+/// header if enabled.
+fn tcp_incoming(
+    tcp_listener: TcpListener,
+    listener: &Listeners,
+) -> Result<ProxyProtocolIncoming<AddrIncoming>> {
+    tcp_listener
+        .set_nonblocking(true)
+        .with_context(|| "failed to set TCP non-blocking mode")?;
+    let tcp_listener = tokio::net::TcpListener::from_std(tcp_listener)
+        .with_context(|| "failed to create tokio::net::TcpListener")?;
+    let mut incoming = AddrIncoming::from_listener(tcp_listener).with_context(|| {
+        "failed to create an AddrIncoming from the current tokio::net::TcpListener"
+    })?;
+    incoming.set_nodelay(true);
+
+    let trusted = if listener.proxy_protocol {
+        listener.proxy_protocol_trusted_cidrs.clone()
+    } else {
+        Vec::new()
+    };
+    Ok(ProxyProtocolIncoming::new(incoming, trusted))
+}
 
-        #[cfg(unix)]
-        let http1_cancel_recv = Arc::new(Mutex::new(_cancel_recv));
+/// A listener the server accepts incoming connections on.
+enum Listener {
+    /// A TCP socket listener.
+    Tcp(TcpListener),
+    /// A Unix domain socket listener with its socket file path if it was bound by the server.
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
 
+/// Returns the main listener options out of the general ones.
+fn main_listener(general: &General) -> Listeners {
+    Listeners {
+        host: general.host.clone(),
+        port: general.port,
+        fd: general.fd,
         #[cfg(unix)]
-        let http1_server = http1_server.with_graceful_shutdown(signals::wait_for_signals(
-            signals,
-            grace_period,
-            http1_cancel_recv,
-        ));
+        unix_socket: general.unix_socket.clone(),
+        #[cfg(unix)]
+        unix_socket_mode: general.unix_socket_mode.clone(),
+        #[cfg(unix)]
+        unix_socket_owner: general.unix_socket_owner.clone(),
+        ipv6_only: false,
+        proxy_protocol: general.proxy_protocol,
+        proxy_protocol_trusted_cidrs: general.proxy_protocol_trusted_cidrs.clone(),
+        #[cfg(feature = "http2")]
+        http2: general.http2,
+        #[cfg(feature = "http2")]
+        tls: general.tls.unwrap_or(general.http2),
+        #[cfg(feature = "http2")]
+        tls_cert: general
+            .tls_cert
+            .clone()
+            .or_else(|| general.http2_tls_cert.clone()),
+        #[cfg(feature = "http2")]
+        tls_key: general
+            .tls_key
+            .clone()
+            .or_else(|| general.http2_tls_key.clone()),
+        #[cfg(feature = "http2")]
+        tls_client_auth: general.tls_client_auth,
+        #[cfg(feature = "http2")]
+        tls_client_ca: general.tls_client_ca.clone(),
+    }
+}
 
-        #[cfg(windows)]
-        let http1_server = http1_server.with_graceful_shutdown(async move {
-            let http1_cancel_recv = if general.windows_service {
-                // http1_cancel_recv
-                Arc::new(Mutex::new(_cancel_recv))
-            } else {
-                // http1_ctrlc_recv
-                Arc::new(Mutex::new(Some(receiver)))
-            };
-            signals::wait_for_ctrl_c(http1_cancel_recv, grace_period).await;
-        });
+/// Binds a server listener either to an inherited file descriptor,
+/// a Unix domain socket or a TCP socket.
+fn bind_listener(listener: &Listeners, listenfd: &mut ListenFd) -> Result<(Listener, String)> {
+    #[cfg(unix)]
+    if let Some(path) = &listener.unix_socket {
+        let unix_listener = unix_socket::bind(
+            path,
+            listener.unix_socket_mode.as_deref(),
+            listener.unix_socket_owner.as_deref(),
+        )?;
+        server_info!("server bound to unix socket {}", path.display());
+        let addr_str = format!("unix:{}", path.display());
+        return Ok((Listener::Unix(unix_listener, Some(path.clone())), addr_str));
+    }
 
-        server_info!(
-            parent: tracing::info_span!("Server::start_server", ?addr_str, ?threads),
-            "http1 server is listening on http://{}",
-            addr_str
-        );
+    match listener.fd {
+        Some(fd) => {
+            let addr_str = format!("@FD({fd})");
 
-        server_info!("press ctrl+c to shut down the server");
+            // An inherited Unix domain socket (e.g. via systemd) is accepted as well
+            #[cfg(unix)]
+            if let Ok(Some(unix_listener)) = listenfd.take_unix_listener(fd) {
+                server_info!(
+                    "converted inherited file descriptor {} to a 'unix' listener",
+                    fd
+                );
+                return Ok((Listener::Unix(unix_listener, None), addr_str));
+            }
 
-        #[cfg(unix)]
-        http1_server.await?;
+            let tcp_listener = listenfd
+                .take_tcp_listener(fd)?
+                .with_context(|| "failed to convert inherited 'fd' into a 'tcp' listener")?;
+            server_info!(
+                "converted inherited file descriptor {} to a 'tcp' listener",
+                fd
+            );
+            Ok((Listener::Tcp(tcp_listener), addr_str))
+        }
+        None => {
+            let ip = listener
+                .host
+                .parse::<IpAddr>()
+                .with_context(|| format!("failed to parse {} address", listener.host))?;
+            let addr = SocketAddr::from((ip, listener.port));
+            let tcp_listener = bind_tcp(addr, listener.ipv6_only)
+                .with_context(|| format!("failed to bind to {addr} address"))?;
+            let addr_str = addr.to_string();
+            server_info!("server bound to tcp socket {}", addr_str);
+            Ok((Listener::Tcp(tcp_listener), addr_str))
+        }
+    }
+}
 
-        #[cfg(windows)]
-        let http1_server_task = tokio::spawn(async move {
-            if let Err(err) = http1_server.await {
-                tracing::error!("http1 server failed to start up: {:?}", err);
-                std::process::exit(1)
-            }
-        });
-        #[cfg(windows)]
-        tokio::try_join!(ctrlc_task, http1_server_task)?;
+/// Binds a TCP socket optionally accepting only IPv6 connections on an IPv6 address,
+/// so an IPv4 and an IPv6 listener can share the same port.
+fn bind_tcp(addr: SocketAddr, ipv6_only: bool) -> std::io::Result<TcpListener> {
+    if !ipv6_only || !addr.is_ipv6() {
+        return TcpListener::bind(addr);
+    }
 
-        #[cfg(windows)]
-        _cancel_fn();
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
 
-        #[cfg(unix)]
-        handle.close();
+/// Builds the request handler options out of the given settings.
+/// It's used on server startup and on configuration reloads.
+pub(crate) fn build_handler_opts(
+    general: &General,
+    advanced_opts: Option<Advanced>,
+) -> Result<RequestHandlerOpts> {
+    // Check for a valid root directory
+    let root_dir = helpers::get_valid_dirpath(&general.root)
+        .with_context(|| "root directory was not found or inaccessible")?;
+
+    // Custom HTML error page files
+    // NOTE: in the case of relative paths, they're joined to the root directory
+    let mut page404 = general.page404.clone();
+    if page404.is_relative() && !page404.starts_with(&root_dir) {
+        page404 = root_dir.join(page404);
+    }
+    if !page404.is_file() {
+        tracing::debug!(
+            "404 file path not found or not a regular file: {}",
+            page404.display()
+        );
+    }
+    let mut page50x = general.page50x.clone();
+    if page50x.is_relative() && !page50x.starts_with(&root_dir) {
+        page50x = root_dir.join(page50x);
+    }
+    if !page50x.is_file() {
+        tracing::debug!(
+            "50x file path not found or not a regular file: {}",
+            page50x.display()
+        );
+    }
 
-        server_warn!("termination signal caught, shutting down the server execution");
-        Ok(())
+    // Log remote address option
+    let log_remote_address = general.log_remote_address;
+
+    // Log the X-Forwarded-For header.
+    let log_forwarded_for = general.log_forwarded_for;
+
+    // Trusted IPs for remote addresses.
+    let trusted_proxies = general.trusted_proxies.clone();
+    let trusted_proxies_header = general.trusted_proxies_header;
+
+    // Log redirect trailing slash option
+    let redirect_trailing_slash = general.redirect_trailing_slash;
+    server_info!(
+        "redirect trailing slash: enabled={}",
+        redirect_trailing_slash
+    );
+
+    // Ignore hidden files option
+    let ignore_hidden_files = general.ignore_hidden_files;
+    server_info!("ignore hidden files: enabled={}", ignore_hidden_files);
+
+    // Disable symlinks option
+    let disable_symlinks = general.disable_symlinks;
+    server_info!("disable symlinks: enabled={}", disable_symlinks);
+
+    // Index files option
+    let index_files = general
+        .index_files
+        .split(',')
+        .map(|s| s.trim().to_owned())
+        .collect::<Vec<_>>();
+    if index_files.is_empty() {
+        bail!("index files list is empty, provide at least one index file")
+    }
+    server_info!("index files: {}", general.index_files);
+
+    let mut handler_opts = RequestHandlerOpts {
+        root_dir,
+        page404,
+        page50x,
+        log_remote_address,
+        log_forwarded_for,
+        trusted_proxies,
+        trusted_proxies_header,
+        redirect_trailing_slash,
+        ignore_hidden_files,
+        disable_symlinks,
+        index_files,
+        advanced_opts,
+        ..Default::default()
+    };
+
+    // Directory listing options
+    #[cfg(feature = "directory-listing")]
+    directory_listing::init(
+        general.directory_listing,
+        general.directory_listing_order,
+        general.directory_listing_format.clone(),
+        &mut handler_opts,
+    );
+
+    // Fallback page option
+    #[cfg(feature = "fallback-page")]
+    fallback_page::init(&general.page_fallback, &mut handler_opts);
+
+    // ETag generation option
+    etag::init(general.etag, &mut handler_opts);
+
+    // Health endpoint option
+    health::init(general.health, &mut handler_opts);
+
+    // Admin listener option serving the health, readiness and metrics endpoints instead
+    admin::init(general.admin_port.is_some(), &mut handler_opts);
+
+    // Log remote address option
+    log_addr::init(general.log_remote_address, &mut handler_opts);
+
+    // Request ID option
+    request_id::init(
+        general.request_id,
+        &general.request_id_header,
+        &mut handler_opts,
+    )?;
+
+    // OpenTelemetry traces export option
+    #[cfg(feature = "otlp")]
+    otlp::init(
+        general.otlp_endpoint.as_deref(),
+        general.otlp_protocol,
+        &general.otlp_service_name,
+        &mut handler_opts,
+    );
+
+    // Access log option
+    access_log::init(
+        general.access_log,
+        &general.access_log_format,
+        general.access_log_file.as_deref(),
+        access_log::Rotation {
+            size: general.access_log_rotate_size,
+            interval: general.access_log_rotate_interval,
+            keep: general.access_log_rotate_keep,
+            compress: general.access_log_rotate_compress,
+        },
+        &mut handler_opts,
+    )?;
+
+    // Metrics endpoint option
+    #[cfg(feature = "metrics")]
+    metrics::init(general.metrics, &mut handler_opts);
+
+    // Tokio runtime metrics option (experimental)
+    #[cfg(all(unix, feature = "experimental"))]
+    metrics::init_runtime_metrics(general.experimental_metrics, &mut handler_opts);
+
+    // CORS option
+    cors::init(
+        &general.cors_allow_origins,
+        &general.cors_allow_headers,
+        &general.cors_expose_headers,
+        &mut handler_opts,
+    );
+
+    // `Basic` HTTP Authentication Schema option
+    #[cfg(feature = "jwt-auth")]
+    jwt_auth::init(general, &mut handler_opts)?;
+    #[cfg(feature = "basic-auth")]
+    basic_auth::init(general, &mut handler_opts)?;
+
+    // Maintenance mode option
+    maintenance_mode::init(general, &mut handler_opts)?;
+
+    // Signed URLs option
+    signed_urls::init(general, &mut handler_opts)?;
+
+    // IP access control option
+    ip_access::init(general, &mut handler_opts);
+
+    // Check pre-compressed files based on the `Accept-Encoding` header
+    #[cfg(any(
+        feature = "compression",
+        feature = "compression-deflate",
+        feature = "compression-gzip",
+        feature = "compression-brotli",
+        feature = "compression-zstd",
+    ))]
+    compression_static::init(general.compression_static, &mut handler_opts);
+
+    // Auto compression based on the `Accept-Encoding` header
+    #[cfg(any(
+        feature = "compression",
+        feature = "compression-deflate",
+        feature = "compression-gzip",
+        feature = "compression-brotli",
+        feature = "compression-zstd",
+    ))]
+    compression::init(
+        general.compression,
+        general.compression_level,
+        &mut handler_opts,
+    );
+
+    // Cache control headers option
+    control_headers::init(general.cache_control_headers, &mut handler_opts);
+
+    // Security Headers option
+    security_headers::init(general.security_headers, &mut handler_opts);
+
+    Ok(handler_opts)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{bind_listener, main_listener, Listener};
+    use crate::settings::cli::General;
+    use clap::Parser;
+    use listenfd::ListenFd;
+    use std::net::TcpListener;
+    use std::os::fd::IntoRawFd;
+
+    #[test]
+    fn bind_multiple_inherited_listeners() {
+        let sockets = [
+            TcpListener::bind("127.0.0.1:0").unwrap(),
+            TcpListener::bind("127.0.0.1:0").unwrap(),
+        ];
+        let addrs = [
+            sockets[0].local_addr().unwrap(),
+            sockets[1].local_addr().unwrap(),
+        ];
+        let fds = sockets.map(|s| s.into_raw_fd());
+        let first_fd = *fds.iter().min().unwrap();
+        let last_fd = *fds.iter().max().unwrap();
+        std::env::remove_var("LISTEN_PID");
+        std::env::set_var("LISTEN_FDS_FIRST_FD", first_fd.to_string());
+        std::env::set_var("LISTEN_FDS", (last_fd - first_fd + 1).to_string());
+
+        let general = General::parse_from(["static-web-server"]);
+        let mut listenfd = ListenFd::from_env();
+        for (fd, addr) in fds.into_iter().zip(addrs) {
+            let mut listener = main_listener(&general);
+            listener.fd = Some((fd - first_fd) as usize);
+            let (socket, _) = bind_listener(&listener, &mut listenfd).unwrap();
+            match socket {
+                Listener::Tcp(socket) => assert_eq!(socket.local_addr().unwrap(), addr),
+                _ => panic!("inherited file descriptor {fd} is not a tcp listener"),
+            }
+        }
     }
 }
@@ -11,12 +11,20 @@ use std::convert::Infallible;
 use std::future::{ready, Future, Ready};
 use std::net::SocketAddr;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
 
 use crate::{handler::RequestHandler, transport::Transport, Error};
 
+#[cfg(feature = "metrics")]
+use crate::metrics;
+#[cfg(feature = "http2")]
+use crate::mtls::ClientCertSlot;
+#[cfg(unix)]
+use crate::unix_socket::PeerCred;
+
 /// It defines the router service which is the main entry point for Hyper Server.
+#[derive(Clone)]
 pub struct RouterService {
     builder: RequestServiceBuilder,
 }
@@ -28,6 +36,11 @@ impl RouterService {
             builder: RequestServiceBuilder::new(handler),
         }
     }
+
+    /// Returns the request handler shared with the request services.
+    pub fn shared_handler(&self) -> SharedRequestHandler {
+        self.builder.handler.clone()
+    }
 }
 
 impl<T: Transport + Send + 'static> Service<&T> for RouterService {
@@ -40,14 +53,31 @@ impl<T: Transport + Send + 'static> Service<&T> for RouterService {
     }
 
     fn call(&mut self, conn: &T) -> Self::Future {
-        ready(Ok(self.builder.build(conn.remote_addr())))
+        let service = self.builder.build(conn.remote_addr());
+        #[cfg(feature = "http2")]
+        let service = RequestService {
+            client_cert: conn.client_cert(),
+            ..service
+        };
+        #[cfg(unix)]
+        let service = RequestService {
+            peer_cred: conn.peer_cred(),
+            ..service
+        };
+        ready(Ok(service))
     }
 }
 
 /// It defines a Hyper service request which delegates a request handler.
 pub struct RequestService {
-    handler: Arc<RequestHandler>,
+    handler: SharedRequestHandler,
     remote_addr: Option<SocketAddr>,
+    #[cfg(feature = "http2")]
+    client_cert: Option<ClientCertSlot>,
+    #[cfg(unix)]
+    peer_cred: Option<PeerCred>,
+    #[cfg(feature = "metrics")]
+    _connection: Option<metrics::Connection>,
 }
 
 impl Service<Request<Body>> for RequestService {
@@ -60,22 +90,33 @@ impl Service<Request<Body>> for RequestService {
     }
 
     fn call(&mut self, mut req: Request<Body>) -> Self::Future {
-        let handler = self.handler.clone();
+        let handler = self.handler.get();
         let remote_addr = self.remote_addr;
+        // Expose the verified TLS client certificate to the request pipeline
+        #[cfg(feature = "http2")]
+        if let Some(cert) = self.client_cert.as_ref().and_then(|slot| slot.get()) {
+            req.extensions_mut().insert(cert.clone());
+        }
+        // Expose the Unix domain socket peer credentials to the request pipeline
+        #[cfg(unix)]
+        if let Some(cred) = self.peer_cred {
+            req.extensions_mut().insert(cred);
+        }
         Box::pin(async move { handler.handle(&mut req, remote_addr).await })
     }
 }
 
 /// It defines a Hyper service request builder.
+#[derive(Clone)]
 pub struct RequestServiceBuilder {
-    handler: Arc<RequestHandler>,
+    handler: SharedRequestHandler,
 }
 
 impl RequestServiceBuilder {
     /// Initializes a new request service builder.
     pub fn new(handler: RequestHandler) -> Self {
         Self {
-            handler: Arc::new(handler),
+            handler: SharedRequestHandler::new(handler),
         }
     }
 
@@ -84,6 +125,45 @@ impl RequestServiceBuilder {
         RequestService {
             handler: self.handler.clone(),
             remote_addr,
+            #[cfg(feature = "http2")]
+            client_cert: None,
+            #[cfg(unix)]
+            peer_cred: None,
+            #[cfg(feature = "metrics")]
+            _connection: metrics::connection_opened(),
+        }
+    }
+}
+
+/// A request handler shared with the request services which can be swapped at runtime.
+/// New requests use the current handler while in-flight ones keep theirs.
+#[derive(Clone)]
+pub struct SharedRequestHandler {
+    inner: Arc<RwLock<Arc<RequestHandler>>>,
+}
+
+impl SharedRequestHandler {
+    /// Creates a new shared request handler.
+    pub fn new(handler: RequestHandler) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Arc::new(handler))),
+        }
+    }
+
+    /// Returns the current request handler.
+    pub fn get(&self) -> Arc<RequestHandler> {
+        match self.inner.read() {
+            Ok(handler) => handler.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the current request handler.
+    pub fn set(&self, handler: RequestHandler) {
+        let handler = Arc::new(handler);
+        match self.inner.write() {
+            Ok(mut current) => *current = handler,
+            Err(poisoned) => *poisoned.into_inner() = handler,
         }
     }
 }
@@ -7,10 +7,19 @@
 
 use clap::Parser;
 use hyper::StatusCode;
+use ipnet::IpNet;
 use std::{net::IpAddr, path::PathBuf};
 
+use crate::access_log::RotateInterval;
 #[cfg(feature = "directory-listing")]
 use crate::directory_listing::DirListFmt;
+use crate::etag::ETagMode;
+use crate::log_addr::ForwardedHeader;
+use crate::logger::{LogFormat, LogOutput};
+#[cfg(feature = "http2")]
+use crate::mtls::ClientAuthMode;
+#[cfg(feature = "otlp")]
+use crate::otlp::OtlpProtocol;
 use crate::Result;
 
 /// General server configuration available in CLI and config file options.
@@ -44,7 +53,8 @@ pub struct General {
         )
     )]
     /// Instead of binding to a TCP port, accept incoming connections to an already-bound TCP
-    /// socket listener on the specified file descriptor number (usually zero). Requires that the
+    /// (or Unix domain, on Unix) socket listener on the specified file descriptor number
+    /// (usually zero). Requires that the
     /// parent process (e.g. inetd, launchd, or systemd) binds an address and port on behalf of
     /// static-web-server, before arranging for the resulting file descriptor to be inherited by
     /// static-web-server. Cannot be used in conjunction with the port and host arguments. The
@@ -52,6 +62,54 @@ pub struct General {
     /// static-web-server to be sandboxed more completely.
     pub fd: Option<usize>,
 
+    #[arg(
+        long,
+        env = "SERVER_UNIX_SOCKET",
+        conflicts_with_all(&["host", "port", "fd"])
+    )]
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    /// Instead of binding to a TCP port, listen on a Unix domain socket at the specified file path.
+    /// A stale socket file left behind by a previous server process is removed on startup.
+    pub unix_socket: Option<PathBuf>,
+
+    #[arg(long, env = "SERVER_UNIX_SOCKET_MODE")]
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    /// File mode (octal) of the Unix domain socket file. E.g. 660.
+    pub unix_socket_mode: Option<String>,
+
+    #[arg(long, env = "SERVER_UNIX_SOCKET_OWNER")]
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    /// Owner of the Unix domain socket file in the form of "USER[:GROUP]" where both can be
+    /// either names or numeric IDs. E.g. www-data:www-data or 1000:1000.
+    pub unix_socket_owner: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(false),
+        action = clap::ArgAction::Set,
+        env = "SERVER_PROXY_PROTOCOL",
+    )]
+    /// Decode the PROXY protocol (v1 or v2) header that trusted load balancers prepend to
+    /// incoming TCP connections and use the client address it carries as the remote address.
+    pub proxy_protocol: bool,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_PROXY_PROTOCOL_TRUSTED_CIDRS",
+    )]
+    /// List of CIDRs (e.g. 10.0.0.0/8,fd00::/8) of the load balancers allowed to send a PROXY
+    /// protocol header. Connections from other sources are served as regular connections.
+    pub proxy_protocol_trusted_cidrs: Vec<IpNet>,
+
     #[cfg_attr(
         not(target_family = "wasm"),
         arg(
@@ -123,6 +181,34 @@ pub struct General {
     /// Specify a logging level in lower case. Values: error, warn, info, debug or trace
     pub log_level: String,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        env = "SERVER_LOG_FORMAT",
+        ignore_case(true)
+    )]
+    /// Specify the diagnostic log output format. Values: "text" (human-readable lines) or "json" (one JSON object per event).
+    pub log_format: LogFormat,
+
+    #[arg(long, default_value = "", env = "SERVER_LOG_FILTER")]
+    /// Comma-separated per-target log level directives like "static_web_server=debug,hyper=warn" applied on top of the log level.
+    pub log_filter: String,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "stderr",
+        env = "SERVER_LOG_OUTPUT",
+        ignore_case(true)
+    )]
+    /// Specify where the diagnostic log is written to. Values: "stderr", "syslog" (a local syslog socket using the RFC 5424 format) or "journald" (the systemd journal with structured fields).
+    pub log_output: LogOutput,
+
+    #[arg(long, default_value = "/dev/log", env = "SERVER_LOG_SYSLOG_SOCKET")]
+    /// Local syslog Unix datagram socket path used by the "syslog" log output.
+    pub log_syslog_socket: PathBuf,
+
     #[arg(
         long,
         short = 'c',
@@ -161,21 +247,77 @@ pub struct General {
     )]
     #[cfg(feature = "http2")]
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
-    /// Enable HTTP/2 with TLS support.
+    /// Enable HTTP/2 support. It implies TLS unless "tls" is explicitly disabled, in which case
+    /// HTTP/2 is served over cleartext (h2c) along with HTTP/1.1.
     pub http2: bool,
 
-    #[arg(long, required_if_eq("http2", "true"), env = "SERVER_HTTP2_TLS_CERT")]
+    #[arg(long, env = "SERVER_HTTP2_TLS_CERT")]
     #[cfg(feature = "http2")]
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
-    /// Specify the file path to read the certificate.
+    /// Specify the file path to read the certificate. Alias of "tls_cert" kept for compatibility.
     pub http2_tls_cert: Option<PathBuf>,
 
-    #[arg(long, required_if_eq("http2", "true"), env = "SERVER_HTTP2_TLS_KEY")]
+    #[arg(long, env = "SERVER_HTTP2_TLS_KEY")]
     #[cfg(feature = "http2")]
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
-    /// Specify the file path to read the private key.
+    /// Specify the file path to read the private key. Alias of "tls_key" kept for compatibility.
     pub http2_tls_key: Option<PathBuf>,
 
+    #[arg(
+        long,
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(false),
+        action = clap::ArgAction::Set,
+        env = "SERVER_TLS",
+    )]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Enable TLS (HTTPS) support independently of the HTTP protocol version. ALPN negotiates
+    /// "h2" and "http/1.1" when "http2" is enabled or "http/1.1" only otherwise.
+    /// If not specified, it follows the "http2" option.
+    pub tls: Option<bool>,
+
+    #[arg(long, env = "SERVER_TLS_CERT")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Specify the file path to read the TLS certificate.
+    pub tls_cert: Option<PathBuf>,
+
+    #[arg(long, env = "SERVER_TLS_KEY")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Specify the file path to read the TLS private key.
+    pub tls_key: Option<PathBuf>,
+
+    #[arg(long, default_value = "0", env = "SERVER_TLS_WATCH_INTERVAL")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Interval in seconds to check the TLS certificate and key files (including the ones of
+    /// virtual hosts) for changes and reload them without restarting. Zero disables it.
+    /// On Unix, certificates can also be reloaded on demand by sending a `SIGHUP` signal.
+    pub tls_watch_interval: u64,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "none",
+        ignore_case(true),
+        env = "SERVER_TLS_CLIENT_AUTH"
+    )]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// TLS client certificate authentication (mutual TLS) mode: "none", "optional" (verify
+    /// certificates if presented) or "required" (reject clients without a valid certificate).
+    /// It requires the "tls-client-ca" option.
+    pub tls_client_auth: ClientAuthMode,
+
+    #[arg(long, env = "SERVER_TLS_CLIENT_CA")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Specify the file path to read the CA certificates bundle used to verify client certificates.
+    pub tls_client_ca: Option<PathBuf>,
+
     #[arg(
         long,
         default_value = "false",
@@ -183,12 +325,11 @@ pub struct General {
         num_args(0..=1),
         require_equals(false),
         action = clap::ArgAction::Set,
-        requires_if("true", "http2"),
         env = "SERVER_HTTPS_REDIRECT"
     )]
     #[cfg(feature = "http2")]
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
-    /// Redirect all requests with scheme "http" to "https" for the current server instance. It depends on "http2" to be enabled.
+    /// Redirect all requests with scheme "http" to "https" for the current server instance. It depends on "tls" (or "http2") to be enabled.
     pub https_redirect: bool,
 
     #[arg(
@@ -352,6 +493,8 @@ pub struct General {
     #[arg(
         long,
         default_value = "false",
+        default_value_if("tls", "false", Some("false")),
+        default_value_if("tls", "true", Some("true")),
         default_value_if("http2", "true", Some("true")),
         default_missing_value("true"),
         num_args(0..=1),
@@ -359,7 +502,7 @@ pub struct General {
         action = clap::ArgAction::Set,
         env = "SERVER_SECURITY_HEADERS",
     )]
-    /// Enable security headers by default when HTTP/2 feature is activated.
+    /// Enable security headers by default when TLS is activated, including HTTP/2 unless TLS is disabled (h2c).
     /// Headers included: "Strict-Transport-Security: max-age=63072000; includeSubDomains; preload" (2 years max-age),
     /// "X-Frame-Options: DENY" and "Content-Security-Policy: frame-ancestors 'self'".
     pub security_headers: bool,
@@ -388,6 +531,95 @@ pub struct General {
     #[arg(long, default_value = "", env = "SERVER_BASIC_AUTH")]
     pub basic_auth: String,
 
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    #[arg(long, env = "SERVER_BASIC_AUTH_FILE")]
+    /// Apache htpasswd file of the "Basic" HTTP Authentication scheme users. The passwords must be hashed using "BCrypt", "SHA-256-crypt", "SHA-512-crypt" or "Argon2". The file is loaded again once it changes.
+    pub basic_auth_file: Option<PathBuf>,
+
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    #[arg(long, env = "SERVER_BASIC_AUTH_GROUPS_FILE")]
+    /// Apache groups file with "group: user1 user2" lines defining the groups of the "basic_auth_file" users. The file is loaded again once it changes.
+    pub basic_auth_groups_file: Option<PathBuf>,
+
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_BASIC_AUTH_GROUPS",
+    )]
+    /// List of groups whose users are allowed to access. All the authenticated users are allowed if not set.
+    pub basic_auth_groups: Vec<String>,
+
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    #[arg(
+        long,
+        default_value = "Static Web Server",
+        env = "SERVER_BASIC_AUTH_REALM"
+    )]
+    /// Realm of the "Basic" HTTP Authentication scheme sent to the clients.
+    pub basic_auth_realm: String,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(long, env = "SERVER_JWT_AUTH_SECRET")]
+    /// Shared secret verifying the HS256 signatures of the JSON Web Tokens (JWT) sent via the "Authorization: Bearer" header or the "jwt_auth_cookie".
+    /// The JWT authentication is enabled if a secret, public key or JWKS file is defined.
+    pub jwt_auth_secret: Option<String>,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(long, env = "SERVER_JWT_AUTH_PUBLIC_KEY")]
+    /// PEM file of the RSA (RS256) or EC P-256 (ES256) public key verifying the JWT signatures.
+    pub jwt_auth_public_key: Option<PathBuf>,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(long, env = "SERVER_JWT_AUTH_JWKS_FILE")]
+    /// JSON Web Key Set (JWKS) file of the HS256, RS256 or ES256 keys verifying the JWT signatures, selected via the token "kid" header.
+    pub jwt_auth_jwks_file: Option<PathBuf>,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(long, env = "SERVER_JWT_AUTH_COOKIE")]
+    /// Cookie name to read the JWT from when there is no "Authorization: Bearer" header.
+    pub jwt_auth_cookie: Option<String>,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_JWT_AUTH_AUDIENCE",
+    )]
+    /// List of accepted JWT "aud" claim values. The audience is not checked if not set.
+    pub jwt_auth_audience: Vec<String>,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_JWT_AUTH_ISSUER",
+    )]
+    /// List of accepted JWT "iss" claim values. The issuer is not checked if not set.
+    pub jwt_auth_issuer: Vec<String>,
+
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    #[arg(long, default_value = "60", env = "SERVER_JWT_AUTH_LEEWAY")]
+    /// Clock skew in seconds tolerated when checking the JWT "exp" and "nbf" claims.
+    pub jwt_auth_leeway: u64,
+
     #[arg(long, short = 'q', default_value = "0", env = "SERVER_GRACE_PERIOD")]
     /// Defines a grace period in seconds after a `SIGTERM` signal is caught which will delay the server before to shut it down gracefully. The maximum value is 255 seconds.
     pub grace_period: u8,
@@ -436,6 +668,114 @@ pub struct General {
     /// List of IPs to use X-Forwarded-For from. The default is to trust all
     pub trusted_proxies: Vec<IpAddr>,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value = "x-forwarded-for",
+        env = "SERVER_TRUSTED_PROXIES_HEADER",
+        ignore_case(true)
+    )]
+    /// Header the client IP is taken from when the request comes from a trusted proxy (see "trusted_proxies").
+    /// The proxy must set or append to it. Values: "x-forwarded-for" or "forwarded".
+    pub trusted_proxies_header: ForwardedHeader,
+
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(false),
+        action = clap::ArgAction::Set,
+        env = "SERVER_REQUEST_ID",
+    )]
+    /// Assign an ID to every request which is added to its log entries and echoed in a response header.
+    /// An ID received in the same header from a trusted proxy (see "trusted_proxies") is reused.
+    pub request_id: bool,
+
+    #[arg(long, default_value = "x-request-id", env = "SERVER_REQUEST_ID_HEADER")]
+    /// Request and response header name carrying the request ID.
+    pub request_id_header: String,
+
+    #[arg(long, env = "SERVER_OTLP_ENDPOINT")]
+    #[cfg(feature = "otlp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "otlp")))]
+    /// OpenTelemetry collector URL like "http://localhost:4318" to export a trace span for every request to via OTLP. The export is disabled if not set.
+    pub otlp_endpoint: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "http",
+        env = "SERVER_OTLP_PROTOCOL",
+        ignore_case(true)
+    )]
+    #[cfg(feature = "otlp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "otlp")))]
+    /// OTLP transport protocol used to export the traces. Values: "http" (HTTP with binary Protobuf payloads) or "grpc".
+    pub otlp_protocol: OtlpProtocol,
+
+    #[arg(
+        long,
+        default_value = "static-web-server",
+        env = "SERVER_OTLP_SERVICE_NAME"
+    )]
+    #[cfg(feature = "otlp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "otlp")))]
+    /// Service name of the exported traces.
+    pub otlp_service_name: String,
+
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(false),
+        action = clap::ArgAction::Set,
+        env = "SERVER_ACCESS_LOG",
+    )]
+    /// Write an access log entry for every request once its response is completed.
+    /// It is written separately from the server diagnostic log.
+    pub access_log: bool,
+
+    #[arg(long, default_value = "combined", env = "SERVER_ACCESS_LOG_FORMAT")]
+    /// Access log entries format. Possible values: "common", "combined", "json" or a custom template
+    /// containing placeholders like "{remote_addr} {method} {uri} {status} {bytes} {duration_ms}".
+    pub access_log_format: String,
+
+    #[arg(long, env = "SERVER_ACCESS_LOG_FILE")]
+    /// File path where the access log entries are appended to. The standard error output is used by default.
+    pub access_log_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "0", env = "SERVER_ACCESS_LOG_ROTATE_SIZE")]
+    /// Rotate the access log file once it would grow beyond this size in bytes. Zero disables size-based rotation.
+    pub access_log_rotate_size: u64,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "never",
+        env = "SERVER_ACCESS_LOG_ROTATE_INTERVAL",
+        ignore_case(true)
+    )]
+    /// Rotate the access log file when a new period starts. Values: "hourly", "daily" or "never".
+    pub access_log_rotate_interval: RotateInterval,
+
+    #[arg(long, default_value = "7", env = "SERVER_ACCESS_LOG_ROTATE_KEEP")]
+    /// Number of rotated access log files to keep. Older files are deleted.
+    pub access_log_rotate_keep: usize,
+
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(false),
+        action = clap::ArgAction::Set,
+        env = "SERVER_ACCESS_LOG_ROTATE_COMPRESS",
+    )]
+    /// Compress the rotated access log files using gzip.
+    pub access_log_rotate_compress: bool,
+
     #[arg(
         long,
         default_value = "true",
@@ -472,6 +812,16 @@ pub struct General {
     /// Prevent following files or directories if any path name component is a symbolic link.
     pub disable_symlinks: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value = "weak",
+        env = "SERVER_ETAG",
+        ignore_case(true)
+    )]
+    /// Specify how the `ETag` header of file responses is generated. Values: "weak" (based on the file modification time and size), "strong" (based on a SHA-256 hash of the file content) or "off". Default "weak".
+    pub etag: ETagMode,
+
     #[arg(
         long,
         default_value = "false",
@@ -481,10 +831,24 @@ pub struct General {
         action = clap::ArgAction::Set,
         env = "SERVER_HEALTH",
     )]
-    /// Add a /health endpoint that doesn't generate any log entry and returns a 200 status code.
+    /// Add a /health endpoint that doesn't generate any log entry and returns a 200 status code,
+    /// plus a /ready endpoint that returns a 503 status code while the server is not ready to serve requests.
     /// This is especially useful with Kubernetes liveness and readiness probes.
     pub health: bool,
 
+    #[cfg(feature = "metrics")]
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(false),
+        action = clap::ArgAction::Set,
+        env = "SERVER_METRICS",
+    )]
+    /// Add a /metrics endpoint that returns Prometheus metrics about the HTTP requests, responses, connections and caches.
+    pub metrics: bool,
+
     #[cfg(all(unix, feature = "experimental"))]
     #[arg(
         long = "experimental-metrics",
@@ -495,9 +859,43 @@ pub struct General {
         action = clap::ArgAction::Set,
         env = "SERVER_EXPERIMENTAL_METRICS",
     )]
-    /// Add a /metrics endpoint that returns a Prometheus metrics response.
+    /// Add the Tokio runtime metrics to the /metrics endpoint, enabling it if needed.
     pub experimental_metrics: bool,
 
+    #[arg(long, env = "SERVER_ADMIN_PORT")]
+    /// Port of a separate admin listener serving the health, readiness and metrics endpoints instead of the public listeners. The admin listener is disabled if not set.
+    pub admin_port: Option<u16>,
+
+    #[arg(long, default_value = "127.0.0.1", env = "SERVER_ADMIN_HOST")]
+    /// Host address (E.g 127.0.0.1 or ::1) of the admin listener. It depends on "admin_port" to be set.
+    pub admin_host: String,
+
+    #[arg(long, default_value = "/health", env = "SERVER_ADMIN_HEALTH_PATH")]
+    /// URL path of the health endpoint on the admin listener.
+    pub admin_health_path: String,
+
+    #[arg(long, default_value = "/ready", env = "SERVER_ADMIN_READY_PATH")]
+    /// URL path of the readiness endpoint on the admin listener.
+    pub admin_ready_path: String,
+
+    #[arg(
+        long,
+        default_value = "/maintenance",
+        env = "SERVER_ADMIN_MAINTENANCE_PATH"
+    )]
+    /// URL path of the endpoint on the admin listener to get (GET), enable (POST) or disable (DELETE) the maintenance mode at runtime.
+    pub admin_maintenance_path: String,
+
+    #[arg(long, default_value = "/metrics", env = "SERVER_ADMIN_METRICS_PATH")]
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    /// URL path of the metrics endpoint on the admin listener. It depends on "metrics" to be enabled.
+    pub admin_metrics_path: String,
+
+    #[arg(long, env = "SERVER_ADMIN_AUTH_TOKEN")]
+    /// Token that the admin listener clients must send via the "Authorization: Bearer <token>" header. No authentication is required if not set.
+    pub admin_auth_token: Option<String>,
+
     #[arg(
         long,
         default_value = "false",
@@ -530,6 +928,80 @@ pub struct General {
     /// Provide a custom maintenance mode HTML file. If not provided then a generic message will be displayed.
     pub maintenance_mode_file: PathBuf,
 
+    #[arg(long, env = "SERVER_MAINTENANCE_MODE_TRIGGER_FILE")]
+    /// File enabling the maintenance mode while it exists (e.g. ".maintenance"). A relative path is resolved against the root directory, or the virtual host root directory.
+    pub maintenance_mode_trigger_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_MAINTENANCE_MODE_HOSTS",
+    )]
+    /// List of hosts (e.g. example.com,*.example.org) the maintenance mode is scoped to. The maintenance mode applies to all hosts if not set.
+    pub maintenance_mode_hosts: Vec<String>,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_MAINTENANCE_MODE_PATHS",
+    )]
+    /// List of path glob patterns (e.g. /shop/**) the maintenance mode is scoped to. The maintenance mode applies to all paths if not set.
+    pub maintenance_mode_paths: Vec<String>,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        value_parser = value_parser_ip_net,
+        action = clap::ArgAction::Set,
+        env = "SERVER_MAINTENANCE_MODE_ALLOW_IPS",
+    )]
+    /// List of client IPs or CIDRs (e.g. 203.0.113.7,10.0.0.0/8) allowed to bypass the maintenance mode.
+    /// Behind a trusted proxy (see "trusted_proxies") the client IP is taken from the "trusted_proxies_header" header.
+    pub maintenance_mode_allow_ips: Vec<IpNet>,
+
+    #[arg(long, env = "SERVER_SIGNED_URLS_SECRET")]
+    /// Shared secret verifying the HMAC-SHA256 signatures of signed, expiring URLs (see the "sign-url" subcommand).
+    /// The signed URLs are required if a secret is defined.
+    pub signed_urls_secret: Option<String>,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        action = clap::ArgAction::Set,
+        env = "SERVER_SIGNED_URLS_PATHS",
+    )]
+    /// List of path glob patterns (e.g. /downloads/**) requiring a signed URL. All paths require it if not set.
+    pub signed_urls_paths: Vec<String>,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        value_parser = value_parser_ip_net,
+        action = clap::ArgAction::Set,
+        env = "SERVER_ALLOW_IPS",
+    )]
+    /// List of client IPs or CIDRs (e.g. 203.0.113.7,10.0.0.0/8,2001:db8::/32) allowed to access the server, all if not set.
+    /// Behind a trusted proxy (see "trusted_proxies") the client IP is taken from the "trusted_proxies_header" header.
+    pub allow_ips: Vec<IpNet>,
+
+    #[arg(
+        long,
+        require_equals(false),
+        value_delimiter(','),
+        value_parser = value_parser_ip_net,
+        action = clap::ArgAction::Set,
+        env = "SERVER_DENY_IPS",
+    )]
+    /// List of client IPs or CIDRs denied access to the server, taking precedence over the allowed ones.
+    pub deny_ips: Vec<IpNet>,
+
     //
     // Windows specific arguments and commands
     //
@@ -588,12 +1060,41 @@ pub enum Commands {
         /// Path to write generated artifacts to
         out_dir: PathBuf,
     },
+
+    /// Generate a signed URL which expires after some time
+    #[command(name = "sign-url")]
+    SignUrl {
+        /// Shared secret signing the URL, the "signed_urls_secret" option is used if not set
+        #[arg(long)]
+        secret: Option<String>,
+        /// Validity of the URL in seconds
+        #[arg(long, default_value = "3600")]
+        expires_in: u64,
+        /// Client IP the URL is bound to
+        #[arg(long)]
+        ip: Option<IpAddr>,
+        /// Path prefix (e.g. /downloads/) the URL is valid for, making the same signature valid for any path starting with it
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Base URL (e.g. https://example.com) prepended to the signed path
+        #[arg(long)]
+        base_url: Option<String>,
+        /// Path of the URL to sign (e.g. /downloads/report.pdf)
+        path: String,
+    },
 }
 
 fn value_parser_pathbuf(s: &str) -> Result<PathBuf, String> {
     Ok(PathBuf::from(s))
 }
 
+/// Parses an IP network in CIDR notation or a single IP address.
+pub(crate) fn value_parser_ip_net(s: &str) -> Result<IpNet, String> {
+    s.parse::<IpNet>()
+        .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("invalid IP address or CIDR \"{s}\""))
+}
+
 fn value_parser_status_code(s: &str) -> Result<StatusCode, String> {
     match s.parse::<u16>() {
         Ok(code) => StatusCode::from_u16(code).map_err(|err| err.to_string()),
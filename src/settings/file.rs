@@ -6,6 +6,7 @@
 //! The server configuration file options (manifest)
 
 use headers::HeaderMap;
+use ipnet::IpNet;
 use serde::Deserialize;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::net::IpAddr;
@@ -15,7 +16,15 @@ use std::{collections::BTreeSet, path::PathBuf};
 #[cfg(feature = "directory-listing")]
 use crate::directory_listing::DirListFmt;
 
-use crate::{helpers, Context, Result};
+use crate::access_log::RotateInterval;
+use crate::etag::ETagMode;
+use crate::log_addr::ForwardedHeader;
+use crate::logger::{LogFormat, LogOutput};
+#[cfg(feature = "http2")]
+use crate::mtls::ClientAuthMode;
+#[cfg(feature = "otlp")]
+use crate::otlp::OtlpProtocol;
+use crate::{helpers, settings::cli::value_parser_ip_net, Context, Result};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -159,6 +168,103 @@ pub struct VirtualHosts {
     pub host: String,
     /// The root directory for this virtual host
     pub root: Option<PathBuf>,
+    /// The TLS certificate file path for this virtual host, selected via SNI
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_cert: Option<PathBuf>,
+    /// The TLS private key file path for this virtual host
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_key: Option<PathBuf>,
+}
+
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents TLS client certificate authorization rules for request paths.
+pub struct ClientCertAuth {
+    /// Source pattern glob of the request paths.
+    pub source: String,
+    /// Optional glob patterns of the allowed certificate subjects.
+    pub subjects: Option<Vec<String>>,
+}
+
+#[cfg(feature = "basic-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents authentication rules for request paths.
+pub struct Auth {
+    /// Source pattern glob of the request paths.
+    pub source: String,
+    /// Optional host to match against the `Host` header.
+    pub host: Option<String>,
+    /// Optional users allowed to access.
+    pub users: Option<Vec<String>>,
+    /// Optional groups whose users are allowed to access.
+    pub groups: Option<Vec<String>>,
+    /// Whether the matching requests don't require authentication.
+    pub public: Option<bool>,
+    /// Optional JWT claims and their accepted values.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub claims: Option<std::collections::BTreeMap<String, Vec<String>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents an additional address the server listens on.
+pub struct Listeners {
+    /// The host address to bind to, the general `host` if not specified.
+    pub host: Option<String>,
+    /// The port to bind to.
+    pub port: Option<u16>,
+    /// An inherited file descriptor to listen on instead of a host and port.
+    pub fd: Option<usize>,
+    /// A Unix domain socket file path to listen on instead of a host and port.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket: Option<PathBuf>,
+    /// Unix domain socket file mode (octal).
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket_mode: Option<String>,
+    /// Unix domain socket file owner.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket_owner: Option<String>,
+    /// Accept only IPv6 connections on an IPv6 address.
+    pub ipv6_only: Option<bool>,
+    /// PROXY protocol header decoding.
+    pub proxy_protocol: Option<bool>,
+    /// CIDRs of the load balancers allowed to send a PROXY protocol header,
+    /// the general `proxy-protocol-trusted-cidrs` if not specified.
+    pub proxy_protocol_trusted_cidrs: Option<Vec<IpNet>>,
+    /// HTTP/2 support.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2: Option<bool>,
+    /// TLS (HTTPS), it follows `http2` if not specified.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls: Option<bool>,
+    /// TLS certificate file path, the general `tls-cert` if not specified.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_cert: Option<PathBuf>,
+    /// TLS private key file path, the general `tls-key` if not specified.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_key: Option<PathBuf>,
+    /// TLS client certificate authentication mode.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_client_auth: Option<ClientAuthMode>,
+    /// TLS client CA certificates bundle file path, the general `tls-client-ca` if not specified.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_client_ca: Option<PathBuf>,
 }
 
 #[cfg(feature = "experimental")]
@@ -176,6 +282,37 @@ pub struct MemoryCache {
     pub max_file_size: Option<u64>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents IP access control rules for request paths.
+pub struct IpAccess {
+    /// Source pattern glob of the request paths.
+    pub source: String,
+    /// Optional host to match against the `Host` header.
+    pub host: Option<String>,
+    /// Optional client IPs or CIDRs allowed to access.
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub allow: Option<Vec<IpNet>>,
+    /// Optional client IPs or CIDRs denied access.
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub deny: Option<Vec<IpNet>>,
+}
+
+/// Deserializes a list of IP networks in CIDR notation or single IP addresses.
+fn deserialize_ip_nets<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<IpNet>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|values| {
+            values
+                .iter()
+                .map(|value| value_parser_ip_net(value).map_err(serde::de::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
 /// Advanced server options only available in configuration file mode.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -188,6 +325,18 @@ pub struct Advanced {
     pub redirects: Option<Vec<Redirects>>,
     /// Name-based virtual hosting
     pub virtual_hosts: Option<Vec<VirtualHosts>>,
+    /// TLS client certificate authorization rules
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub client_cert_auth: Option<Vec<ClientCertAuth>>,
+    /// Authentication rules
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub auth: Option<Vec<Auth>>,
+    /// IP access control rules
+    pub ip_access: Option<Vec<IpAccess>>,
+    /// Additional listeners
+    pub listeners: Option<Vec<Listeners>>,
     #[cfg(feature = "experimental")]
     /// In-memory cache feature (experimental).
     pub memory_cache: Option<MemoryCache>,
@@ -208,6 +357,18 @@ pub struct General {
     /// Logging.
     pub log_level: Option<LogLevel>,
 
+    /// Logging output format.
+    pub log_format: Option<LogFormat>,
+
+    /// Logging per-target filter directives.
+    pub log_filter: Option<String>,
+
+    /// Logging output destination.
+    pub log_output: Option<LogOutput>,
+
+    /// Logging syslog socket path.
+    pub log_syslog_socket: Option<PathBuf>,
+
     /// Cache Control headers.
     pub cache_control_headers: Option<bool>,
 
@@ -289,6 +450,31 @@ pub struct General {
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
     pub http2_tls_key: Option<PathBuf>,
 
+    /// TLS (HTTPS) independently of the HTTP protocol version.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls: Option<bool>,
+    /// TLS certificate file path.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_cert: Option<PathBuf>,
+    /// TLS private key file path.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_key: Option<PathBuf>,
+    /// Interval in seconds to check the TLS files for changes.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_watch_interval: Option<u64>,
+    /// TLS client certificate authentication mode.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_client_auth: Option<ClientAuthMode>,
+    /// TLS client CA certificates bundle file path.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_client_ca: Option<PathBuf>,
+
     /// Redirect all HTTP requests to HTTPS.
     #[cfg(feature = "http2")]
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
@@ -337,9 +523,82 @@ pub struct General {
     #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
     pub basic_auth: Option<String>,
 
+    /// Basic Authentication users file.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_file: Option<PathBuf>,
+
+    /// Basic Authentication groups file.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_groups_file: Option<PathBuf>,
+
+    /// Basic Authentication groups allowed to access.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_groups: Option<Vec<String>>,
+
+    /// Basic Authentication realm.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_realm: Option<String>,
+
+    /// JWT Authentication HS256 secret.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_secret: Option<String>,
+
+    /// JWT Authentication RS256 or ES256 public key file.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_public_key: Option<PathBuf>,
+
+    /// JWT Authentication JWKS file.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_jwks_file: Option<PathBuf>,
+
+    /// JWT Authentication cookie name.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_cookie: Option<String>,
+
+    /// JWT Authentication accepted audiences.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_audience: Option<Vec<String>>,
+
+    /// JWT Authentication accepted issuers.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_issuer: Option<Vec<String>>,
+
+    /// JWT Authentication clock skew leeway in seconds.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth_leeway: Option<u64>,
+
     /// File descriptor binding feature.
     pub fd: Option<usize>,
 
+    /// Unix domain socket file path.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket: Option<PathBuf>,
+    /// Unix domain socket file mode (octal).
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket_mode: Option<String>,
+    /// Unix domain socket file owner.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket_owner: Option<String>,
+
+    /// PROXY protocol header decoding.
+    pub proxy_protocol: Option<bool>,
+    /// CIDRs of the load balancers allowed to send a PROXY protocol header.
+    pub proxy_protocol_trusted_cidrs: Option<Vec<IpNet>>,
+
     /// Worker threads.
     pub threads_multiplier: Option<usize>,
 
@@ -363,6 +622,48 @@ pub struct General {
     /// Trusted IPs for remote addresses.
     pub trusted_proxies: Option<Vec<IpAddr>>,
 
+    /// Header the client IP is taken from behind a trusted proxy.
+    pub trusted_proxies_header: Option<ForwardedHeader>,
+
+    /// Request ID feature.
+    pub request_id: Option<bool>,
+
+    /// Request ID header name.
+    pub request_id_header: Option<String>,
+
+    #[cfg(feature = "otlp")]
+    /// OpenTelemetry collector URL.
+    pub otlp_endpoint: Option<String>,
+
+    #[cfg(feature = "otlp")]
+    /// OTLP transport protocol.
+    pub otlp_protocol: Option<OtlpProtocol>,
+
+    #[cfg(feature = "otlp")]
+    /// Service name of the exported traces.
+    pub otlp_service_name: Option<String>,
+
+    /// Access log feature.
+    pub access_log: Option<bool>,
+
+    /// Access log entries format.
+    pub access_log_format: Option<String>,
+
+    /// Access log file path.
+    pub access_log_file: Option<PathBuf>,
+
+    /// Access log file rotation size in bytes.
+    pub access_log_rotate_size: Option<u64>,
+
+    /// Access log file rotation interval.
+    pub access_log_rotate_interval: Option<RotateInterval>,
+
+    /// Number of rotated access log files to keep.
+    pub access_log_rotate_keep: Option<usize>,
+
+    /// Compress the rotated access log files.
+    pub access_log_rotate_compress: Option<bool>,
+
     /// Redirect trailing slash feature.
     pub redirect_trailing_slash: Option<bool>,
 
@@ -372,13 +673,42 @@ pub struct General {
     /// Prevent following symbolic links of files or directories.
     pub disable_symlinks: Option<bool>,
 
+    /// The `ETag` generation mode.
+    pub etag: Option<ETagMode>,
+
     /// Health endpoint feature.
     pub health: Option<bool>,
 
+    #[cfg(feature = "metrics")]
+    /// Metrics endpoint feature.
+    pub metrics: Option<bool>,
+
     #[cfg(all(unix, feature = "experimental"))]
-    /// Metrics endpoint feature (experimental).
+    /// Tokio runtime metrics feature (experimental).
     pub experimental_metrics: Option<bool>,
 
+    /// Admin listener port.
+    pub admin_port: Option<u16>,
+
+    /// Admin listener host address.
+    pub admin_host: Option<String>,
+
+    /// Health endpoint path on the admin listener.
+    pub admin_health_path: Option<String>,
+
+    /// Readiness endpoint path on the admin listener.
+    pub admin_ready_path: Option<String>,
+
+    /// Maintenance mode endpoint path on the admin listener.
+    pub admin_maintenance_path: Option<String>,
+
+    #[cfg(feature = "metrics")]
+    /// Metrics endpoint path on the admin listener.
+    pub admin_metrics_path: Option<String>,
+
+    /// Admin listener authentication token.
+    pub admin_auth_token: Option<String>,
+
     /// Maintenance mode feature.
     pub maintenance_mode: Option<bool>,
 
@@ -388,6 +718,33 @@ pub struct General {
     /// Custom maintenance mode HTML file.
     pub maintenance_mode_file: Option<PathBuf>,
 
+    /// File enabling the maintenance mode while it exists.
+    pub maintenance_mode_trigger_file: Option<PathBuf>,
+
+    /// Hosts the maintenance mode is scoped to.
+    pub maintenance_mode_hosts: Option<Vec<String>>,
+
+    /// Path glob patterns the maintenance mode is scoped to.
+    pub maintenance_mode_paths: Option<Vec<String>>,
+
+    /// Client IPs or CIDRs allowed to bypass the maintenance mode.
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub maintenance_mode_allow_ips: Option<Vec<IpNet>>,
+
+    /// Shared secret verifying the signed URLs.
+    pub signed_urls_secret: Option<String>,
+
+    /// Path glob patterns requiring a signed URL.
+    pub signed_urls_paths: Option<Vec<String>>,
+
+    /// Client IPs or CIDRs allowed to access the server.
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub allow_ips: Option<Vec<IpNet>>,
+
+    /// Client IPs or CIDRs denied access to the server.
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub deny_ips: Option<Vec<IpNet>>,
+
     #[cfg(feature = "experimental")]
     /// In-memory files cache feature.
     pub memory_cache: Option<bool>,
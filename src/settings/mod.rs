@@ -10,11 +10,15 @@ use clap::Parser;
 use globset::{Glob, GlobBuilder, GlobMatcher};
 use headers::HeaderMap;
 use hyper::StatusCode;
+use ipnet::IpNet;
 use regex::Regex;
 use std::path::{Path, PathBuf};
 
 use crate::{helpers, logger, Context, Result};
 
+#[cfg(feature = "otlp")]
+use crate::otlp;
+
 pub mod cli;
 #[doc(hidden)]
 pub mod cli_output;
@@ -26,6 +30,8 @@ use cli::General;
 
 #[cfg(feature = "experimental")]
 use self::file::MemoryCache;
+#[cfg(feature = "http2")]
+use crate::mtls::ClientAuthMode;
 
 use self::file::{RedirectsKind, Settings as FileSettings};
 
@@ -74,6 +80,104 @@ pub struct VirtualHosts {
     pub host: String,
     /// The root directory for this virtual host
     pub root: PathBuf,
+    /// Optional TLS certificate and private key file paths selected via SNI
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_cert_key: Option<(PathBuf, PathBuf)>,
+}
+
+/// The `ClientCertAuth` file options.
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub struct ClientCertAuth {
+    /// Source pattern glob matcher of the request paths
+    pub source: GlobMatcher,
+    /// Glob matchers of the allowed certificate subjects, any verified certificate if empty
+    pub subjects: Vec<GlobMatcher>,
+}
+
+/// The `Auth` file options.
+#[cfg(feature = "basic-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+pub struct Auth {
+    /// Source pattern glob matcher of the request paths
+    pub source: GlobMatcher,
+    /// Optional host to match against the "Host" header
+    pub host: Option<String>,
+    /// Users allowed to access
+    pub users: Vec<String>,
+    /// Groups whose users are allowed to access
+    pub groups: Vec<String>,
+    /// Whether the matching requests don't require authentication
+    pub public: bool,
+    /// JWT claims and their accepted values
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub claims: Vec<(String, Vec<String>)>,
+}
+
+/// The `IpAccess` file options.
+pub struct IpAccess {
+    /// Source pattern glob matcher of the request paths
+    pub source: GlobMatcher,
+    /// Optional host to match against the "Host" header
+    pub host: Option<String>,
+    /// Client IPs or CIDRs allowed to access, all if empty
+    pub allow: Vec<IpNet>,
+    /// Client IPs or CIDRs denied access
+    pub deny: Vec<IpNet>,
+}
+
+/// The `Listeners` file options.
+pub struct Listeners {
+    /// The host address to bind to
+    pub host: String,
+    /// The port to bind to
+    pub port: u16,
+    /// An inherited file descriptor to listen on instead
+    pub fd: Option<usize>,
+    /// A Unix domain socket file path to listen on instead
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket: Option<PathBuf>,
+    /// Unix domain socket file mode (octal)
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket_mode: Option<String>,
+    /// Unix domain socket file owner
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub unix_socket_owner: Option<String>,
+    /// Accept only IPv6 connections on an IPv6 address
+    pub ipv6_only: bool,
+    /// PROXY protocol header decoding
+    pub proxy_protocol: bool,
+    /// CIDRs of the load balancers allowed to send a PROXY protocol header
+    pub proxy_protocol_trusted_cidrs: Vec<IpNet>,
+    /// HTTP/2 support
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2: bool,
+    /// TLS (HTTPS) support
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls: bool,
+    /// TLS certificate file path
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_cert: Option<PathBuf>,
+    /// TLS private key file path
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_key: Option<PathBuf>,
+    /// TLS client certificate authentication mode
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_client_auth: ClientAuthMode,
+    /// TLS client CA certificates bundle file path
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_client_ca: Option<PathBuf>,
 }
 
 /// The `advanced` file options.
@@ -87,6 +191,18 @@ pub struct Advanced {
     pub redirects: Option<Vec<Redirects>>,
     /// Name-based virtual hosting
     pub virtual_hosts: Option<Vec<VirtualHosts>>,
+    /// TLS client certificate authorization rules.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub client_cert_auth: Option<Vec<ClientCertAuth>>,
+    /// Authentication rules.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub auth: Option<Vec<Auth>>,
+    /// IP access control rules.
+    pub ip_access: Option<Vec<IpAccess>>,
+    /// Additional listeners.
+    pub listeners: Option<Vec<Listeners>>,
     #[cfg(feature = "experimental")]
     /// In-memory cache feature (experimental).
     pub memory_cache: Option<MemoryCache>,
@@ -127,6 +243,10 @@ impl Settings {
         let mut port = opts.port;
         let mut root = opts.root;
         let mut log_level = opts.log_level;
+        let mut log_format = opts.log_format;
+        let mut log_filter = opts.log_filter;
+        let mut log_output = opts.log_output;
+        let mut log_syslog_socket = opts.log_syslog_socket;
         let mut config_file = opts.config_file.clone();
         let mut cache_control_headers = opts.cache_control_headers;
 
@@ -165,6 +285,18 @@ impl Settings {
         #[cfg(feature = "http2")]
         let mut http2_tls_key = opts.http2_tls_key;
         #[cfg(feature = "http2")]
+        let mut tls = opts.tls;
+        #[cfg(feature = "http2")]
+        let mut tls_cert = opts.tls_cert;
+        #[cfg(feature = "http2")]
+        let mut tls_key = opts.tls_key;
+        #[cfg(feature = "http2")]
+        let mut tls_watch_interval = opts.tls_watch_interval;
+        #[cfg(feature = "http2")]
+        let mut tls_client_auth = opts.tls_client_auth;
+        #[cfg(feature = "http2")]
+        let mut tls_client_ca = opts.tls_client_ca;
+        #[cfg(feature = "http2")]
         let mut https_redirect = opts.https_redirect;
         #[cfg(feature = "http2")]
         let mut https_redirect_host = opts.https_redirect_host;
@@ -187,8 +319,38 @@ impl Settings {
 
         #[cfg(feature = "basic-auth")]
         let mut basic_auth = opts.basic_auth;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_file = opts.basic_auth_file;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_groups_file = opts.basic_auth_groups_file;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_groups = opts.basic_auth_groups;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_realm = opts.basic_auth_realm;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_secret = opts.jwt_auth_secret;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_public_key = opts.jwt_auth_public_key;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_jwks_file = opts.jwt_auth_jwks_file;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_cookie = opts.jwt_auth_cookie;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_audience = opts.jwt_auth_audience;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_issuer = opts.jwt_auth_issuer;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_auth_leeway = opts.jwt_auth_leeway;
 
         let mut fd = opts.fd;
+        #[cfg(unix)]
+        let mut unix_socket = opts.unix_socket;
+        #[cfg(unix)]
+        let mut unix_socket_mode = opts.unix_socket_mode;
+        #[cfg(unix)]
+        let mut unix_socket_owner = opts.unix_socket_owner;
+        let mut proxy_protocol = opts.proxy_protocol;
+        let mut proxy_protocol_trusted_cidrs = opts.proxy_protocol_trusted_cidrs;
         let mut threads_multiplier = opts.threads_multiplier;
         let mut max_blocking_threads = opts.max_blocking_threads;
         let mut grace_period = opts.grace_period;
@@ -199,18 +361,55 @@ impl Settings {
         let mut log_remote_address = opts.log_remote_address;
         let mut log_forwarded_for = opts.log_forwarded_for;
         let mut trusted_proxies = opts.trusted_proxies;
+        let mut trusted_proxies_header = opts.trusted_proxies_header;
+        let mut request_id = opts.request_id;
+        let mut request_id_header = opts.request_id_header;
+        #[cfg(feature = "otlp")]
+        let mut otlp_endpoint = opts.otlp_endpoint;
+        #[cfg(feature = "otlp")]
+        let mut otlp_protocol = opts.otlp_protocol;
+        #[cfg(feature = "otlp")]
+        let mut otlp_service_name = opts.otlp_service_name;
+        let mut access_log = opts.access_log;
+        let mut access_log_format = opts.access_log_format;
+        let mut access_log_file = opts.access_log_file;
+        let mut access_log_rotate_size = opts.access_log_rotate_size;
+        let mut access_log_rotate_interval = opts.access_log_rotate_interval;
+        let mut access_log_rotate_keep = opts.access_log_rotate_keep;
+        let mut access_log_rotate_compress = opts.access_log_rotate_compress;
         let mut redirect_trailing_slash = opts.redirect_trailing_slash;
         let mut ignore_hidden_files = opts.ignore_hidden_files;
         let mut disable_symlinks = opts.disable_symlinks;
+        let mut etag = opts.etag;
         let mut index_files = opts.index_files;
         let mut health = opts.health;
 
+        #[cfg(feature = "metrics")]
+        let mut metrics = opts.metrics;
+
         #[cfg(all(unix, feature = "experimental"))]
         let mut experimental_metrics = opts.experimental_metrics;
 
+        let mut admin_port = opts.admin_port;
+        let mut admin_host = opts.admin_host;
+        let mut admin_health_path = opts.admin_health_path;
+        let mut admin_ready_path = opts.admin_ready_path;
+        let mut admin_maintenance_path = opts.admin_maintenance_path;
+        #[cfg(feature = "metrics")]
+        let mut admin_metrics_path = opts.admin_metrics_path;
+        let mut admin_auth_token = opts.admin_auth_token;
+
         let mut maintenance_mode = opts.maintenance_mode;
         let mut maintenance_mode_status = opts.maintenance_mode_status;
         let mut maintenance_mode_file = opts.maintenance_mode_file;
+        let mut maintenance_mode_trigger_file = opts.maintenance_mode_trigger_file;
+        let mut maintenance_mode_hosts = opts.maintenance_mode_hosts;
+        let mut maintenance_mode_paths = opts.maintenance_mode_paths;
+        let mut maintenance_mode_allow_ips = opts.maintenance_mode_allow_ips;
+        let mut signed_urls_secret = opts.signed_urls_secret;
+        let mut signed_urls_paths = opts.signed_urls_paths;
+        let mut allow_ips = opts.allow_ips;
+        let mut deny_ips = opts.deny_ips;
 
         // Windows-only options
         #[cfg(windows)]
@@ -241,6 +440,18 @@ impl Settings {
                 if let Some(ref v) = general.log_level {
                     log_level = v.name().to_lowercase();
                 }
+                if let Some(v) = general.log_format {
+                    log_format = v
+                }
+                if let Some(v) = general.log_filter {
+                    log_filter = v
+                }
+                if let Some(v) = general.log_output {
+                    log_output = v
+                }
+                if let Some(v) = general.log_syslog_socket {
+                    log_syslog_socket = v
+                }
                 if let Some(v) = general.cache_control_headers {
                     cache_control_headers = v
                 }
@@ -293,6 +504,30 @@ impl Settings {
                     http2_tls_key = Some(v)
                 }
                 #[cfg(feature = "http2")]
+                if let Some(v) = general.tls {
+                    tls = Some(v)
+                }
+                #[cfg(feature = "http2")]
+                if let Some(v) = general.tls_cert {
+                    tls_cert = Some(v)
+                }
+                #[cfg(feature = "http2")]
+                if let Some(v) = general.tls_key {
+                    tls_key = Some(v)
+                }
+                #[cfg(feature = "http2")]
+                if let Some(v) = general.tls_watch_interval {
+                    tls_watch_interval = v
+                }
+                #[cfg(feature = "http2")]
+                if let Some(v) = general.tls_client_auth {
+                    tls_client_auth = v
+                }
+                #[cfg(feature = "http2")]
+                if let Some(v) = general.tls_client_ca {
+                    tls_client_ca = Some(v)
+                }
+                #[cfg(feature = "http2")]
                 if let Some(v) = general.https_redirect {
                     https_redirect = v
                 }
@@ -312,7 +547,8 @@ impl Settings {
                 match general.security_headers {
                     Some(v) => security_headers = v,
                     _ => {
-                        if http2 {
+                        // HTTP/2 implies TLS unless disabled, e.g. for cleartext h2c
+                        if tls.unwrap_or(http2) {
                             security_headers = true;
                         }
                     }
@@ -346,9 +582,71 @@ impl Settings {
                 if let Some(ref v) = general.basic_auth {
                     v.clone_into(&mut basic_auth)
                 }
+                #[cfg(feature = "basic-auth")]
+                if let Some(v) = general.basic_auth_file {
+                    basic_auth_file = Some(v)
+                }
+                #[cfg(feature = "basic-auth")]
+                if let Some(v) = general.basic_auth_groups_file {
+                    basic_auth_groups_file = Some(v)
+                }
+                #[cfg(feature = "basic-auth")]
+                if let Some(v) = general.basic_auth_groups {
+                    basic_auth_groups = v
+                }
+                #[cfg(feature = "basic-auth")]
+                if let Some(v) = general.basic_auth_realm {
+                    basic_auth_realm = v
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_secret {
+                    jwt_auth_secret = Some(v)
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_public_key {
+                    jwt_auth_public_key = Some(v)
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_jwks_file {
+                    jwt_auth_jwks_file = Some(v)
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_cookie {
+                    jwt_auth_cookie = Some(v)
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_audience {
+                    jwt_auth_audience = v
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_issuer {
+                    jwt_auth_issuer = v
+                }
+                #[cfg(feature = "jwt-auth")]
+                if let Some(v) = general.jwt_auth_leeway {
+                    jwt_auth_leeway = v
+                }
                 if let Some(v) = general.fd {
                     fd = Some(v)
                 }
+                #[cfg(unix)]
+                if let Some(v) = general.unix_socket {
+                    unix_socket = Some(v)
+                }
+                #[cfg(unix)]
+                if let Some(v) = general.unix_socket_mode {
+                    unix_socket_mode = Some(v)
+                }
+                #[cfg(unix)]
+                if let Some(v) = general.unix_socket_owner {
+                    unix_socket_owner = Some(v)
+                }
+                if let Some(v) = general.proxy_protocol {
+                    proxy_protocol = v
+                }
+                if let Some(v) = general.proxy_protocol_trusted_cidrs {
+                    proxy_protocol_trusted_cidrs = v
+                }
                 if let Some(v) = general.threads_multiplier {
                     threads_multiplier = v
                 }
@@ -371,6 +669,48 @@ impl Settings {
                 if let Some(v) = general.trusted_proxies {
                     trusted_proxies = v
                 }
+                if let Some(v) = general.trusted_proxies_header {
+                    trusted_proxies_header = v
+                }
+                if let Some(v) = general.request_id {
+                    request_id = v
+                }
+                if let Some(v) = general.request_id_header {
+                    request_id_header = v
+                }
+                #[cfg(feature = "otlp")]
+                if let Some(v) = general.otlp_endpoint {
+                    otlp_endpoint = Some(v)
+                }
+                #[cfg(feature = "otlp")]
+                if let Some(v) = general.otlp_protocol {
+                    otlp_protocol = v
+                }
+                #[cfg(feature = "otlp")]
+                if let Some(v) = general.otlp_service_name {
+                    otlp_service_name = v
+                }
+                if let Some(v) = general.access_log {
+                    access_log = v
+                }
+                if let Some(v) = general.access_log_format {
+                    access_log_format = v
+                }
+                if let Some(v) = general.access_log_file {
+                    access_log_file = Some(v)
+                }
+                if let Some(v) = general.access_log_rotate_size {
+                    access_log_rotate_size = v
+                }
+                if let Some(v) = general.access_log_rotate_interval {
+                    access_log_rotate_interval = v
+                }
+                if let Some(v) = general.access_log_rotate_keep {
+                    access_log_rotate_keep = v
+                }
+                if let Some(v) = general.access_log_rotate_compress {
+                    access_log_rotate_compress = v
+                }
                 if let Some(v) = general.redirect_trailing_slash {
                     redirect_trailing_slash = v
                 }
@@ -380,13 +720,42 @@ impl Settings {
                 if let Some(v) = general.disable_symlinks {
                     disable_symlinks = v
                 }
+                if let Some(v) = general.etag {
+                    etag = v
+                }
                 if let Some(v) = general.health {
                     health = v
                 }
+                #[cfg(feature = "metrics")]
+                if let Some(v) = general.metrics {
+                    metrics = v
+                }
                 #[cfg(all(unix, feature = "experimental"))]
                 if let Some(v) = general.experimental_metrics {
                     experimental_metrics = v
                 }
+                if let Some(v) = general.admin_port {
+                    admin_port = Some(v)
+                }
+                if let Some(v) = general.admin_host {
+                    admin_host = v
+                }
+                if let Some(v) = general.admin_health_path {
+                    admin_health_path = v
+                }
+                if let Some(v) = general.admin_ready_path {
+                    admin_ready_path = v
+                }
+                if let Some(v) = general.admin_maintenance_path {
+                    admin_maintenance_path = v
+                }
+                #[cfg(feature = "metrics")]
+                if let Some(v) = general.admin_metrics_path {
+                    admin_metrics_path = v
+                }
+                if let Some(v) = general.admin_auth_token {
+                    admin_auth_token = Some(v)
+                }
                 if let Some(v) = general.index_files {
                     index_files = v
                 }
@@ -400,6 +769,30 @@ impl Settings {
                 if let Some(v) = general.maintenance_mode_file {
                     maintenance_mode_file = v
                 }
+                if let Some(v) = general.maintenance_mode_trigger_file {
+                    maintenance_mode_trigger_file = Some(v)
+                }
+                if let Some(v) = general.maintenance_mode_hosts {
+                    maintenance_mode_hosts = v
+                }
+                if let Some(v) = general.maintenance_mode_paths {
+                    maintenance_mode_paths = v
+                }
+                if let Some(v) = general.maintenance_mode_allow_ips {
+                    maintenance_mode_allow_ips = v
+                }
+                if let Some(ref v) = general.signed_urls_secret {
+                    signed_urls_secret = Some(v.to_owned())
+                }
+                if let Some(v) = general.signed_urls_paths {
+                    signed_urls_paths = v
+                }
+                if let Some(v) = general.allow_ips {
+                    allow_ips = v
+                }
+                if let Some(v) = general.deny_ips {
+                    deny_ips = v
+                }
 
                 // Windows-only options
                 #[cfg(windows)]
@@ -410,7 +803,15 @@ impl Settings {
 
             // Logging system initialization in config file context
             if log_init {
-                logger::init(log_level.as_str())?;
+                #[cfg(feature = "otlp")]
+                otlp::init_exporter(otlp_endpoint.as_deref(), otlp_protocol, &otlp_service_name)?;
+                logger::init(
+                    log_level.as_str(),
+                    log_format,
+                    &log_filter,
+                    log_output,
+                    &log_syslog_socket,
+                )?;
             }
 
             tracing::debug!("config file read successfully");
@@ -570,9 +971,26 @@ impl Settings {
                                     vhosts_entry.host,
                                     root_dir.display()
                                 );
+
+                                // Optional TLS certificate for the virtual host
+                                #[cfg(feature = "http2")]
+                                let tls_cert_key = match (
+                                    vhosts_entry.tls_cert.to_owned(),
+                                    vhosts_entry.tls_key.to_owned(),
+                                ) {
+                                    (Some(cert), Some(key)) => Some((cert, key)),
+                                    (None, None) => None,
+                                    _ => bail!(
+                                        "both tls-cert and tls-key are required for virtual host {}",
+                                        vhosts_entry.host
+                                    ),
+                                };
+
                                 vhosts_vec.push(VirtualHosts {
                                     host: vhosts_entry.host.to_owned(),
                                     root: root_dir,
+                                    #[cfg(feature = "http2")]
+                                    tls_cert_key,
                                 });
                             }
                         }
@@ -581,18 +999,211 @@ impl Settings {
                     _ => None,
                 };
 
+                // 4. TLS client certificate authorization rules assignment
+                #[cfg(feature = "http2")]
+                let client_cert_auth_entries = match advanced.client_cert_auth {
+                    Some(entries) => {
+                        let mut rules_vec: Vec<ClientCertAuth> = Vec::new();
+
+                        for entry in entries.iter() {
+                            let source = Glob::new(&entry.source)
+                                .with_context(|| {
+                                    format!(
+                                        "can not compile glob pattern for client-cert-auth source: {}",
+                                        &entry.source
+                                    )
+                                })?
+                                .compile_matcher();
+
+                            let mut subjects = Vec::new();
+                            for subject in entry.subjects.iter().flatten() {
+                                let subject = Glob::new(subject)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for client-cert-auth subject: {subject}"
+                                        )
+                                    })?
+                                    .compile_matcher();
+                                subjects.push(subject);
+                            }
+
+                            rules_vec.push(ClientCertAuth { source, subjects });
+                        }
+                        Some(rules_vec)
+                    }
+                    _ => None,
+                };
+
+                // 5. Authentication rules assignment
+                #[cfg(feature = "basic-auth")]
+                let auth_entries = match advanced.auth {
+                    Some(entries) => {
+                        let mut rules_vec: Vec<Auth> = Vec::new();
+
+                        for entry in entries.iter() {
+                            let source = Glob::new(&entry.source)
+                                .with_context(|| {
+                                    format!(
+                                        "can not compile glob pattern for auth source: {}",
+                                        &entry.source
+                                    )
+                                })?
+                                .compile_matcher();
+
+                            let public = entry.public.unwrap_or_default();
+                            #[cfg(feature = "jwt-auth")]
+                            let has_claims = entry.claims.is_some();
+                            #[cfg(not(feature = "jwt-auth"))]
+                            let has_claims = false;
+                            if public
+                                && (entry.users.is_some() || entry.groups.is_some() || has_claims)
+                            {
+                                bail!(
+                                    "public auth rule {} can not define users, groups or claims",
+                                    &entry.source
+                                )
+                            }
+
+                            rules_vec.push(Auth {
+                                source,
+                                host: entry.host.clone(),
+                                users: entry.users.clone().unwrap_or_default(),
+                                groups: entry.groups.clone().unwrap_or_default(),
+                                public,
+                                #[cfg(feature = "jwt-auth")]
+                                claims: entry
+                                    .claims
+                                    .clone()
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .collect(),
+                            });
+                        }
+                        Some(rules_vec)
+                    }
+                    _ => None,
+                };
+
+                // 6. IP access control rules assignment
+                let ip_access_entries = match advanced.ip_access {
+                    Some(entries) => {
+                        let mut rules_vec: Vec<IpAccess> = Vec::new();
+
+                        for entry in entries.iter() {
+                            let source = Glob::new(&entry.source)
+                                .with_context(|| {
+                                    format!(
+                                        "can not compile glob pattern for ip access source: {}",
+                                        &entry.source
+                                    )
+                                })?
+                                .compile_matcher();
+
+                            rules_vec.push(IpAccess {
+                                source,
+                                host: entry.host.clone(),
+                                allow: entry.allow.clone().unwrap_or_default(),
+                                deny: entry.deny.clone().unwrap_or_default(),
+                            });
+                        }
+                        Some(rules_vec)
+                    }
+                    _ => None,
+                };
+
+                // 7. Additional listeners assignment
+                let listeners_entries = match advanced.listeners {
+                    Some(entries) => {
+                        let mut listeners_vec: Vec<Listeners> = Vec::new();
+
+                        for entry in entries.iter() {
+                            #[cfg(unix)]
+                            let unix_socket_entry = entry.unix_socket.is_some();
+                            #[cfg(not(unix))]
+                            let unix_socket_entry = false;
+                            let addresses =
+                                [entry.port.is_some(), entry.fd.is_some(), unix_socket_entry];
+                            if addresses.iter().filter(|v| **v).count() != 1 {
+                                bail!("a listener requires exactly one of port, fd or unix-socket")
+                            }
+
+                            #[cfg(feature = "http2")]
+                            let listener_http2 = entry.http2.unwrap_or_default();
+
+                            listeners_vec.push(Listeners {
+                                host: entry.host.clone().unwrap_or_else(|| host.clone()),
+                                port: entry.port.unwrap_or(port),
+                                fd: entry.fd,
+                                #[cfg(unix)]
+                                unix_socket: entry.unix_socket.clone(),
+                                #[cfg(unix)]
+                                unix_socket_mode: entry.unix_socket_mode.clone(),
+                                #[cfg(unix)]
+                                unix_socket_owner: entry.unix_socket_owner.clone(),
+                                ipv6_only: entry.ipv6_only.unwrap_or_default(),
+                                proxy_protocol: entry.proxy_protocol.unwrap_or_default(),
+                                proxy_protocol_trusted_cidrs: entry
+                                    .proxy_protocol_trusted_cidrs
+                                    .clone()
+                                    .unwrap_or_else(|| proxy_protocol_trusted_cidrs.clone()),
+                                #[cfg(feature = "http2")]
+                                http2: listener_http2,
+                                #[cfg(feature = "http2")]
+                                tls: entry.tls.unwrap_or(listener_http2),
+                                #[cfg(feature = "http2")]
+                                tls_cert: entry
+                                    .tls_cert
+                                    .clone()
+                                    .or_else(|| tls_cert.clone())
+                                    .or_else(|| http2_tls_cert.clone()),
+                                #[cfg(feature = "http2")]
+                                tls_key: entry
+                                    .tls_key
+                                    .clone()
+                                    .or_else(|| tls_key.clone())
+                                    .or_else(|| http2_tls_key.clone()),
+                                #[cfg(feature = "http2")]
+                                tls_client_auth: entry
+                                    .tls_client_auth
+                                    .unwrap_or(ClientAuthMode::None),
+                                #[cfg(feature = "http2")]
+                                tls_client_ca: entry
+                                    .tls_client_ca
+                                    .clone()
+                                    .or_else(|| tls_client_ca.clone()),
+                            });
+                        }
+                        Some(listeners_vec)
+                    }
+                    _ => None,
+                };
+
                 settings_advanced = Some(Advanced {
                     headers: headers_entries,
                     rewrites: rewrites_entries,
                     redirects: redirects_entries,
                     virtual_hosts: vhosts_entries,
+                    #[cfg(feature = "http2")]
+                    client_cert_auth: client_cert_auth_entries,
+                    #[cfg(feature = "basic-auth")]
+                    auth: auth_entries,
+                    ip_access: ip_access_entries,
+                    listeners: listeners_entries,
                     #[cfg(feature = "experimental")]
                     memory_cache: advanced.memory_cache,
                 });
             }
         } else if log_init {
             // Logging system initialization on demand
-            logger::init(log_level.as_str())?;
+            #[cfg(feature = "otlp")]
+            otlp::init_exporter(otlp_endpoint.as_deref(), otlp_protocol, &otlp_service_name)?;
+            logger::init(
+                log_level.as_str(),
+                log_format,
+                &log_filter,
+                log_output,
+                &log_syslog_socket,
+            )?;
         }
 
         Ok(Settings {
@@ -602,6 +1213,10 @@ impl Settings {
                 port,
                 root,
                 log_level,
+                log_format,
+                log_filter,
+                log_output,
+                log_syslog_socket,
                 config_file,
                 cache_control_headers,
                 #[cfg(any(
@@ -637,6 +1252,18 @@ impl Settings {
                 #[cfg(feature = "http2")]
                 http2_tls_key,
                 #[cfg(feature = "http2")]
+                tls,
+                #[cfg(feature = "http2")]
+                tls_cert,
+                #[cfg(feature = "http2")]
+                tls_key,
+                #[cfg(feature = "http2")]
+                tls_watch_interval,
+                #[cfg(feature = "http2")]
+                tls_client_auth,
+                #[cfg(feature = "http2")]
+                tls_client_ca,
+                #[cfg(feature = "http2")]
                 https_redirect,
                 #[cfg(feature = "http2")]
                 https_redirect_host,
@@ -656,7 +1283,37 @@ impl Settings {
                 directory_listing_format,
                 #[cfg(feature = "basic-auth")]
                 basic_auth,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_file,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_groups_file,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_groups,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_realm,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_secret,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_public_key,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_jwks_file,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_cookie,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_audience,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_issuer,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth_leeway,
                 fd,
+                #[cfg(unix)]
+                unix_socket,
+                #[cfg(unix)]
+                unix_socket_mode,
+                #[cfg(unix)]
+                unix_socket_owner,
+                proxy_protocol,
+                proxy_protocol_trusted_cidrs,
                 threads_multiplier,
                 max_blocking_threads,
                 grace_period,
@@ -665,16 +1322,51 @@ impl Settings {
                 log_remote_address,
                 log_forwarded_for,
                 trusted_proxies,
+                trusted_proxies_header,
+                request_id,
+                request_id_header,
+                #[cfg(feature = "otlp")]
+                otlp_endpoint,
+                #[cfg(feature = "otlp")]
+                otlp_protocol,
+                #[cfg(feature = "otlp")]
+                otlp_service_name,
+                access_log,
+                access_log_format,
+                access_log_file,
+                access_log_rotate_size,
+                access_log_rotate_interval,
+                access_log_rotate_keep,
+                access_log_rotate_compress,
                 redirect_trailing_slash,
                 ignore_hidden_files,
                 disable_symlinks,
+                etag,
                 index_files,
                 health,
+                #[cfg(feature = "metrics")]
+                metrics,
                 #[cfg(all(unix, feature = "experimental"))]
                 experimental_metrics,
+                admin_port,
+                admin_host,
+                admin_health_path,
+                admin_ready_path,
+                admin_maintenance_path,
+                #[cfg(feature = "metrics")]
+                admin_metrics_path,
+                admin_auth_token,
                 maintenance_mode,
                 maintenance_mode_status,
                 maintenance_mode_file,
+                maintenance_mode_trigger_file,
+                maintenance_mode_hosts,
+                maintenance_mode_paths,
+                maintenance_mode_allow_ips,
+                signed_urls_secret,
+                signed_urls_paths,
+                allow_ips,
+                deny_ips,
 
                 // Windows-only options and commands
                 #[cfg(windows)]
@@ -10,6 +10,8 @@ use std::sync::Arc;
 use tokio::sync::{watch::Receiver, Mutex};
 use tokio::time::{sleep, Duration};
 
+use crate::health::ServerState;
+
 #[cfg(unix)]
 use {
     crate::Result, futures_util::stream::StreamExt, signal_hook::consts::signal::*,
@@ -19,9 +21,20 @@ use {
 #[cfg(unix)]
 #[cfg_attr(docsrs, doc(cfg(unix)))]
 #[inline]
-/// It creates a common list of signals stream for `SIGTERM`, `SIGINT` and `SIGQUIT` to be observed.
+/// It creates a common list of signals stream for `SIGTERM`, `SIGINT` and `SIGQUIT` to be observed
+/// as well as `SIGUSR1` to reopen the access log files and `SIGUSR2` to toggle the maintenance mode.
 pub fn create_signals() -> Result<Signals> {
-    Ok(Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT])?)
+    Ok(Signals::new([
+        SIGHUP, SIGTERM, SIGINT, SIGQUIT, SIGUSR1, SIGUSR2,
+    ])?)
+}
+
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+#[inline]
+/// It creates a signals stream for `SIGHUP` to be observed by reloading tasks.
+pub fn create_reload_signals() -> Result<Signals> {
+    Ok(Signals::new([SIGHUP])?)
 }
 
 #[cfg(unix)]
@@ -39,8 +52,16 @@ pub async fn wait_for_signals(
         while let Some(signal) = signals.next().await {
             match signal {
                 SIGHUP => {
-                    // NOTE: for now we don't do something for SIGHUPs
-                    tracing::debug!("SIGHUP caught, nothing to do about")
+                    // NOTE: SIGHUPs are handled by the reloading tasks (if any)
+                    tracing::debug!("SIGHUP caught, nothing to do about on shutdown handling")
+                }
+                SIGUSR1 => {
+                    tracing::info!("SIGUSR1 caught, reopening the access log files");
+                    crate::access_log::reopen();
+                }
+                SIGUSR2 => {
+                    tracing::info!("SIGUSR2 caught, toggling the maintenance mode");
+                    crate::maintenance_mode::toggle();
                 }
                 SIGTERM | SIGINT | SIGQUIT => {
                     tracing::info!("SIGTERM, SIGINT or SIGQUIT signal caught");
@@ -69,6 +90,8 @@ pub async fn wait_for_signals(
 
 /// Function intended to delay the server's graceful shutdown providing a grace period in seconds.
 async fn delay_graceful_shutdown(grace_period_secs: u8) {
+    // Report the server as not ready so it stops getting new traffic during the grace period
+    ServerState::ShuttingDown.set();
     if grace_period_secs > 0 {
         tracing::info!(
             "grace period of {}s after the SIGTERM started",
@@ -0,0 +1,347 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module verifying signed, expiring URLs.
+//!
+//! A signed URL carries its expiration time and an HMAC-SHA256 signature in its query,
+//! optionally bound to a client IP or to a path prefix instead of the exact path.
+//!
+
+use hmac::{Hmac, Mac};
+use hyper::{Body, Request, Response, StatusCode};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    error_page, handler::RequestHandlerOpts, helpers, log_addr, settings::cli::General, Error,
+    Result,
+};
+
+/// Query parameter holding the expiration time as a Unix timestamp in seconds.
+const EXPIRES_PARAM: &str = "expires";
+/// Query parameter holding the client IP the URL is bound to.
+const IP_PARAM: &str = "ip";
+/// Query parameter holding the path prefix the URL is valid for.
+const PREFIX_PARAM: &str = "prefix";
+/// Query parameter holding the hex-encoded signature.
+const SIGNATURE_PARAM: &str = "signature";
+
+/// Characters of the signed path to be percent-encoded, keeping the unreserved ones and slashes.
+const PATH_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'_')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Marks a request whose signed URL was verified, so it's not verified again once rewritten.
+#[derive(Clone, Copy)]
+struct Verified;
+
+/// Initializes the signed URLs verification.
+pub(crate) fn init(general: &General, handler_opts: &mut RequestHandlerOpts) -> Result {
+    if general.signed_urls_secret.as_deref() == Some("") {
+        bail!("signed urls secret can not be empty")
+    }
+    handler_opts.signed_urls_secret = general.signed_urls_secret.clone();
+    handler_opts.signed_urls_paths =
+        helpers::compile_path_globs(&general.signed_urls_paths, "signed urls")?;
+
+    server_info!(
+        "signed urls: enabled={}",
+        handler_opts.signed_urls_secret.is_some()
+    );
+    if handler_opts.signed_urls_secret.is_some() && !general.signed_urls_paths.is_empty() {
+        server_info!("signed urls paths: {:?}", general.signed_urls_paths);
+    }
+    Ok(())
+}
+
+/// Returns the current Unix timestamp in seconds.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Signs a path, returning it percent-encoded along with the query verified by the server.
+///
+/// The URL expires at the `expires` Unix timestamp and can be bound to a client IP.
+/// If a `prefix` is given, the same query is valid for any path starting with it.
+pub fn sign_url(
+    secret: &str,
+    path: &str,
+    expires: u64,
+    ip: Option<IpAddr>,
+    prefix: Option<&str>,
+) -> Result<String> {
+    if secret.is_empty() {
+        bail!("signed urls secret is not defined, use the --secret or --signed-urls-secret options")
+    }
+    if !path.starts_with('/') {
+        bail!("the path to sign \"{path}\" must start with a slash")
+    }
+    if let Some(prefix) = prefix {
+        if !is_in_prefix(path, prefix) {
+            bail!("the path to sign \"{path}\" does not start with the prefix \"{prefix}\"")
+        }
+    }
+
+    let ip = ip.map(|ip| ip.to_string());
+    let signature = signer(secret, path, expires, ip.as_deref(), prefix)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    query.append_pair(EXPIRES_PARAM, &expires.to_string());
+    if let Some(ip) = &ip {
+        query.append_pair(IP_PARAM, ip);
+    }
+    if let Some(prefix) = prefix {
+        query.append_pair(PREFIX_PARAM, prefix);
+    }
+    query.append_pair(SIGNATURE_PARAM, &signature);
+
+    Ok(format!(
+        "{}?{}",
+        utf8_percent_encode(path, PATH_ENCODE_SET),
+        query.finish()
+    ))
+}
+
+/// Returns the HMAC fed with the signed fields of a URL.
+fn signer(
+    secret: &str,
+    path: &str,
+    expires: u64,
+    ip: Option<&str>,
+    prefix: Option<&str>,
+) -> HmacSha256 {
+    // Tell the exact path and prefix scopes apart so that a signature can't be reused for the other
+    let scope = match prefix {
+        Some(prefix) => format!("prefix:{prefix}"),
+        None => format!("path:{path}"),
+    };
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{expires}\n{scope}\n{}", ip.unwrap_or_default()).as_bytes());
+    mac
+}
+
+/// Checks whether a path starts with a prefix at a segment boundary, without parent directory segments.
+fn is_in_prefix(path: &str, prefix: &str) -> bool {
+    path.starts_with(prefix)
+        && (prefix.ends_with('/')
+            || path.len() == prefix.len()
+            || path[prefix.len()..].starts_with('/'))
+        && !path.split('/').any(|segment| segment == "..")
+}
+
+/// Decodes a hex string.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Verifies the signed URL of a normalized request path, returning the reason if it's not valid.
+fn verify(
+    secret: &str,
+    path: &str,
+    query: Option<&str>,
+    client_ip: Option<IpAddr>,
+    now: u64,
+) -> Result<(), &'static str> {
+    let (mut expires, mut ip, mut prefix, mut signature) = (None, None, None, None);
+    for (name, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        let param = match name.as_ref() {
+            EXPIRES_PARAM => &mut expires,
+            IP_PARAM => &mut ip,
+            PREFIX_PARAM => &mut prefix,
+            SIGNATURE_PARAM => &mut signature,
+            _ => continue,
+        };
+        param.get_or_insert(value);
+    }
+
+    let (Some(expires), Some(signature)) = (expires, signature) else {
+        return Err("missing signature");
+    };
+    let expires = expires
+        .parse::<u64>()
+        .map_err(|_| "invalid expiration time")?;
+    if now > expires {
+        return Err("expired");
+    }
+    if let Some(ip) = &ip {
+        if ip.parse::<IpAddr>().ok() != client_ip {
+            return Err("client IP mismatch");
+        }
+    }
+    if let Some(prefix) = &prefix {
+        if !is_in_prefix(path, prefix) {
+            return Err("path out of the signed prefix");
+        }
+    }
+
+    let signature = decode_hex(&signature).ok_or("invalid signature")?;
+    signer(secret, path, expires, ip.as_deref(), prefix.as_deref())
+        .verify_slice(&signature)
+        .map_err(|_| "invalid signature")
+}
+
+/// Rejects the requests in scope without a valid signed URL.
+pub(crate) fn pre_process<T>(
+    opts: &RequestHandlerOpts,
+    req: &mut Request<T>,
+    remote_addr: Option<SocketAddr>,
+) -> Option<Result<Response<Body>, Error>> {
+    let secret = opts.signed_urls_secret.as_deref()?;
+
+    // A verified request is not verified again once rewritten
+    if req.extensions().get::<Verified>().is_some() {
+        return None;
+    }
+
+    // Match the normalized path so that equivalent paths can't bypass the scope
+    let uri = req.uri();
+    let status = match helpers::normalize_request_path(uri.path()) {
+        Ok(path) => {
+            if let Some(paths) = &opts.signed_urls_paths {
+                if !paths.is_match(&path) {
+                    return None;
+                }
+            }
+            let client_ip = log_addr::client_ip(opts, req, remote_addr);
+            let reason = match verify(secret, &path, uri.query(), client_ip, unix_time()) {
+                Ok(()) => {
+                    req.extensions_mut().insert(Verified);
+                    return None;
+                }
+                Err(reason) => reason,
+            };
+            tracing::debug!("signed url rejected ({reason}): {uri}");
+            StatusCode::FORBIDDEN
+        }
+        Err(status) => status,
+    };
+
+    Some(error_page::error_response(
+        uri,
+        req.method(),
+        &status,
+        &opts.page404,
+        &opts.page50x,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use percent_encoding::percent_decode_str;
+
+    const SECRET: &str = "s3cr3t";
+
+    fn check(url: &str, client_ip: Option<IpAddr>, now: u64) -> Result<(), &'static str> {
+        let (path, query) = url.split_once('?').unwrap();
+        let path = percent_decode_str(path).decode_utf8_lossy();
+        verify(SECRET, &path, Some(query), client_ip, now)
+    }
+
+    #[test]
+    fn test_signed_url() {
+        let url = sign_url(SECRET, "/downloads/my report.pdf", 1000, None, None).unwrap();
+        assert!(url.starts_with("/downloads/my%20report.pdf?expires=1000&signature="));
+
+        assert_eq!(check(&url, None, 1000), Ok(()));
+        assert_eq!(check(&url, None, 1001), Err("expired"));
+        assert_eq!(
+            check(&url.replace("report", "invoice"), None, 0),
+            Err("invalid signature")
+        );
+        assert_eq!(
+            check(&url.replace("expires=1000", "expires=2000"), None, 0),
+            Err("invalid signature")
+        );
+        assert_eq!(
+            verify(
+                "other",
+                "/downloads/my report.pdf",
+                url.split_once('?').map(|(_, q)| q),
+                None,
+                0
+            ),
+            Err("invalid signature")
+        );
+        assert_eq!(
+            check("/downloads/my%20report.pdf?expires=1000", None, 0),
+            Err("missing signature")
+        );
+    }
+
+    #[test]
+    fn test_signed_url_ip() {
+        let ip = "203.0.113.7".parse().ok();
+        let url = sign_url(SECRET, "/file.zip", 1000, ip, None).unwrap();
+        assert_eq!(check(&url, ip, 0), Ok(()));
+        assert_eq!(
+            check(&url, "203.0.113.8".parse().ok(), 0),
+            Err("client IP mismatch")
+        );
+        assert_eq!(check(&url, None, 0), Err("client IP mismatch"));
+        assert_eq!(
+            check(&url.replace("&ip=203.0.113.7", ""), ip, 0),
+            Err("invalid signature")
+        );
+    }
+
+    #[test]
+    fn test_signed_url_prefix() {
+        let url = sign_url(SECRET, "/downloads/a.pdf", 1000, None, Some("/downloads")).unwrap();
+        let query = url.split_once('?').unwrap().1;
+        assert_eq!(check(&url, None, 0), Ok(()));
+        assert_eq!(
+            check(&format!("/downloads/b/c.pdf?{query}"), None, 0),
+            Ok(())
+        );
+        assert_eq!(
+            check(&format!("/downloads-private/a.pdf?{query}"), None, 0),
+            Err("path out of the signed prefix")
+        );
+        assert_eq!(
+            check(&format!("/downloads/../secret.txt?{query}"), None, 0),
+            Err("path out of the signed prefix")
+        );
+
+        // An exact path signature can't be used as a prefix one
+        let url = sign_url(SECRET, "/downloads", 1000, None, None).unwrap();
+        assert_eq!(
+            check(
+                &format!(
+                    "/downloads/b.pdf?{}&prefix=/downloads",
+                    url.split_once('?').unwrap().1
+                ),
+                None,
+                0
+            ),
+            Err("invalid signature")
+        );
+
+        assert!(sign_url(SECRET, "/other/a.pdf", 1000, None, Some("/downloads/")).is_err());
+        assert!(sign_url("", "/a.pdf", 1000, None, None).is_err());
+        assert!(sign_url(SECRET, "a.pdf", 1000, None, None).is_err());
+    }
+}
@@ -16,10 +16,11 @@ use std::io;
 use std::path::PathBuf;
 
 use crate::conditional_headers::ConditionalHeaders;
+use crate::etag::ETagMode;
 use crate::fs::meta::{try_metadata, try_metadata_with_html_suffix, FileMetadata};
 use crate::fs::path::{sanitize_path, PathExt};
 use crate::http_ext::{MethodExt, HTTP_SUPPORTED_METHODS};
-use crate::response::response_body;
+use crate::response::{file_etag, response_body};
 use crate::Result;
 
 #[cfg(feature = "experimental")]
@@ -79,6 +80,8 @@ pub struct HandleOpts<'a> {
     pub ignore_hidden_files: bool,
     /// Prevent following symlinks for files and directories.
     pub disable_symlinks: bool,
+    /// The `ETag` generation mode.
+    pub etag: ETagMode,
 }
 
 /// Static file response type with additional data.
@@ -184,15 +187,17 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<StaticFileResponse, Sta
     // if it does not contain an `index.html` file (if a proper auto index is generated)
     #[cfg(feature = "directory-listing")]
     if is_dir && opts.dir_listing && !file_path.exists() {
-        let resp = directory_listing::auto_index(DirListOpts {
-            method,
-            current_path: uri_path,
-            uri_query: opts.uri_query,
-            filepath: file_path,
-            dir_listing_order: opts.dir_listing_order,
-            dir_listing_format: opts.dir_listing_format,
-            ignore_hidden_files: opts.ignore_hidden_files,
-            disable_symlinks: opts.disable_symlinks,
+        let resp = tracing::debug_span!("directory_listing").in_scope(|| {
+            directory_listing::auto_index(DirListOpts {
+                method,
+                current_path: uri_path,
+                uri_query: opts.uri_query,
+                filepath: file_path,
+                dir_listing_order: opts.dir_listing_order,
+                dir_listing_format: opts.dir_listing_format,
+                ignore_hidden_files: opts.ignore_hidden_files,
+                disable_symlinks: opts.disable_symlinks,
+            })
         })?;
 
         return Ok(StaticFileResponse {
@@ -209,9 +214,11 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<StaticFileResponse, Sta
             file_path,
             &metadata,
             Some(precomp_path),
+            opts.etag,
             #[cfg(feature = "experimental")]
             opts.memory_cache,
-        )?;
+        )
+        .await?;
 
         // Prepare corresponding headers to let know how to decode the payload
         resp.headers_mut().remove(CONTENT_LENGTH);
@@ -234,10 +241,18 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<StaticFileResponse, Sta
     }
 
     #[cfg(feature = "experimental")]
-    let resp = file_reply(headers_opt, file_path, &metadata, None, opts.memory_cache)?;
+    let resp = file_reply(
+        headers_opt,
+        file_path,
+        &metadata,
+        None,
+        opts.etag,
+        opts.memory_cache,
+    )
+    .await?;
 
     #[cfg(not(feature = "experimental"))]
-    let resp = file_reply(headers_opt, file_path, &metadata, None)?;
+    let resp = file_reply(headers_opt, file_path, &metadata, None, opts.etag).await?;
 
     Ok(StaticFileResponse {
         resp,
@@ -434,11 +449,12 @@ fn get_composed_file_metadata<'a>(
 /// the `meta` param value should corresponds to it.
 /// However, if `path_precompressed` contains some value then
 /// the `meta` param  value will belong to the `path_precompressed` (precompressed file variant).
-fn file_reply<'a>(
+async fn file_reply<'a>(
     headers: &'a HeaderMap<HeaderValue>,
     path: &'a PathBuf,
     meta: &'a Metadata,
     path_precompressed: Option<PathBuf>,
+    etag: ETagMode,
     #[cfg(feature = "experimental")] memory_cache: Option<&'a MemCacheOpts>,
 ) -> Result<Response<Body>, StatusCode> {
     let conditionals = ConditionalHeaders::new(headers);
@@ -446,11 +462,14 @@ fn file_reply<'a>(
 
     match File::open(file_path) {
         Ok(file) => {
+            // The `ETag` belongs to the file variant actually served
+            let etag = file_etag(&file, file_path, meta, etag).await?;
+
             #[cfg(feature = "experimental")]
-            let resp = response_body(file, path, meta, conditionals, memory_cache);
+            let resp = response_body(file, path, meta, conditionals, etag, memory_cache);
 
             #[cfg(not(feature = "experimental"))]
-            let resp = response_body(file, path, meta, conditionals);
+            let resp = response_body(file, path, meta, conditionals, etag);
 
             resp
         }
@@ -13,11 +13,18 @@ pub mod fixtures {
 
     use crate::{
         handler::{RequestHandler, RequestHandlerOpts},
+        helpers, maintenance_mode,
         settings::cli::General,
         settings::Advanced,
         Settings,
     };
 
+    #[cfg(feature = "basic-auth")]
+    use crate::basic_auth::HtpasswdFile;
+
+    #[cfg(feature = "jwt-auth")]
+    use crate::jwt_auth::JwtAuth;
+
     /// Testing Remote address
     pub const REMOTE_ADDR: &str = "127.0.0.1:1234";
 
@@ -64,6 +71,14 @@ pub mod fixtures {
         ))]
         let compression_static = general.compression_static;
 
+        #[cfg(feature = "metrics")]
+        let metrics = general.metrics;
+        #[cfg(all(unix, feature = "experimental"))]
+        let metrics = metrics || general.experimental_metrics;
+
+        #[cfg(feature = "jwt-auth")]
+        let jwt_auth = JwtAuth::new(&general).unwrap().map(Arc::new);
+
         let req_handler_opts = RequestHandlerOpts {
             root_dir: general.root,
             compression,
@@ -93,19 +108,55 @@ pub mod fixtures {
             page_fallback: vec![],
             #[cfg(feature = "basic-auth")]
             basic_auth: general.basic_auth,
+            #[cfg(feature = "basic-auth")]
+            basic_auth_file: general.basic_auth_file.map(|path| {
+                Arc::new(
+                    HtpasswdFile::load(&path, general.basic_auth_groups_file.as_deref()).unwrap(),
+                )
+            }),
+            #[cfg(feature = "basic-auth")]
+            basic_auth_groups: general.basic_auth_groups,
+            #[cfg(feature = "basic-auth")]
+            basic_auth_realm: general.basic_auth_realm,
+            #[cfg(feature = "jwt-auth")]
+            jwt_auth,
             log_remote_address: general.log_remote_address,
             log_forwarded_for: general.log_forwarded_for,
             trusted_proxies: general.trusted_proxies,
+            trusted_proxies_header: general.trusted_proxies_header,
+            access_log: None,
+            request_id_header: general
+                .request_id
+                .then(|| general.request_id_header.parse().unwrap()),
+            #[cfg(feature = "otlp")]
+            otlp: false,
             redirect_trailing_slash: general.redirect_trailing_slash,
             ignore_hidden_files: general.ignore_hidden_files,
             disable_symlinks: general.disable_symlinks,
+            etag: general.etag,
             index_files: vec![general.index_files],
             health: general.health,
-            #[cfg(all(unix, feature = "experimental"))]
-            experimental_metrics: general.experimental_metrics,
+            admin_listener: general.admin_port.is_some(),
+            #[cfg(feature = "metrics")]
+            metrics,
             maintenance_mode: general.maintenance_mode,
             maintenance_mode_status: general.maintenance_mode_status,
             maintenance_mode_file: general.maintenance_mode_file,
+            maintenance_mode_trigger_file: general.maintenance_mode_trigger_file,
+            maintenance_mode_hosts: general.maintenance_mode_hosts,
+            maintenance_mode_paths: maintenance_mode::compile_paths(
+                &general.maintenance_mode_paths,
+            )
+            .unwrap(),
+            maintenance_mode_allow_ips: general.maintenance_mode_allow_ips,
+            signed_urls_secret: general.signed_urls_secret,
+            signed_urls_paths: helpers::compile_path_globs(
+                &general.signed_urls_paths,
+                "signed urls",
+            )
+            .unwrap(),
+            allow_ips: general.allow_ips,
+            deny_ips: general.deny_ips,
             #[cfg(feature = "experimental")]
             memory_cache: None,
             advanced_opts: advanced,
@@ -17,12 +17,23 @@ use std::io::{self, BufReader, Cursor, Read};
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tokio_rustls::rustls::{pki_types::PrivateKeyDer, Error as TlsError, ServerConfig};
-
+use tokio_rustls::rustls::{
+    crypto::ring::sign::any_supported_type,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert, VerifierBuilderError, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    Error as TlsError, InconsistentKeys, RootCertStore, ServerConfig,
+};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::mtls::{ClientAuthMode, ClientCert, ClientCertSlot};
 use crate::transport::Transport;
+use crate::virtual_hosts::host_matches;
 
 /// Represents errors that can occur building the TlsConfig
 #[derive(Debug)]
@@ -39,6 +50,10 @@ pub enum TlsConfigError {
     UnknownPrivateKeyFormat,
     /// An error from an invalid key
     InvalidKey(TlsError),
+    /// An error parsing the client CA certificates
+    ClientCaParseError,
+    /// An error building the client certificate verifier
+    ClientVerifier(VerifierBuilderError),
 }
 
 impl std::fmt::Display for TlsConfigError {
@@ -50,16 +65,26 @@ impl std::fmt::Display for TlsConfigError {
             TlsConfigError::UnknownPrivateKeyFormat => write!(f, "unknown private key format"),
             TlsConfigError::EmptyKey => write!(f, "key contains no private key"),
             TlsConfigError::InvalidKey(err) => write!(f, "key contains an invalid key, {err}"),
+            TlsConfigError::ClientCaParseError => write!(f, "client CA certificates parse error"),
+            TlsConfigError::ClientVerifier(err) => {
+                write!(f, "client certificate verifier error, {err}")
+            }
         }
     }
 }
 
 impl std::error::Error for TlsConfigError {}
 
+type PemReader = Box<dyn Read + Send + Sync>;
+
 /// Builder to set the configuration for the Tls server.
 pub struct TlsConfigBuilder {
     cert: Box<dyn Read + Send + Sync>,
     key: Box<dyn Read + Send + Sync>,
+    alpn_protocols: Vec<Vec<u8>>,
+    sni_certs: Vec<(String, PemReader, PemReader)>,
+    client_auth: ClientAuthMode,
+    client_ca: PemReader,
 }
 
 impl std::fmt::Debug for TlsConfigBuilder {
@@ -74,6 +99,10 @@ impl TlsConfigBuilder {
         TlsConfigBuilder {
             key: Box::new(io::empty()),
             cert: Box::new(io::empty()),
+            alpn_protocols: vec!["h2".into(), "http/1.1".into()],
+            sni_certs: Vec::new(),
+            client_auth: ClientAuthMode::None,
+            client_ca: Box::new(io::empty()),
         }
     }
 
@@ -107,49 +136,205 @@ impl TlsConfigBuilder {
         self
     }
 
+    /// Sets the ALPN protocols to negotiate, "h2" and "http/1.1" by default.
+    pub fn alpn_protocols(mut self, protocols: &[&str]) -> Self {
+        self.alpn_protocols = protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+        self
+    }
+
+    /// Adds a TLS certificate and key pair via file paths for a host name
+    /// which is selected via SNI (Server Name Indication).
+    /// The host name can be a wildcard like `*.example.com`.
+    /// The main certificate is used as a fallback if no host name matches.
+    pub fn sni_cert_key_path(
+        mut self,
+        host: &str,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Self {
+        self.sni_certs.push((
+            host.to_owned(),
+            Box::new(LazyFile {
+                path: cert.as_ref().into(),
+                file: None,
+            }),
+            Box::new(LazyFile {
+                path: key.as_ref().into(),
+                file: None,
+            }),
+        ));
+        self
+    }
+
+    /// Sets whether TLS clients have to present a certificate, `ClientAuthMode::None` by default.
+    pub fn client_auth(mut self, mode: ClientAuthMode) -> Self {
+        self.client_auth = mode;
+        self
+    }
+
+    /// Specify the file path for the CA certificates bundle used to verify client certificates.
+    pub fn client_ca_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.client_ca = Box::new(LazyFile {
+            path: path.as_ref().into(),
+            file: None,
+        });
+        self
+    }
+
+    /// Sets the CA certificates bundle used to verify client certificates via bytes slice.
+    pub fn client_ca(mut self, ca: &[u8]) -> Self {
+        self.client_ca = Box::new(Cursor::new(Vec::from(ca)));
+        self
+    }
+
     /// Builds TLS configuration.
-    pub fn build(mut self) -> Result<ServerConfig, TlsConfigError> {
-        let mut cert_rdr = BufReader::new(self.cert);
-        let cert = rustls_pemfile::certs(&mut cert_rdr)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_e| TlsConfigError::CertParseError)?;
-
-        // convert it to Vec<u8> to allow reading it again if key is RSA
-        let mut key_buf = Vec::new();
-        self.key
-            .read_to_end(&mut key_buf)
-            .map_err(TlsConfigError::Io)?;
-
-        if key_buf.is_empty() {
-            return Err(TlsConfigError::EmptyKey);
+    pub fn build(self) -> Result<ServerConfig, TlsConfigError> {
+        let (cert, key) = read_cert_key(self.cert, self.key)?;
+
+        let builder = ServerConfig::builder();
+        let builder = match self.client_auth {
+            ClientAuthMode::None => builder.with_no_client_auth(),
+            mode => {
+                let verifier = client_verifier(self.client_ca, mode)?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let mut config = if self.sni_certs.is_empty() {
+            builder
+                .with_single_cert(cert, key)
+                .map_err(TlsConfigError::InvalidKey)?
+        } else {
+            let mut resolver = SniCertResolver::new(certified_key(cert, key)?);
+            for (host, cert, key) in self.sni_certs {
+                let (cert, key) = read_cert_key(cert, key)?;
+                resolver.add(&host, certified_key(cert, key)?);
+            }
+            builder.with_cert_resolver(Arc::new(resolver))
+        };
+        config.alpn_protocols = self.alpn_protocols;
+        Ok(config)
+    }
+}
+
+/// Reads a certificate chain and its private key from PEM sources.
+fn read_cert_key(
+    cert: PemReader,
+    mut key: PemReader,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), TlsConfigError> {
+    let mut cert_rdr = BufReader::new(cert);
+    let cert = rustls_pemfile::certs(&mut cert_rdr)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_e| TlsConfigError::CertParseError)?;
+
+    // convert it to Vec<u8> to allow reading it again if key is RSA
+    let mut key_buf = Vec::new();
+    key.read_to_end(&mut key_buf).map_err(TlsConfigError::Io)?;
+
+    if key_buf.is_empty() {
+        return Err(TlsConfigError::EmptyKey);
+    }
+
+    let mut key: Option<PrivateKeyDer<'_>> = None;
+    let mut reader = Cursor::new(key_buf);
+    for item in std::iter::from_fn(|| rustls_pemfile::read_one(&mut reader).transpose()) {
+        match item.map_err(|_e| TlsConfigError::InvalidIdentityPem)? {
+            // rsa pkcs1 key
+            rustls_pemfile::Item::Pkcs1Key(k) => key = Some(k.into()),
+            // pkcs8 key
+            rustls_pemfile::Item::Pkcs8Key(k) => key = Some(k.into()),
+            // sec1 ec key
+            rustls_pemfile::Item::Sec1Key(k) => key = Some(k.into()),
+            // unknown format
+            _ => return Err(TlsConfigError::UnknownPrivateKeyFormat),
         }
+    }
 
-        let mut key: Option<PrivateKeyDer<'_>> = None;
-        let mut reader = Cursor::new(key_buf);
-        for item in std::iter::from_fn(|| rustls_pemfile::read_one(&mut reader).transpose()) {
-            match item.map_err(|_e| TlsConfigError::InvalidIdentityPem)? {
-                // rsa pkcs1 key
-                rustls_pemfile::Item::Pkcs1Key(k) => key = Some(k.into()),
-                // pkcs8 key
-                rustls_pemfile::Item::Pkcs8Key(k) => key = Some(k.into()),
-                // sec1 ec key
-                rustls_pemfile::Item::Sec1Key(k) => key = Some(k.into()),
-                // unknown format
-                _ => return Err(TlsConfigError::UnknownPrivateKeyFormat),
-            }
+    match key {
+        Some(k) => Ok((cert, k)),
+        _ => Err(TlsConfigError::EmptyKey),
+    }
+}
+
+/// Creates a client certificate verifier trusting the given CA certificates bundle.
+fn client_verifier(
+    ca: PemReader,
+    mode: ClientAuthMode,
+) -> Result<Arc<dyn tokio_rustls::rustls::server::danger::ClientCertVerifier>, TlsConfigError> {
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut BufReader::new(ca)) {
+        let cert = cert.map_err(|_e| TlsConfigError::ClientCaParseError)?;
+        roots
+            .add(cert)
+            .map_err(|_e| TlsConfigError::ClientCaParseError)?;
+    }
+
+    let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+    let builder = match mode {
+        ClientAuthMode::Optional => builder.allow_unauthenticated(),
+        _ => builder,
+    };
+    builder.build().map_err(TlsConfigError::ClientVerifier)
+}
+
+/// Creates a signing-capable certified key out of a certificate chain and its private key.
+fn certified_key(
+    cert: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<Arc<CertifiedKey>, TlsConfigError> {
+    let key = any_supported_type(&key).map_err(TlsConfigError::InvalidKey)?;
+    let certified_key = CertifiedKey::new(cert, key);
+    match certified_key.keys_match() {
+        // don't treat unknown consistency as an error
+        Ok(()) | Err(TlsError::InconsistentKeys(InconsistentKeys::Unknown)) => (),
+        Err(err) => return Err(TlsConfigError::InvalidKey(err)),
+    }
+    Ok(Arc::new(certified_key))
+}
+
+/// Resolves a certificate via the SNI (Server Name Indication) host name
+/// sent by the client, falling back to a default certificate.
+#[derive(Debug)]
+pub struct SniCertResolver {
+    default: Arc<CertifiedKey>,
+    certs: Vec<(String, Arc<CertifiedKey>)>,
+}
+
+impl SniCertResolver {
+    /// Creates a new resolver with a default (fallback) certificate.
+    pub fn new(default: Arc<CertifiedKey>) -> Self {
+        Self {
+            default,
+            certs: Vec::new(),
         }
+    }
 
-        let key = match key {
-            Some(k) => k,
-            _ => return Err(TlsConfigError::EmptyKey),
-        };
+    /// Adds a certificate for a host name, which can be a wildcard like `*.example.com`.
+    pub fn add(&mut self, host: &str, cert: Arc<CertifiedKey>) {
+        self.certs.push((host.to_owned(), cert));
+    }
 
-        let mut config = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(cert, key)
-            .map_err(TlsConfigError::InvalidKey)?;
-        config.alpn_protocols = vec!["h2".into(), "http/1.1".into()];
-        Ok(config)
+    /// Finds the certificate for the given server name.
+    /// Exact host names take precedence over wildcard ones.
+    fn find(&self, server_name: Option<&str>) -> Arc<CertifiedKey> {
+        if let Some(name) = server_name {
+            let exact = self
+                .certs
+                .iter()
+                .find(|(host, _)| host.eq_ignore_ascii_case(name));
+            let found =
+                exact.or_else(|| self.certs.iter().find(|(host, _)| host_matches(host, name)));
+            if let Some((host, cert)) = found {
+                tracing::trace!("tls certificate selected via sni: {} -> {}", name, host);
+                return cert.clone();
+            }
+        }
+        self.default.clone()
+    }
+}
+
+impl ResolvesServerCert for SniCertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.find(client_hello.server_name()))
     }
 }
 
@@ -186,38 +371,66 @@ impl Read for LazyFile {
     }
 }
 
-impl Transport for TlsStream {
+impl<IO> Transport for TlsStream<IO>
+where
+    IO: Transport + Unpin,
+{
     fn remote_addr(&self) -> Option<SocketAddr> {
-        Some(self.remote_addr)
+        self.remote_addr
+    }
+
+    fn client_cert(&self) -> Option<ClientCertSlot> {
+        Some(self.client_cert.clone())
     }
 }
 
-enum State {
-    Handshaking(tokio_rustls::Accept<AddrStream>),
-    Streaming(tokio_rustls::server::TlsStream<AddrStream>),
+enum State<IO> {
+    Handshaking(tokio_rustls::Accept<IO>),
+    Streaming(tokio_rustls::server::TlsStream<IO>),
 }
 
 /// TlsStream implements AsyncRead/AsyncWrite handshaking tokio_rustls::Accept first.
 ///
 /// tokio_rustls::server::TlsStream doesn't expose constructor methods,
 /// so we have to TlsAcceptor::accept and handshake to have access to it.
-pub struct TlsStream {
-    state: State,
-    remote_addr: SocketAddr,
+pub struct TlsStream<IO = AddrStream> {
+    state: State<IO>,
+    remote_addr: Option<SocketAddr>,
+    client_cert: ClientCertSlot,
 }
 
-impl TlsStream {
-    fn new(stream: AddrStream, config: Arc<ServerConfig>) -> TlsStream {
+impl<IO> TlsStream<IO>
+where
+    IO: Transport + Unpin,
+{
+    fn new(stream: IO, config: Arc<ServerConfig>) -> TlsStream<IO> {
         let remote_addr = stream.remote_addr();
         let accept = tokio_rustls::TlsAcceptor::from(config).accept(stream);
         TlsStream {
             state: State::Handshaking(accept),
             remote_addr,
+            client_cert: ClientCertSlot::default(),
+        }
+    }
+
+    /// Keeps the verified client certificate once the handshake is completed.
+    fn handshake_completed(&self, stream: &tokio_rustls::server::TlsStream<IO>) {
+        let cert = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .and_then(|der| ClientCert::from_der(der));
+        if let Some(cert) = cert {
+            let _ = self.client_cert.set(cert);
         }
     }
 }
 
-impl AsyncRead for TlsStream {
+impl<IO> AsyncRead for TlsStream<IO>
+where
+    IO: Transport + Unpin,
+{
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -227,18 +440,26 @@ impl AsyncRead for TlsStream {
         match pin.state {
             State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
                 Ok(mut stream) => {
+                    pin.handshake_completed(&stream);
                     let result = Pin::new(&mut stream).poll_read(cx, buf);
                     pin.state = State::Streaming(stream);
                     result
                 }
-                Err(err) => Poll::Ready(Err(err)),
+                Err(err) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::tls_handshake_failed();
+                    Poll::Ready(Err(err))
+                }
             },
             State::Streaming(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
         }
     }
 }
 
-impl AsyncWrite for TlsStream {
+impl<IO> AsyncWrite for TlsStream<IO>
+where
+    IO: Transport + Unpin,
+{
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -248,11 +469,16 @@ impl AsyncWrite for TlsStream {
         match pin.state {
             State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
                 Ok(mut stream) => {
+                    pin.handshake_completed(&stream);
                     let result = Pin::new(&mut stream).poll_write(cx, buf);
                     pin.state = State::Streaming(stream);
                     result
                 }
-                Err(err) => Poll::Ready(Err(err)),
+                Err(err) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::tls_handshake_failed();
+                    Poll::Ready(Err(err))
+                }
             },
             State::Streaming(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
         }
@@ -274,23 +500,146 @@ impl AsyncWrite for TlsStream {
 }
 
 /// Type to intercept Tls incoming connections.
-pub struct TlsAcceptor {
-    config: Arc<ServerConfig>,
-    incoming: AddrIncoming,
+pub struct TlsAcceptor<I = AddrIncoming> {
+    config: SharedTlsConfig,
+    incoming: I,
 }
 
-impl TlsAcceptor {
+impl<I> TlsAcceptor<I> {
     /// Creates a new Tls interceptor.
-    pub fn new(config: ServerConfig, incoming: AddrIncoming) -> TlsAcceptor {
-        TlsAcceptor {
-            config: Arc::new(config),
-            incoming,
+    pub fn new(config: ServerConfig, incoming: I) -> TlsAcceptor<I> {
+        Self::with_shared_config(SharedTlsConfig::new(config), incoming)
+    }
+
+    /// Creates a new Tls interceptor with a configuration that can be swapped at runtime.
+    pub fn with_shared_config(config: SharedTlsConfig, incoming: I) -> TlsAcceptor<I> {
+        TlsAcceptor { config, incoming }
+    }
+}
+
+/// A TLS server configuration shared with the acceptor which can be swapped at runtime.
+/// New handshakes use the current configuration while established connections keep theirs.
+#[derive(Clone, Debug)]
+pub struct SharedTlsConfig {
+    inner: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl SharedTlsConfig {
+    /// Creates a new shared TLS configuration.
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Arc::new(config))),
+        }
+    }
+
+    /// Returns the current TLS configuration.
+    pub fn get(&self) -> Arc<ServerConfig> {
+        match self.inner.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the current TLS configuration.
+    pub fn set(&self, config: ServerConfig) {
+        let config = Arc::new(config);
+        match self.inner.write() {
+            Ok(mut current) => *current = config,
+            Err(poisoned) => *poisoned.into_inner() = config,
+        }
+    }
+}
+
+/// Watches for TLS certificate changes and reloads the shared configuration
+/// using the given `build` function, either on `SIGHUP` (Unix only) or
+/// when the modification time of the watched files changes.
+/// The current configuration is kept if the reload fails.
+pub(crate) fn spawn_reloader<F>(
+    config: SharedTlsConfig,
+    build: F,
+    files: Vec<PathBuf>,
+    watch_interval_secs: u64,
+) -> crate::Result
+where
+    F: Fn() -> Result<ServerConfig, TlsConfigError> + Send + Sync + 'static,
+{
+    let build = Arc::new(build);
+
+    #[cfg(unix)]
+    {
+        use futures_util::stream::StreamExt;
+
+        let mut signals = crate::signals::create_reload_signals()?;
+        let config = config.clone();
+        let build = build.clone();
+        tokio::spawn(async move {
+            while signals.next().await.is_some() {
+                server_info!("SIGHUP caught, reloading tls certificates");
+                reload(&config, &*build);
+            }
+        });
+    }
+
+    if watch_interval_secs > 0 {
+        server_info!(
+            "tls certificate files watched for changes every {}s",
+            watch_interval_secs
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(watch_interval_secs));
+            interval.tick().await;
+            let mut current = files_modified(&files);
+            let mut pending = None;
+            loop {
+                interval.tick().await;
+                let modified = files_modified(&files);
+                // NOTE: reload only once the files stay unchanged for a whole interval
+                // to avoid loading a certificate whose key is still being written.
+                if pending.as_ref() == Some(&modified) {
+                    pending = None;
+                    current = modified;
+                    server_info!("tls certificate files changed, reloading them");
+                    reload(&config, &*build);
+                } else if modified != current {
+                    pending = Some(modified);
+                } else {
+                    pending = None;
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn reload<F>(config: &SharedTlsConfig, build: &F)
+where
+    F: Fn() -> Result<ServerConfig, TlsConfigError>,
+{
+    match build() {
+        Ok(new_config) => {
+            config.set(new_config);
+            server_info!("tls certificates reloaded successfully");
+        }
+        Err(err) => {
+            tracing::error!("failed to reload tls certificates, keeping the current ones: {err}");
         }
     }
 }
 
-impl Accept for TlsAcceptor {
-    type Conn = TlsStream;
+fn files_modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+impl<I> Accept for TlsAcceptor<I>
+where
+    I: Accept<Error = io::Error> + Unpin,
+    I::Conn: Transport + Unpin,
+{
+    type Conn = TlsStream<I::Conn>;
     type Error = io::Error;
 
     fn poll_accept(
@@ -299,7 +648,7 @@ impl Accept for TlsAcceptor {
     ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
         let pin = self.get_mut();
         match ready!(Pin::new(&mut pin.incoming).poll_accept(cx)) {
-            Some(Ok(sock)) => Poll::Ready(Some(Ok(TlsStream::new(sock, pin.config.clone())))),
+            Some(Ok(sock)) => Poll::Ready(Some(Ok(TlsStream::new(sock, pin.config.get())))),
             Some(Err(e)) => Poll::Ready(Some(Err(e))),
             None => Poll::Ready(None),
         }
@@ -352,6 +701,30 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn client_auth_with_ca() {
+        for mode in [ClientAuthMode::Optional, ClientAuthMode::Required] {
+            TlsConfigBuilder::new()
+                .cert_path("tests/tls/local.dev_cert.sec1_ec.pem")
+                .key_path("tests/tls/local.dev_key.sec1_ec.pem")
+                .client_auth(mode)
+                .client_ca_path("tests/tls/local.dev_cert.pkcs8.pem")
+                .build()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn client_auth_without_ca() {
+        let err = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.sec1_ec.pem")
+            .key_path("tests/tls/local.dev_key.sec1_ec.pem")
+            .client_auth(ClientAuthMode::Required)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::ClientVerifier(_)));
+    }
+
     #[test]
     fn file_cert_key_sec1_ec() {
         TlsConfigBuilder::new()
@@ -372,4 +745,154 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn alpn_protocols_http1_only() {
+        let config = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.pkcs8.pem")
+            .key_path("tests/tls/local.dev_key.pkcs8.pem")
+            .alpn_protocols(&["http/1.1"])
+            .build()
+            .unwrap();
+        assert_eq!(config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+
+        let config = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.pkcs8.pem")
+            .key_path("tests/tls/local.dev_key.pkcs8.pem")
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.alpn_protocols,
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+    }
+
+    fn load_certified_key(cert: &str, key: &str) -> Arc<CertifiedKey> {
+        let (cert, key) = read_cert_key(
+            Box::new(File::open(cert).unwrap()),
+            Box::new(File::open(key).unwrap()),
+        )
+        .unwrap();
+        certified_key(cert, key).unwrap()
+    }
+
+    #[test]
+    fn sni_cert_resolver_find() {
+        let default = load_certified_key(
+            "tests/tls/local.dev_cert.rsa_pkcs1.pem",
+            "tests/tls/local.dev_key.rsa_pkcs1.pem",
+        );
+        let wildcard = load_certified_key(
+            "tests/tls/local.dev_cert.sec1_ec.pem",
+            "tests/tls/local.dev_key.sec1_ec.pem",
+        );
+        let exact = load_certified_key(
+            "tests/tls/local.dev_cert.pkcs8.pem",
+            "tests/tls/local.dev_key.pkcs8.pem",
+        );
+
+        let mut resolver = SniCertResolver::new(default.clone());
+        resolver.add("*.example.com", wildcard.clone());
+        resolver.add("api.example.com", exact.clone());
+
+        assert!(Arc::ptr_eq(&resolver.find(Some("api.example.com")), &exact));
+        assert!(Arc::ptr_eq(&resolver.find(Some("API.example.com")), &exact));
+        assert!(Arc::ptr_eq(
+            &resolver.find(Some("www.example.com")),
+            &wildcard
+        ));
+        assert!(Arc::ptr_eq(&resolver.find(Some("example.com")), &default));
+        assert!(Arc::ptr_eq(
+            &resolver.find(Some("a.b.example.com")),
+            &default
+        ));
+        assert!(Arc::ptr_eq(&resolver.find(None), &default));
+    }
+
+    #[test]
+    fn file_cert_key_sni() {
+        TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.rsa_pkcs1.pem")
+            .key_path("tests/tls/local.dev_key.rsa_pkcs1.pem")
+            .sni_cert_key_path(
+                "*.example.com",
+                "tests/tls/local.dev_cert.sec1_ec.pem",
+                "tests/tls/local.dev_key.sec1_ec.pem",
+            )
+            .build()
+            .unwrap();
+
+        let result = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.rsa_pkcs1.pem")
+            .key_path("tests/tls/local.dev_key.rsa_pkcs1.pem")
+            .sni_cert_key_path(
+                "example.com",
+                "tests/tls/local.dev_cert.sec1_ec.pem",
+                "tests/tls/missing.pem",
+            )
+            .build();
+        assert!(result.is_err());
+    }
+
+    fn build_from(dir: &Path) -> Result<ServerConfig, TlsConfigError> {
+        TlsConfigBuilder::new()
+            .cert_path(dir.join("cert.pem"))
+            .key_path(dir.join("key.pem"))
+            .build()
+    }
+
+    #[test]
+    fn shared_config_reload() {
+        let config = SharedTlsConfig::new(
+            TlsConfigBuilder::new()
+                .cert_path("tests/tls/local.dev_cert.pkcs8.pem")
+                .key_path("tests/tls/local.dev_key.pkcs8.pem")
+                .build()
+                .unwrap(),
+        );
+        let initial = config.get();
+
+        // a failed reload keeps the current configuration
+        reload(&config, &|| build_from(Path::new("tests/tls/missing")));
+        assert!(Arc::ptr_eq(&initial, &config.get()));
+
+        reload(&config, &|| {
+            TlsConfigBuilder::new()
+                .cert_path("tests/tls/local.dev_cert.sec1_ec.pem")
+                .key_path("tests/tls/local.dev_key.sec1_ec.pem")
+                .build()
+        });
+        assert!(!Arc::ptr_eq(&initial, &config.get()));
+    }
+
+    #[tokio::test]
+    async fn watch_reload_on_file_changes() {
+        let dir = std::env::temp_dir().join(format!("sws-tls-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("tests/tls/local.dev_cert.pkcs8.pem", dir.join("cert.pem")).unwrap();
+        std::fs::copy("tests/tls/local.dev_key.pkcs8.pem", dir.join("key.pem")).unwrap();
+
+        let config = SharedTlsConfig::new(build_from(&dir).unwrap());
+        let initial = config.get();
+
+        let files = vec![dir.join("cert.pem"), dir.join("key.pem")];
+        let build_dir = dir.clone();
+        spawn_reloader(config.clone(), move || build_from(&build_dir), files, 1).unwrap();
+
+        // make sure the new files get a different modification time
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        std::fs::copy("tests/tls/local.dev_cert.sec1_ec.pem", dir.join("cert.pem")).unwrap();
+        std::fs::copy("tests/tls/local.dev_key.sec1_ec.pem", dir.join("key.pem")).unwrap();
+
+        let mut reloaded = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if !Arc::ptr_eq(&initial, &config.get()) {
+                reloaded = true;
+                break;
+            }
+        }
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(reloaded, "tls configuration was not reloaded");
+    }
 }
@@ -16,10 +16,29 @@ use std::task::{Context, Poll};
 use hyper::server::conn::AddrStream;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
+#[cfg(feature = "http2")]
+use crate::mtls::ClientCertSlot;
+#[cfg(unix)]
+use crate::unix_socket::PeerCred;
+
 /// Transport trait that supports the remote (peer) address.
 pub trait Transport: AsyncRead + AsyncWrite {
     /// Returns the remote (peer) address of this connection.
     fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Returns the peer process credentials of this connection if it's a Unix domain socket.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    fn peer_cred(&self) -> Option<PeerCred> {
+        None
+    }
+
+    /// Returns the slot holding the verified TLS client certificate of this connection.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    fn client_cert(&self) -> Option<ClientCertSlot> {
+        None
+    }
 }
 
 impl Transport for AddrStream {
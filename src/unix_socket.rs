@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! The module handles requests over Unix domain sockets.
+//!
+
+use futures_util::ready;
+use hyper::server::accept::Accept;
+use nix::unistd::{Group, User};
+use std::fs::{self, Permissions};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener as StdUnixListener, UnixStream as StdUnixStream};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Sleep};
+
+use crate::transport::Transport;
+use crate::{Context as _, Result};
+
+/// The credentials of the process on the other side of a Unix domain socket connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCred {
+    /// The user ID of the peer process.
+    pub uid: u32,
+    /// The group ID of the peer process.
+    pub gid: u32,
+    /// The process ID of the peer process if the platform provides it.
+    pub pid: Option<i32>,
+}
+
+/// Binds a Unix domain socket listener to the given path.
+///
+/// A stale socket file left behind by a previous process is removed first,
+/// while a socket still accepting connections makes the binding fail.
+/// The socket file `mode` (octal, e.g. `660`) and `owner` (`USER[:GROUP]`) are optional.
+pub fn bind(path: &Path, mode: Option<&str>, owner: Option<&str>) -> Result<StdUnixListener> {
+    remove_stale_socket(path)?;
+
+    let listener = StdUnixListener::bind(path)
+        .with_context(|| format!("failed to bind to unix socket {}", path.display()))?;
+
+    if let Some(mode) = mode {
+        let mode = parse_mode(mode)?;
+        fs::set_permissions(path, Permissions::from_mode(mode)).with_context(|| {
+            format!(
+                "failed to set the file mode of unix socket {}",
+                path.display()
+            )
+        })?;
+    }
+
+    if let Some(owner) = owner {
+        let (uid, gid) = parse_owner(owner)?;
+        std::os::unix::fs::chown(path, uid, gid).with_context(|| {
+            format!("failed to set the owner of unix socket {}", path.display())
+        })?;
+    }
+
+    Ok(listener)
+}
+
+/// Removes a socket file which is not accepting connections anymore.
+fn remove_stale_socket(path: &Path) -> Result {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to access unix socket {}", path.display()))
+        }
+    };
+
+    if !meta.file_type().is_socket() {
+        bail!(
+            "unix socket path {} already exists and is not a socket",
+            path.display()
+        );
+    }
+    if StdUnixStream::connect(path).is_ok() {
+        bail!(
+            "unix socket {} is already in use by another process",
+            path.display()
+        );
+    }
+
+    fs::remove_file(path)
+        .with_context(|| format!("failed to remove stale unix socket {}", path.display()))?;
+    tracing::debug!("removed stale unix socket {}", path.display());
+    Ok(())
+}
+
+/// Parses an octal file mode like `660` or `0660`.
+fn parse_mode(mode: &str) -> Result<u32> {
+    match u32::from_str_radix(mode.trim_start_matches("0o"), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => bail!("invalid unix socket file mode \"{mode}\", use an octal value like 660"),
+    }
+}
+
+/// Parses an owner like `USER[:GROUP]` where both can be either names or numeric IDs.
+fn parse_owner(owner: &str) -> Result<(Option<u32>, Option<u32>)> {
+    let (user, group) = match owner.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (owner, None),
+    };
+    let uid = match user {
+        "" => None,
+        user => Some(
+            resolve_uid(user)
+                .with_context(|| format!("unix socket owner user \"{user}\" was not found"))?,
+        ),
+    };
+    let gid = match group {
+        None | Some("") => None,
+        Some(group) => Some(
+            resolve_gid(group)
+                .with_context(|| format!("unix socket owner group \"{group}\" was not found"))?,
+        ),
+    };
+    Ok((uid, gid))
+}
+
+/// Resolves a numeric user ID or looks up a user name via the system user database (NSS).
+fn resolve_uid(name: &str) -> Result<u32> {
+    if let Ok(id) = name.parse::<u32>() {
+        return Ok(id);
+    }
+    match User::from_name(name)? {
+        Some(user) => Ok(user.uid.as_raw()),
+        None => bail!("no such user in the user database"),
+    }
+}
+
+/// Resolves a numeric group ID or looks up a group name via the system group database (NSS).
+fn resolve_gid(name: &str) -> Result<u32> {
+    if let Ok(id) = name.parse::<u32>() {
+        return Ok(id);
+    }
+    match Group::from_name(name)? {
+        Some(group) => Ok(group.gid.as_raw()),
+        None => bail!("no such group in the group database"),
+    }
+}
+
+/// Time to wait before accepting connections again after an accept error
+/// like too many open files.
+const ACCEPT_ERROR_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Type to accept incoming Unix domain socket connections.
+///
+/// Like `hyper::server::conn::AddrIncoming`, accept errors don't stop the server:
+/// connection errors are skipped while other errors (e.g. `EMFILE`) pause accepting for a while.
+pub struct UnixIncoming {
+    listener: tokio::net::UnixListener,
+    timeout: Option<Pin<Box<Sleep>>>,
+}
+
+impl UnixIncoming {
+    /// Creates a new incoming connections acceptor from a standard Unix listener.
+    pub fn from_std(listener: StdUnixListener) -> Result<Self> {
+        listener
+            .set_nonblocking(true)
+            .with_context(|| "failed to set unix socket non-blocking mode")?;
+        let listener = tokio::net::UnixListener::from_std(listener)
+            .with_context(|| "failed to create tokio::net::UnixListener")?;
+        Ok(Self {
+            listener,
+            timeout: None,
+        })
+    }
+}
+
+impl Accept for UnixIncoming {
+    type Conn = UnixStream;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let pin = self.get_mut();
+
+        // Wait until the previous accept error timeout elapses
+        if let Some(timeout) = &mut pin.timeout {
+            ready!(timeout.as_mut().poll(cx));
+            pin.timeout = None;
+        }
+
+        loop {
+            match ready!(pin.listener.poll_accept(cx)) {
+                Ok((stream, _)) => return Poll::Ready(Some(Ok(UnixStream::new(stream)))),
+                Err(err) if is_connection_error(&err) => {
+                    tracing::debug!("unix socket accepted connection already errored: {err}");
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "unix socket accept error, retrying in {:?}: {err}",
+                        ACCEPT_ERROR_TIMEOUT
+                    );
+                    let mut timeout = Box::pin(sleep(ACCEPT_ERROR_TIMEOUT));
+                    if timeout.as_mut().poll(cx).is_pending() {
+                        pin.timeout = Some(timeout);
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks if an accept error only concerns the accepted connection itself.
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// A Unix domain socket connection which keeps the credentials of its peer.
+pub struct UnixStream {
+    stream: tokio::net::UnixStream,
+    peer_cred: Option<PeerCred>,
+}
+
+impl UnixStream {
+    fn new(stream: tokio::net::UnixStream) -> Self {
+        let peer_cred = stream.peer_cred().ok().map(|cred| PeerCred {
+            uid: cred.uid(),
+            gid: cred.gid(),
+            pid: cred.pid(),
+        });
+        Self { stream, peer_cred }
+    }
+}
+
+impl Transport for UnixStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.peer_cred
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_socket_mode() {
+        assert_eq!(parse_mode("660").unwrap(), 0o660);
+        assert_eq!(parse_mode("0600").unwrap(), 0o600);
+        assert!(parse_mode("999").is_err());
+        assert!(parse_mode("rw").is_err());
+    }
+
+    #[test]
+    fn parse_socket_owner() {
+        assert_eq!(parse_owner("1000").unwrap(), (Some(1000), None));
+        assert_eq!(parse_owner("1000:100").unwrap(), (Some(1000), Some(100)));
+        assert_eq!(parse_owner(":100").unwrap(), (None, Some(100)));
+        assert_eq!(parse_owner("root:root").unwrap(), (Some(0), Some(0)));
+        assert!(parse_owner("no-such-user-sws").is_err());
+    }
+
+    #[test]
+    fn bind_removes_stale_socket() {
+        let dir = std::env::temp_dir().join(format!("sws-unix-socket-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sws.sock");
+
+        // A socket file left behind without a listener is replaced
+        drop(StdUnixListener::bind(&path).unwrap());
+        let listener = bind(&path, Some("600"), None).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A socket still accepting connections is kept
+        assert!(bind(&path, None, None).is_err());
+        drop(listener);
+
+        // Not a socket file
+        let file = dir.join("file.txt");
+        fs::write(&file, "").unwrap();
+        assert!(bind(&file, None, None).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -17,21 +17,77 @@ pub(crate) fn get_real_root<'a, T>(
     req: &mut Request<T>,
     vhosts_opts: Option<&'a [VirtualHosts]>,
 ) -> Option<&'a PathBuf> {
-    if let Some(vhosts) = vhosts_opts {
-        if let Ok(host_str) = req.headers().get(HOST)?.to_str() {
-            for vhost in vhosts {
-                if vhost.host == host_str {
-                    tracing::info!(
-                        "virtual host matched: vhost={} vhost_root={} method={} uri={}",
-                        vhost.host,
-                        vhost.root.display(),
-                        req.method(),
-                        req.uri(),
-                    );
-                    return Some(&vhost.root);
-                }
-            }
+    let vhost = get_vhost(req, vhosts_opts)?;
+    tracing::info!(
+        "virtual host matched: vhost={} vhost_root={} method={} uri={}",
+        vhost.host,
+        vhost.root.display(),
+        req.method(),
+        req.uri(),
+    );
+    Some(&vhost.root)
+}
+
+/// It returns the virtual host matching the "Host" header if any.
+pub(crate) fn get_vhost<'a, T>(
+    req: &Request<T>,
+    vhosts_opts: Option<&'a [VirtualHosts]>,
+) -> Option<&'a VirtualHosts> {
+    let host_str = req.headers().get(HOST)?.to_str().ok()?;
+    vhosts_opts?.iter().find(|vhost| vhost.host == host_str)
+}
+
+/// It checks if a host name matches a virtual host name pattern (case-insensitive).
+/// A pattern like `*.example.com` matches exactly one extra leftmost label,
+/// e.g. `www.example.com` but neither `example.com` nor `a.b.example.com`.
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    if pattern.eq_ignore_ascii_case(host) {
+        return true;
+    }
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => match host.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest.eq_ignore_ascii_case(suffix),
+            None => false,
+        },
+        None => false,
+    }
+}
+
+/// It removes the optional port from a "Host" header value.
+pub(crate) fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if (!name.contains(':') || name.ends_with(']'))
+                && port.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            name
         }
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{host_matches, strip_port};
+
+    #[test]
+    fn host_matches_exact_and_wildcard() {
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("Example.com", "example.COM"));
+        assert!(!host_matches("example.com", "www.example.com"));
+
+        assert!(host_matches("*.example.com", "www.example.com"));
+        assert!(host_matches("*.example.com", "API.Example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "a.b.example.com"));
+        assert!(!host_matches("*.example.com", ".example.com"));
+    }
+
+    #[test]
+    fn strip_host_port() {
+        assert_eq!(strip_port("example.com:8443"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:8443"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
     }
-    None
 }
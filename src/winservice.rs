@@ -55,8 +55,8 @@ fn set_service_state(
         service_type: SERVICE_TYPE,
         // The new state
         current_state,
-        // Accept stop events when running
-        controls_accepted: ServiceControlAccept::STOP,
+        // Accept stop and parameters change (config reload) events when running
+        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::PARAM_CHANGE,
         // Used to report an error when starting or stopping only, otherwise must be zero
         exit_code: ServiceExitCode::Win32(0),
         // Only used for pending states, otherwise must be zero
@@ -81,6 +81,9 @@ fn run_service() -> Result {
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
     let mut shutdown_tx = Some(shutdown_tx);
 
+    // Create a channel to be able to notify a config reload to the server.
+    let (reload_tx, reload_rx) = tokio::sync::watch::channel(());
+
     // Define system service event handler that will be receiving service events.
     let event_handler = move |control_event| -> ServiceControlHandlerResult {
         match control_event {
@@ -98,6 +101,13 @@ fn run_service() -> Result {
                 ServiceControlHandlerResult::NoError
             }
 
+            // Handle parameters change, e.g. `sc control static-web-server paramchange`
+            ServiceControl::Paramchange => {
+                tracing::debug!("windows service: handled 'ServiceControl::Paramchange' event");
+                reload_tx.send(()).ok();
+                ServiceControlHandlerResult::NoError
+            }
+
             _ => ServiceControlHandlerResult::NotImplemented,
         }
     };
@@ -135,6 +145,7 @@ fn run_service() -> Result {
     // Starting web server
     match Server::new(opts) {
         Ok(server) => {
+            let server = server.with_reload(reload_rx);
             if let Err(err) = server.run_as_service(Some(shutdown_rx), stop_handler) {
                 tracing::error!(
                     "windows service: error after starting the server: {:?}",
@@ -0,0 +1,31 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn admin_endpoints_are_not_public() {
+        let opts = fixture_settings("toml/admin.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        for uri in ["/health", "/metrics"] {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+            assert_eq!(res.status(), 404, "{uri}");
+        }
+
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(res.status(), 200);
+    }
+}
@@ -0,0 +1,48 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(all(test, feature = "basic-auth"))]
+mod tests {
+    use headers::{Authorization, HeaderMapExt};
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn auth_rules_by_path_and_host() {
+        let opts = fixture_settings("toml/auth.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        let cases = [
+            ("/index.html", "localhost", None, 200),
+            ("/index.html", "private.example.com", None, 401),
+            ("/index.html", "private.example.com:8787", Some("dave"), 200),
+            ("/index.html", "private.example.com", Some("alice"), 403),
+            ("/assets/main.js", "localhost", None, 401),
+            ("/assets/main.js", "localhost", Some("alice"), 200),
+            ("/assets/main.js", "localhost", Some("dave"), 403),
+            // Rewritten into a protected path
+            ("/pub/main.js", "localhost", None, 401),
+            ("/pub/main.js", "localhost", Some("alice"), 200),
+            ("/pub/main.js", "localhost", Some("dave"), 403),
+        ];
+        for (uri, host, user, status) in cases {
+            let mut req = Request::get(uri)
+                .header("host", host)
+                .body(Body::empty())
+                .unwrap();
+            if let Some(user) = user {
+                req.headers_mut()
+                    .typed_insert(Authorization::basic(user, "jq"));
+            }
+            let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+            assert_eq!(res.status(), status, "{uri} {host} {user:?}");
+        }
+    }
+}
@@ -19,6 +19,7 @@ mod tests {
 
     #[cfg(feature = "directory-listing")]
     use static_web_server::directory_listing::DirListFmt;
+    use static_web_server::etag::ETagMode;
     use static_web_server::static_files::{self, HandleOpts};
 
     fn public_dir() -> PathBuf {
@@ -63,6 +64,7 @@ mod tests {
             compression_static: true,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -135,6 +137,7 @@ mod tests {
             compression_static: true,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -204,6 +207,7 @@ mod tests {
             compression_static: true,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -261,6 +265,7 @@ mod tests {
             compression_static: true,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -305,6 +310,7 @@ mod tests {
             compression_static: true,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -338,4 +344,59 @@ mod tests {
             "body and mainjs_zst_buf are not equal in length"
         );
     }
+
+    #[tokio::test]
+    async fn compression_static_strong_etag_per_variant() {
+        // Both variants share the same size and modification time
+        let dir = std::env::temp_dir().join(format!("sws-etag-variants-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        for (name, content) in [("file.txt", "plain"), ("file.txt.gz", "gzips")] {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+
+        let mut etags = Vec::new();
+        for accept_encoding in ["identity", "gzip"] {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                http::header::ACCEPT_ENCODING,
+                accept_encoding.parse().unwrap(),
+            );
+            let result = static_files::handle(&HandleOpts {
+                method: &Method::GET,
+                headers: &headers,
+                base_path: &dir,
+                uri_path: "file.txt",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: true,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Strong,
+                index_files: &[],
+            })
+            .await
+            .expect("unexpected error response on `handle` function");
+            etags.push(result.resp.headers()["etag"].clone());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_ne!(etags[0], etags[1]);
+    }
 }
@@ -13,6 +13,7 @@ mod tests {
 
     use static_web_server::{
         directory_listing::DirListFmt,
+        etag::ETagMode,
         static_files::{self, HandleOpts},
     };
 
@@ -52,6 +53,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -87,6 +89,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -132,6 +135,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -177,6 +181,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -212,6 +217,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -268,6 +274,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: true,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -342,6 +349,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -389,6 +397,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: true,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
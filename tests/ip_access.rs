@@ -0,0 +1,63 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn ip_access_allow_and_deny_lists() {
+        let opts = fixture_settings("toml/ip_access.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+
+        let cases = [
+            ("/index.html", "192.0.2.1:4000", None, 200),
+            ("/index.html", "198.51.100.7:4000", None, 403),
+            ("/index.html", "[2001:db8:bad::1]:4000", None, 403),
+            ("/index.html", "[2001:db8::1]:4000", None, 200),
+            ("/health", "192.0.2.1:4000", None, 200),
+            ("/health", "198.51.100.7:4000", None, 403),
+            ("/assets/main.js", "10.1.2.3:4000", None, 200),
+            ("/assets/main.js", "10.0.0.13:4000", None, 403),
+            ("/assets/main.js", "192.0.2.1:4000", None, 403),
+            // Rewritten into a restricted path
+            ("/pub/main.js", "10.1.2.3:4000", None, 200),
+            ("/pub/main.js", "192.0.2.1:4000", None, 403),
+            ("/assets/main.js", REMOTE_ADDR, Some("for=203.0.113.7"), 200),
+            ("/assets/main.js", REMOTE_ADDR, Some("for=192.0.2.1"), 403),
+            (
+                "/assets/main.js",
+                REMOTE_ADDR,
+                Some("for=203.0.113.7, for=unknown"),
+                403,
+            ),
+            ("/assets/main.js", REMOTE_ADDR, None, 403),
+            (
+                "/index.html",
+                REMOTE_ADDR,
+                Some("for=\"198.51.100.7:80\""),
+                403,
+            ),
+        ];
+        for (uri, addr, forwarded, status) in cases {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            if let Some(forwarded) = forwarded {
+                req.headers_mut()
+                    .insert("forwarded", forwarded.parse().unwrap());
+            }
+            // Only the configured trusted proxies header is used
+            req.headers_mut()
+                .insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+            let remote_addr = addr.parse::<SocketAddr>().ok();
+            let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+            assert_eq!(res.status(), status, "{uri} {addr} {forwarded:?}");
+        }
+    }
+}
@@ -0,0 +1,103 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(all(test, feature = "jwt-auth"))]
+mod tests {
+    use hyper::{Body, Request};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    fn token(alg: Algorithm, kid: Option<&str>, role: &str, exp_offset: i64) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let claims = json!({"sub": "alice", "aud": "sws", "role": role, "exp": now + exp_offset});
+        let key = match alg {
+            Algorithm::RS256 => {
+                EncodingKey::from_rsa_pem(include_bytes!("fixtures/jwt/rsa.key")).unwrap()
+            }
+            Algorithm::ES256 => {
+                EncodingKey::from_ec_pem(include_bytes!("fixtures/jwt/ec.key")).unwrap()
+            }
+            _ => EncodingKey::from_secret(b"jwks-s3cr3t"),
+        };
+        let mut header = Header::new(alg);
+        header.kid = kid.map(str::to_owned);
+        encode(&header, &claims, &key).unwrap()
+    }
+
+    #[tokio::test]
+    async fn jwt_auth_tokens_and_claims() {
+        let opts = fixture_settings("toml/jwt_auth.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(res.status(), 401);
+        assert_eq!(
+            res.headers()["www-authenticate"],
+            "Bearer realm=\"Static Web Server\""
+        );
+
+        let cases = [
+            (
+                "/index.html",
+                token(Algorithm::RS256, None, "user", 60),
+                200,
+            ),
+            (
+                "/index.html",
+                token(Algorithm::ES256, Some("ec-1"), "user", 60),
+                200,
+            ),
+            (
+                "/index.html",
+                token(Algorithm::HS256, Some("hmac-1"), "user", 60),
+                200,
+            ),
+            (
+                "/index.html",
+                token(Algorithm::RS256, None, "user", -120),
+                401,
+            ),
+            (
+                "/index.html",
+                token(Algorithm::ES256, Some("other"), "user", 60),
+                401,
+            ),
+            (
+                "/assets/main.js",
+                token(Algorithm::RS256, None, "user", 60),
+                403,
+            ),
+            (
+                "/assets/main.js",
+                token(Algorithm::RS256, None, "admin", 60),
+                200,
+            ),
+        ];
+        for (uri, token, status) in cases {
+            for header in [
+                ("authorization", format!("Bearer {token}")),
+                ("cookie", format!("theme=dark; session={token}")),
+            ] {
+                let mut req = Request::get(uri)
+                    .header(header.0, &header.1)
+                    .body(Body::empty())
+                    .unwrap();
+                let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+                assert_eq!(res.status(), status, "{uri} {}", header.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::maintenance_mode;
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn maintenance_mode_toggled_at_runtime() {
+        let opts = fixture_settings("toml/maintenance_mode.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+        let staff_addr = Some("10.0.0.7:4000".parse::<SocketAddr>().unwrap());
+
+        let cases = [
+            (None, "/assets/main.js", remote_addr, 200),
+            (Some(true), "/assets/main.js", remote_addr, 503),
+            (Some(true), "/assets/main.js", staff_addr, 200),
+            (Some(true), "/index.html", remote_addr, 200),
+            (Some(false), "/assets/main.js", remote_addr, 200),
+        ];
+        for (state, uri, addr, status) in cases {
+            if let Some(enabled) = state {
+                maintenance_mode::set_runtime_state(enabled);
+            }
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            let res = req_handler.handle(&mut req, addr).await.unwrap();
+            assert_eq!(res.status(), status, "{state:?} {uri}");
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(feature = "metrics")]
+mod tests {
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    fn request(uri: &str, host: &str) -> Request<Body> {
+        Request::get(uri)
+            .header("host", host)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests_per_vhost() {
+        let opts = fixture_settings("toml/metrics.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        for (uri, host) in [
+            ("/index.html", "localhost"),
+            ("/missing.html", "www.example.com"),
+        ] {
+            let res = req_handler
+                .handle(&mut request(uri, host), remote_addr)
+                .await
+                .unwrap();
+            hyper::body::to_bytes(res.into_body()).await.unwrap();
+        }
+
+        let res = req_handler
+            .handle(&mut request("/metrics", "localhost"), remote_addr)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()["content-type"], "text/plain; charset=utf-8");
+
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body
+            .contains(r#"sws_http_requests_total{method="GET",status="200",vhost="default"} 1"#));
+        assert!(body.contains(
+            r#"sws_http_requests_total{method="GET",status="404",vhost="www.example.com"} 1"#
+        ));
+        assert!(body.contains(
+            r#"sws_http_request_duration_seconds_count{method="GET",vhost="default"} 1"#
+        ));
+    }
+}
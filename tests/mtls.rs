@@ -0,0 +1,42 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(all(test, feature = "http2"))]
+mod tests {
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::mtls::ClientCert;
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn client_cert_rules_by_path() {
+        let opts = fixture_settings("toml/mtls.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        let cases = [
+            ("/index.html", None, 200),
+            ("/assets/main.js", None, 403),
+            ("/assets/main.js", Some("CN=bob"), 403),
+            ("/assets/main.js", Some("CN=alice"), 200),
+            // Rewritten into a protected path
+            ("/pub/main.js", None, 403),
+            ("/pub/main.js", Some("CN=alice"), 200),
+        ];
+        for (uri, subject, status) in cases {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            if let Some(subject) = subject {
+                req.extensions_mut().insert(ClientCert {
+                    subject: subject.to_owned(),
+                });
+            }
+            let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+            assert_eq!(res.status(), status, "{uri} {subject:?}");
+        }
+    }
+}
@@ -0,0 +1,52 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(test)]
+mod tests {
+    use hyper::Request;
+    use std::net::SocketAddr;
+
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn request_id_is_echoed() {
+        let opts = fixture_settings("toml/request_id.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        let mut req = Request::default();
+        *req.uri_mut() = "http://localhost/".parse().unwrap();
+        req.headers_mut()
+            .insert("x-request-id", "upstream-id-1".parse().unwrap());
+
+        let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()["x-request-id"], "upstream-id-1");
+    }
+
+    #[tokio::test]
+    async fn request_id_is_included_in_error_pages() {
+        let opts = fixture_settings("toml/request_id.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some("192.168.1.1:1234".parse::<SocketAddr>().unwrap());
+
+        let mut req = Request::default();
+        *req.uri_mut() = "http://localhost/not-found".parse().unwrap();
+        // Not a trusted proxy
+        req.headers_mut()
+            .insert("x-request-id", "upstream-id-1".parse().unwrap());
+
+        let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(res.status(), 404);
+        let id = res.headers()["x-request-id"].to_str().unwrap().to_owned();
+        assert_ne!(id, "upstream-id-1");
+
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(&format!("<p>Request ID: {id}</p>")), "{body}");
+    }
+}
@@ -15,7 +15,15 @@ mod tests {
         let root = settings.general.unwrap().root.unwrap();
         assert_eq!(root, PathBuf::from("docker/public"));
 
-        let virtual_hosts = settings.advanced.unwrap().virtual_hosts.unwrap();
+        let advanced = settings.advanced.unwrap();
+        let listeners = advanced.listeners.unwrap();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].host.as_deref(), Some("::"));
+        assert_eq!(listeners[0].ipv6_only, Some(true));
+        assert_eq!(listeners[1].port, Some(8788));
+        assert!(listeners[1].fd.is_none());
+
+        let virtual_hosts = advanced.virtual_hosts.unwrap();
         let expected_roots = [PathBuf::from("docker"), PathBuf::from("docker/abc")];
         for vhost in virtual_hosts {
             if let Some(other_root) = &vhost.root {
@@ -0,0 +1,72 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use std::net::SocketAddr;
+
+    use static_web_server::signed_urls::{sign_url, unix_time};
+    use static_web_server::testing::fixtures::{
+        fixture_req_handler, fixture_settings, REMOTE_ADDR,
+    };
+
+    #[tokio::test]
+    async fn signed_urls_required_in_scope() {
+        let opts = fixture_settings("toml/signed_urls.toml");
+        let req_handler = fixture_req_handler(opts.general, opts.advanced);
+        let remote_addr = Some(REMOTE_ADDR.parse::<SocketAddr>().unwrap());
+
+        let now = unix_time();
+        let client_ip = "203.0.113.7".parse().ok();
+        let valid = sign_url("s3cr3t", "/assets/main.js", now + 60, None, None).unwrap();
+        let expired = sign_url("s3cr3t", "/assets/main.js", now - 60, None, None).unwrap();
+        let other_key = sign_url("other", "/assets/main.js", now + 60, None, None).unwrap();
+        let prefix = sign_url(
+            "s3cr3t",
+            "/assets/main.js",
+            now + 60,
+            None,
+            Some("/assets/"),
+        )
+        .unwrap()
+        .replace("main.js", "main.css");
+        let dot_segment = format!("/.{valid}");
+        let bound = sign_url("s3cr3t", "/assets/main.js", now + 60, client_ip, None).unwrap();
+        let versioned = sign_url("s3cr3t", "/assets/v1/main.js", now + 60, None, None).unwrap();
+        let public = sign_url("s3cr3t", "/pub/main.js", now + 60, None, None).unwrap();
+
+        let cases = [
+            ("/index.html", None, 200),
+            ("/assets/main.js", None, 403),
+            ("//assets/main.js", None, 403),
+            ("/./assets/main.js", None, 403),
+            ("/%61ssets/main.js", None, 403),
+            ("/index/../assets/main.js", None, 400),
+            (valid.as_str(), None, 200),
+            (expired.as_str(), None, 403),
+            (other_key.as_str(), None, 403),
+            (dot_segment.as_str(), None, 200),
+            (prefix.as_str(), None, 200),
+            (bound.as_str(), Some("203.0.113.7"), 200),
+            (bound.as_str(), Some("203.0.113.8"), 403),
+            (bound.as_str(), None, 403),
+            // Rewritten into a signed path
+            ("/pub/main.js", None, 403),
+            (public.as_str(), None, 403),
+            // Rewritten once verified
+            (versioned.as_str(), None, 200),
+        ];
+        for (uri, forwarded_for, status) in cases {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            if let Some(ip) = forwarded_for {
+                req.headers_mut()
+                    .insert("x-forwarded-for", ip.parse().unwrap());
+            }
+            let res = req_handler.handle(&mut req, remote_addr).await.unwrap();
+            assert_eq!(res.status(), status, "{uri} {forwarded_for:?}");
+        }
+    }
+}
@@ -22,6 +22,7 @@ mod tests {
 
     #[cfg(feature = "directory-listing")]
     use static_web_server::directory_listing::DirListFmt;
+    use static_web_server::etag::ETagMode;
     use static_web_server::static_files::{self, HandleOpts};
 
     fn root_dir() -> PathBuf {
@@ -59,6 +60,7 @@ mod tests {
             compression_static: false,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -105,6 +107,7 @@ mod tests {
             compression_static: false,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -152,6 +155,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -186,6 +190,7 @@ mod tests {
             compression_static: false,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -222,6 +227,7 @@ mod tests {
             compression_static: false,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -257,6 +263,7 @@ mod tests {
             compression_static: false,
             ignore_hidden_files: false,
             disable_symlinks: false,
+            etag: ETagMode::Weak,
             index_files: &[],
         })
         .await
@@ -297,6 +304,7 @@ mod tests {
                     compression_static: false,
                     ignore_hidden_files: false,
                     disable_symlinks: false,
+                    etag: ETagMode::Weak,
                     index_files: &[],
                 })
                 .await
@@ -352,6 +360,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -389,6 +398,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -428,6 +438,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -468,6 +479,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -511,6 +523,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -552,6 +565,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -591,6 +605,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -629,6 +644,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -650,6 +666,275 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn handle_etag_if_none_match() {
+        for etag_mode in [ETagMode::Weak, ETagMode::Strong] {
+            for method in [Method::HEAD, Method::GET] {
+                let res1 = match static_files::handle(&HandleOpts {
+                    method: &method,
+                    headers: &HeaderMap::new(),
+                    base_path: &root_dir(),
+                    uri_path: "index.html",
+                    uri_query: None,
+                    #[cfg(feature = "experimental")]
+                    memory_cache: None,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing: false,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_order: 6,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_format: &DirListFmt::Html,
+                    redirect_trailing_slash: true,
+                    compression_static: false,
+                    ignore_hidden_files: false,
+                    disable_symlinks: false,
+                    etag: etag_mode,
+                    index_files: &[],
+                })
+                .await
+                {
+                    Ok(result) => {
+                        let res = result.resp;
+                        assert_eq!(res.status(), 200);
+                        res
+                    }
+                    Err(_) => {
+                        panic!("expected a status 200 but not a status error")
+                    }
+                };
+
+                let etag = res1.headers()["etag"].to_owned();
+                match etag_mode {
+                    ETagMode::Weak => assert!(etag.to_str().unwrap().starts_with("W/\"")),
+                    _ => assert!(etag.to_str().unwrap().starts_with('"')),
+                }
+
+                // matching `if-none-match`
+                let mut headers = HeaderMap::new();
+                headers.insert("if-none-match", etag.clone());
+
+                match static_files::handle(&HandleOpts {
+                    method: &method,
+                    headers: &headers,
+                    base_path: &root_dir(),
+                    uri_path: "index.html",
+                    uri_query: None,
+                    #[cfg(feature = "experimental")]
+                    memory_cache: None,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing: false,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_order: 6,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_format: &DirListFmt::Html,
+                    redirect_trailing_slash: true,
+                    compression_static: false,
+                    ignore_hidden_files: false,
+                    disable_symlinks: false,
+                    etag: etag_mode,
+                    index_files: &[],
+                })
+                .await
+                {
+                    Ok(result) => {
+                        let mut res = result.resp;
+                        assert_eq!(res.status(), 304);
+                        assert_eq!(res.headers()["etag"], etag);
+                        let body = hyper::body::to_bytes(res.body_mut())
+                            .await
+                            .expect("unexpected bytes error during `body` conversion");
+                        assert_eq!(body, "");
+                    }
+                    Err(_) => {
+                        panic!("expected a status 304 but not a status error")
+                    }
+                }
+
+                // `if-none-match` takes precedence over `if-modified-since`
+                let mut headers = HeaderMap::new();
+                headers.insert("if-none-match", "\"other\", W/\"tags\"".parse().unwrap());
+                headers.insert(
+                    "if-modified-since",
+                    res1.headers()["last-modified"].to_owned(),
+                );
+
+                match static_files::handle(&HandleOpts {
+                    method: &method,
+                    headers: &headers,
+                    base_path: &root_dir(),
+                    uri_path: "index.html",
+                    uri_query: None,
+                    #[cfg(feature = "experimental")]
+                    memory_cache: None,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing: false,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_order: 6,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_format: &DirListFmt::Html,
+                    redirect_trailing_slash: true,
+                    compression_static: false,
+                    ignore_hidden_files: false,
+                    disable_symlinks: false,
+                    etag: etag_mode,
+                    index_files: &[],
+                })
+                .await
+                {
+                    Ok(result) => {
+                        let res = result.resp;
+                        assert_eq!(res.status(), 200);
+                        assert_eq!(res.headers()["etag"], etag);
+                    }
+                    Err(_) => {
+                        panic!("expected a status 200 but not a status error")
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_etag_if_match() {
+        for method in [Method::HEAD, Method::GET] {
+            let res1 = match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &HeaderMap::new(),
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Strong,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => result.resp,
+                Err(_) => {
+                    panic!("expected a status 200 but not a status error")
+                }
+            };
+
+            // matching `if-match` takes precedence over `if-unmodified-since`
+            let mut headers = HeaderMap::new();
+            headers.insert("if-match", res1.headers()["etag"].to_owned());
+            headers.insert(
+                "if-unmodified-since",
+                "Mon, 18 Nov 1974 00:00:00 GMT".parse().unwrap(),
+            );
+
+            match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &headers,
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Strong,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => assert_eq!(result.resp.status(), 200),
+                Err(_) => {
+                    panic!("expected a status 200 but not a status error")
+                }
+            }
+
+            // non-matching `if-match`
+            let mut headers = HeaderMap::new();
+            headers.insert("if-match", "\"other\"".parse().unwrap());
+
+            match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &headers,
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Strong,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => assert_eq!(result.resp.status(), 412),
+                Err(_) => {
+                    panic!("expected a status 412 but not a status error")
+                }
+            }
+
+            // `if-match: *` passes for an existing file even without entity tags
+            let mut headers = HeaderMap::new();
+            headers.insert("if-match", "*".parse().unwrap());
+
+            match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &headers,
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Off,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => {
+                    let res = result.resp;
+                    assert_eq!(res.status(), 200);
+                    assert!(res.headers().get("etag").is_none());
+                }
+                Err(_) => {
+                    panic!("expected a status 200 but not a status error")
+                }
+            }
+        }
+    }
+
     #[tokio::test]
     async fn handle_file_allowed_disallowed_methods() {
         for method in METHODS {
@@ -671,6 +956,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -756,6 +1042,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -829,6 +1116,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -881,6 +1169,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -905,6 +1194,188 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn handle_byte_ranges_multipart() {
+        let mut headers = HeaderMap::new();
+        headers.insert("range", "bytes=300-399,0-9".parse().unwrap());
+
+        let buf = fs::read(root_dir().join("index.html"))
+            .expect("unexpected error during index.html reading");
+        let buf = Bytes::from(buf);
+
+        for method in [Method::HEAD, Method::GET] {
+            match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &headers,
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Weak,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => {
+                    let mut res = result.resp;
+                    assert_eq!(res.status(), 206);
+                    assert!(res.headers().get("content-range").is_none());
+
+                    let content_type = res.headers()["content-type"].to_str().unwrap();
+                    let boundary = content_type
+                        .strip_prefix("multipart/byteranges; boundary=")
+                        .expect("unexpected multipart content type")
+                        .to_owned();
+
+                    let body = hyper::body::to_bytes(res.body_mut())
+                        .await
+                        .expect("unexpected bytes error during `body` conversion");
+                    assert_eq!(
+                        res.headers()["content-length"],
+                        body.len().to_string().as_str()
+                    );
+
+                    // ranges are sent in ascending order
+                    let mut expected = Vec::new();
+                    for (i, (start, end)) in [(0, 9), (300, 399)].into_iter().enumerate() {
+                        if i > 0 {
+                            expected.extend_from_slice(b"\r\n");
+                        }
+                        expected.extend_from_slice(
+                            format!(
+                                "--{boundary}\r\nContent-Type: text/html\r\nContent-Range: bytes {start}-{end}/{}\r\n\r\n",
+                                buf.len()
+                            )
+                            .as_bytes(),
+                        );
+                        expected.extend_from_slice(&buf[start..=end]);
+                    }
+                    expected.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+                    assert_eq!(body, expected);
+                }
+                Err(_) => {
+                    panic!("expected a normal response rather than a status error")
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_byte_ranges_overlapping_coalesced() {
+        let mut headers = HeaderMap::new();
+        headers.insert("range", "bytes=150-200,100-160,201-210".parse().unwrap());
+
+        let buf = fs::read(root_dir().join("index.html"))
+            .expect("unexpected error during index.html reading");
+        let buf = Bytes::from(buf);
+
+        for method in [Method::HEAD, Method::GET] {
+            match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &headers,
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Weak,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => {
+                    let mut res = result.resp;
+                    assert_eq!(res.status(), 206);
+                    assert_eq!(
+                        res.headers()["content-range"],
+                        format!("bytes 100-210/{}", buf.len())
+                    );
+                    assert_eq!(res.headers()["content-length"], "111");
+                    let body = hyper::body::to_bytes(res.body_mut())
+                        .await
+                        .expect("unexpected bytes error during `body` conversion");
+                    assert_eq!(body, &buf[100..=210]);
+                }
+                Err(_) => {
+                    panic!("expected a normal response rather than a status error")
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_byte_ranges_too_many() {
+        let ranges = (0..101)
+            .map(|i| format!("{}-{}", i * 2, i * 2))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut headers = HeaderMap::new();
+        headers.insert("range", format!("bytes={ranges}").parse().unwrap());
+
+        let buf = fs::read(root_dir().join("index.html"))
+            .expect("unexpected error during index.html reading");
+
+        for method in [Method::HEAD, Method::GET] {
+            match static_files::handle(&HandleOpts {
+                method: &method,
+                headers: &headers,
+                base_path: &root_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "experimental")]
+                memory_cache: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+                disable_symlinks: false,
+                etag: ETagMode::Weak,
+                index_files: &[],
+            })
+            .await
+            {
+                Ok(result) => {
+                    let res = result.resp;
+                    assert_eq!(res.status(), 200);
+                    assert!(res.headers().get("content-range").is_none());
+                    assert_eq!(
+                        res.headers()["content-length"],
+                        buf.len().to_string().as_str()
+                    );
+                }
+                Err(_) => {
+                    panic!("expected a normal response rather than a status error")
+                }
+            }
+        }
+    }
+
     #[tokio::test]
     async fn handle_byte_ranges_out_of_range() {
         let mut headers = HeaderMap::new();
@@ -933,6 +1404,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -986,6 +1458,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1031,6 +1504,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1086,6 +1560,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1138,6 +1613,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1190,6 +1666,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1245,6 +1722,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1290,6 +1768,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1334,6 +1813,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1393,6 +1873,7 @@ mod tests {
                 compression_static: false,
                 ignore_hidden_files: false,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1444,6 +1925,7 @@ mod tests {
                 compression_static: true,
                 ignore_hidden_files: true,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &[],
             })
             .await
@@ -1486,6 +1968,7 @@ mod tests {
                 compression_static: true,
                 ignore_hidden_files: true,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &["index.html", "index.htm"],
             })
             .await
@@ -1530,6 +2013,7 @@ mod tests {
                 compression_static: true,
                 ignore_hidden_files: true,
                 disable_symlinks: true,
+                etag: ETagMode::Weak,
                 index_files: &["index.html", "index.htm"],
             })
             .await
@@ -1564,6 +2048,7 @@ mod tests {
                 compression_static: true,
                 ignore_hidden_files: true,
                 disable_symlinks: false,
+                etag: ETagMode::Weak,
                 index_files: &["index.html", "index.htm"],
             })
             .await